@@ -1,3 +1,8 @@
+mod parser;
+
+use std::fmt;
+
+use parser::{parse, MAX_DEPTH};
 
 fn main() {
     // 1: wovel or consonant
@@ -29,7 +34,7 @@ fn main() {
     );
     
     match evaluate(&expr) {
-        11 => println!("Correct!"),
+        Ok(11) => println!("Correct!"),
         _ => println!("Incorrect!"),
     }
 
@@ -39,10 +44,75 @@ fn main() {
     );
 
     match evaluate(&expr) {
-        25 => println!("Correct!"),
+        Ok(25) => println!("Correct!"),
         _ => println!("Incorrect!"),
     }
 
+    // The same expressions, parsed from text
+    let expr = parse("2 * 3 + 5").unwrap();
+    assert_eq!(evaluate(&expr), Ok(11));
+    let expr = parse("(2 + 3) * 5").unwrap();
+    assert_eq!(evaluate(&expr), Ok(25));
+    println!("{} = {}", expr, evaluate(&expr).unwrap()); // (2 + 3) * 5 = 25
+    assert_eq!(parse(" 2*(3+5) ").unwrap().to_string(), "2 * (3 + 5)");
+
+    for text in ["2 * (3 + 5", "2 + * 3", "2 3", "", "7 $ 1", "99999999999"] {
+        let err = parse(text).unwrap_err();
+        println!("{:?}: {}", text, err);
+    }
+    let err = parse("2 * (3 + 5").unwrap_err();
+    assert_eq!((err.offset, err.expected), (10, "an operator or ')'"));
+    let err = parse("2 + * 3").unwrap_err();
+    assert_eq!((err.offset, err.found.as_str()), (4, "'*'"));
+
+    // The other operators, and what can go wrong when evaluating
+    for (text, value) in [
+        ("10 - 2 - 3", Ok(5)),
+        ("7 / 2 * 2 + 7 % 2", Ok(7)),
+        ("-7 / 2", Ok(-3)),
+        ("-7 % 3", Ok(-1)),
+        ("2 ^ 3 ^ 2", Ok(512)),
+        ("-2 ^ 2", Ok(-4)),
+        ("(-2) ^ 2", Ok(4)),
+        ("-2 * 2 ^ 2", Ok(-8)),
+        ("2 ^ -1 ^ 2", Err(EvalError::NegativeExponent(-1))),
+        ("2 - -3", Ok(5)),
+        ("2147483647 * 2147483647", Ok(4611686014132420609)),
+        ("0 ^ 0 + (-1) ^ 3", Ok(0)),
+        ("1 / (2 - 2)", Err(EvalError::DivisionByZero)),
+        ("5 % 0", Err(EvalError::DivisionByZero)),
+        ("2 ^ 63", Err(EvalError::Overflow)),
+        ("-2147483648 * -2147483648 * 2", Err(EvalError::Overflow)),
+        ("-2 ^ 63 / -1", Err(EvalError::Overflow)),
+        ("(2 ^ 62 - 1 + 2 ^ 62) * 2", Err(EvalError::Overflow)),
+        ("2 ^ (1 - 2)", Err(EvalError::NegativeExponent(-1))),
+    ] {
+        let expr = parse(text).unwrap();
+        match evaluate(&expr) {
+            Ok(n) => println!("{} = {}", expr, n),
+            Err(err) => println!("{}: {}", expr, err),
+        }
+        assert_eq!(evaluate(&expr), value, "{}", text);
+    }
+    assert_eq!(parse("-2147483648"), Ok(Number(i32::MIN)));
+    assert_eq!(parse("- 3"), Ok(Neg(Box::new(Number(3)))));
+    assert_eq!(parse("-2 ^ 2"), parse("- 2 ^ 2"));
+    assert_eq!(parse("(-2) ^ 2").unwrap().to_string(), "(-2) ^ 2");
+
+    // Nesting is limited, so that deep input is an error instead of a crash
+    let deep = format!("{}1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+    assert_eq!(parse(&deep).map(|expr| evaluate(&expr)), Ok(Ok(1)));
+    let err = parse(&"(".repeat(100_000)).unwrap_err();
+    assert_eq!((err.offset, err.found.as_str()), (MAX_DEPTH, "'('"));
+    let err = parse(&format!("{}1", "-".repeat(100_000))).unwrap_err();
+    assert_eq!((err.offset, err.found.as_str()), (MAX_DEPTH, "'-'"));
+    let err = parse(&format!("1{}", " ^ 1".repeat(100_000))).unwrap_err();
+    assert_eq!(err.offset, 4 * MAX_DEPTH + 2);
+    let err = parse(&format!("1{}", " - 1".repeat(200_000))).unwrap_err();
+    assert_eq!((err.offset, err.found.as_str()), (4 * MAX_DEPTH + 2, "'-'"));
+    let long = format!("1{}", " - 1".repeat(MAX_DEPTH));
+    assert_eq!(parse(&long).map(|expr| evaluate(&expr)), Ok(Ok(1 - MAX_DEPTH as i64)));
+
     // 5: Rotate slices in-place
     let mut arr = [1, 2, 3, 4, 5];
     rotate_in_place(&mut arr, 2);
@@ -93,20 +163,80 @@ fn two_sum(nums: &[i32], target: i32) -> Option<(usize, usize)> {
 }
 
 // 4: Mini expression evaluator
+#[derive(Debug, PartialEq)]
 enum Expr {
     Number(i32),
+    Neg(Box<Expr>),
     Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
     Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Rem(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+enum EvalError {
+    DivisionByZero,
+    // The result, or a step on the way, does not fit in an i64
+    Overflow,
+    NegativeExponent(i64),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Overflow => write!(f, "overflow"),
+            EvalError::NegativeExponent(n) => write!(f, "negative exponent {}", n),
+        }
+    }
 }
 
-fn evaluate(expr: &Expr) -> i32 {
+impl std::error::Error for EvalError {}
+
+// Division rounds toward zero and the remainder has the sign of the left
+// operand, as with Rust's `/` and `%`
+fn evaluate(expr: &Expr) -> Result<i64, EvalError> {
+    let checked = |result: Option<i64>| result.ok_or(EvalError::Overflow);
     match expr {
-        Expr::Number(n) => *n,
-        Expr::Add(left, right) => evaluate(left) + evaluate(right),
-        Expr::Mul(left, right) => evaluate(left) * evaluate(right),
+        Expr::Number(n) => Ok(i64::from(*n)),
+        Expr::Neg(operand) => checked(evaluate(operand)?.checked_neg()),
+        Expr::Add(left, right) => checked(evaluate(left)?.checked_add(evaluate(right)?)),
+        Expr::Sub(left, right) => checked(evaluate(left)?.checked_sub(evaluate(right)?)),
+        Expr::Mul(left, right) => checked(evaluate(left)?.checked_mul(evaluate(right)?)),
+        Expr::Div(left, right) => {
+            let (left, right) = (evaluate(left)?, evaluate(right)?);
+            if right == 0 {
+                return Err(EvalError::DivisionByZero);
+            }
+            checked(left.checked_div(right))
+        }
+        Expr::Rem(left, right) => {
+            let (left, right) = (evaluate(left)?, evaluate(right)?);
+            if right == 0 {
+                return Err(EvalError::DivisionByZero);
+            }
+            checked(left.checked_rem(right))
+        }
+        Expr::Pow(base, exponent) => power(evaluate(base)?, evaluate(exponent)?),
     }
 }
 
+fn power(base: i64, exponent: i64) -> Result<i64, EvalError> {
+    if exponent < 0 {
+        return Err(EvalError::NegativeExponent(exponent));
+    }
+    match base {
+        // The only bases whose powers fit whatever the exponent
+        0 | 1 => Ok(if exponent == 0 { 1 } else { base }),
+        -1 => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+        _ => u32::try_from(exponent)
+            .ok()
+            .and_then(|exponent| base.checked_pow(exponent))
+            .ok_or(EvalError::Overflow),
+    }
+}
 
 // 5: Rotate slices in-place
 fn rotate_in_place(data: &mut [i32], k: usize) {
@@ -121,3 +251,45 @@ fn rotate_in_place(data: &mut [i32], k: usize) {
     // Reverse the remaining len-k elements
     data[k..].reverse();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Printing any expression and parsing it gives back the same tree, and
+    // evaluating it never panics
+    #[test]
+    fn print_then_parse_round_trips() {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..1000 {
+            let expr = random_expr(&mut seed, 6);
+            let _ = evaluate(&expr);
+            assert_eq!(parse(&expr.to_string()), Ok(expr));
+        }
+    }
+
+    // A random expression at most `depth` levels deep, for checking the parser
+    fn random_expr(seed: &mut u64, depth: u32) -> Expr {
+        // xorshift64
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        match *seed % 8 {
+            _ if depth == 0 => Expr::Number((*seed >> 8) as i32),
+            0 => Expr::Number((*seed >> 8) as i32 % 10),
+            1 => Expr::Neg(Box::new(random_expr(seed, depth - 1))),
+            kind => {
+                let left = Box::new(random_expr(seed, depth - 1));
+                let right = Box::new(random_expr(seed, depth - 1));
+                match kind {
+                    2 => Expr::Add(left, right),
+                    3 => Expr::Sub(left, right),
+                    4 => Expr::Mul(left, right),
+                    5 => Expr::Div(left, right),
+                    6 => Expr::Rem(left, right),
+                    _ => Expr::Pow(left, right),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Reading and writing `Expr` as text such as `2 * (3 + 5)`.
+//!
+//! The parser climbs precedence levels, from the loosest:
+//!
+//! | operators     | grouping |
+//! |---------------|----------|
+//! | `+` `-`       | left     |
+//! | `*` `/` `%`   | left     |
+//! | prefix `-`    |          |
+//! | `^`           | right    |
+//!
+//! Powers bind tightest, so `-2 ^ 2` is -4, as in ordinary arithmetic. A `-`
+//! right before digits is part of the number unless a `^` follows: `-3` is
+//! `Number(-3)`, while `-(3)` and `- 3` are `Neg(Number(3))`, and `-2 ^ 2`
+//! and `- 2 ^ 2` are both `Neg(Pow(2, 2))`.
+//!
+//! Parentheses and negations may be nested, and operators chained, at most
+//! `MAX_DEPTH` deep. Every operator of a chain such as `1 - 1 - 1` counts,
+//! so that no text gives a tree deep enough to overflow the stack when it
+//! is parsed, evaluated, printed or dropped.
+//!
+//! Printing uses the same table and only adds the parentheses the parser
+//! needs, so printing an expression and parsing the result gives back the
+//! same tree.
+
+use std::fmt;
+
+use crate::Expr;
+
+/// Why a text is not an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the token that was not expected.
+    pub offset: usize,
+    /// What could have come there.
+    pub expected: &'static str,
+    /// What came instead, as written, or "end of input".
+    pub found: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at byte {}: expected {}, found {}",
+            self.offset, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Number,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    LParen,
+    RParen,
+    End,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    offset: usize,
+    text: &'a str,
+}
+
+impl Token<'_> {
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        let found = match self.kind {
+            TokenKind::End => "end of input".to_string(),
+            _ => format!("'{}'", self.text),
+        };
+        ParseError {
+            offset: self.offset,
+            expected,
+            found,
+        }
+    }
+}
+
+/// How deeply parentheses, negations and operators may nest.
+pub const MAX_DEPTH: usize = 256;
+
+/// How tightly prefix `-` binds: tighter than any binary operator but `^`.
+const PREFIX_PRECEDENCE: u8 = 3;
+
+/// A binary operator: how tightly it binds, and how to build its node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl BinOp {
+    fn of(kind: TokenKind) -> Option<BinOp> {
+        match kind {
+            TokenKind::Plus => Some(BinOp::Add),
+            TokenKind::Minus => Some(BinOp::Sub),
+            TokenKind::Star => Some(BinOp::Mul),
+            TokenKind::Slash => Some(BinOp::Div),
+            TokenKind::Percent => Some(BinOp::Rem),
+            TokenKind::Caret => Some(BinOp::Pow),
+            _ => None,
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 2,
+            BinOp::Pow => 4,
+        }
+    }
+
+    /// The lowest precedence the left and right operands may have without
+    /// parentheses.
+    fn operand_precedences(self) -> (u8, u8) {
+        let p = self.precedence();
+        match self {
+            BinOp::Pow => (p + 1, p),
+            _ => (p, p + 1),
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Pow => "^",
+        }
+    }
+
+    fn build(self, left: Expr, right: Expr) -> Expr {
+        let (left, right) = (Box::new(left), Box::new(right));
+        match self {
+            BinOp::Add => Expr::Add(left, right),
+            BinOp::Sub => Expr::Sub(left, right),
+            BinOp::Mul => Expr::Mul(left, right),
+            BinOp::Div => Expr::Div(left, right),
+            BinOp::Rem => Expr::Rem(left, right),
+            BinOp::Pow => Expr::Pow(left, right),
+        }
+    }
+}
+
+/// Splits `text` into tokens, ending with an `End` token at its length.
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '^' => TokenKind::Caret,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '0'..='9' => {
+                let mut end = offset + 1;
+                while let Some((i, _)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = i + 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number,
+                    offset,
+                    text: &text[offset..end],
+                });
+                continue;
+            }
+            _ => {
+                return Err(ParseError {
+                    offset,
+                    expected: "a number, an operator or a parenthesis",
+                    found: format!("'{}'", c),
+                })
+            }
+        };
+        tokens.push(Token {
+            kind,
+            offset,
+            text: &text[offset..offset + c.len_utf8()],
+        });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        offset: text.len(),
+        text: "",
+    });
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    /// How many levels deep the parser is.
+    depth: usize,
+}
+
+/// A parsed expression, with the height of its tree.
+type Parsed = (Expr, usize);
+
+/// Returns the height of a node over children at most `height` high, or
+/// fails at `token`, which built the node, if that goes past `MAX_DEPTH`.
+fn taller(token: Token<'_>, height: usize) -> Result<usize, ParseError> {
+    if height == MAX_DEPTH {
+        return Err(token.unexpected("an expression nested less deeply"));
+    }
+    Ok(height + 1)
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Token<'a> {
+        self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Token<'a> {
+        let token = self.peek();
+        // The last token, `End`, is never consumed.
+        if token.kind != TokenKind::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    /// Runs `parse` one level deeper, or fails at `token`, which opened the
+    /// level, if that goes past `MAX_DEPTH`.
+    fn nested(
+        &mut self,
+        token: Token<'a>,
+        parse: impl FnOnce(&mut Self) -> Result<Parsed, ParseError>,
+    ) -> Result<Parsed, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(token.unexpected("an expression nested less deeply"));
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    /// Parses operands joined by operators that bind at least as tightly as
+    /// `min_precedence`.
+    fn expression(&mut self, min_precedence: u8) -> Result<Parsed, ParseError> {
+        let (mut left, mut height) = self.operand()?;
+        while let Some(op) = BinOp::of(self.peek().kind) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            let token = self.advance();
+            // For operators that group to the left, the right operand only
+            // takes operators that bind tighter.
+            let (right, right_height) =
+                self.nested(token, |p| p.expression(op.operand_precedences().1))?;
+            height = taller(token, height.max(right_height))?;
+            left = op.build(left, right);
+        }
+        Ok((left, height))
+    }
+
+    fn operand(&mut self) -> Result<Parsed, ParseError> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Number => Ok((number(token.text, token.offset)?, 0)),
+            TokenKind::Minus => {
+                let next = self.peek();
+                let power = self
+                    .tokens
+                    .get(self.next + 1)
+                    .is_some_and(|t| t.kind == TokenKind::Caret);
+                if next.kind == TokenKind::Number && next.offset == token.offset + 1 && !power {
+                    self.advance();
+                    return Ok((number(&format!("-{}", next.text), token.offset)?, 0));
+                }
+                let (operand, height) = self.nested(token, |p| p.expression(PREFIX_PRECEDENCE))?;
+                Ok((Expr::Neg(Box::new(operand)), taller(token, height)?))
+            }
+            TokenKind::LParen => {
+                let inner = self.nested(token, |p| p.expression(0))?;
+                let close = self.advance();
+                match close.kind {
+                    TokenKind::RParen => Ok(inner),
+                    _ => Err(close.unexpected("an operator or ')'")),
+                }
+            }
+            _ => Err(token.unexpected("a number, '-' or '('")),
+        }
+    }
+}
+
+fn number(text: &str, offset: usize) -> Result<Expr, ParseError> {
+    text.parse().map(Expr::Number).map_err(|_| ParseError {
+        offset,
+        expected: "a number that fits in 32 bits",
+        found: format!("'{}'", text),
+    })
+}
+
+/// Parses `text` as one expression.
+pub fn parse(text: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        next: 0,
+        depth: 0,
+    };
+    let (expr, _) = parser.expression(0)?;
+    let rest = parser.peek();
+    match rest.kind {
+        TokenKind::End => Ok(expr),
+        _ => Err(rest.unexpected("an operator or end of input")),
+    }
+}
+
+impl Expr {
+    /// Writes the expression, in parentheses if it binds less tightly than
+    /// `min_precedence`.
+    fn write(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        // A leading `-` would take in a `^` that follows, so a negation or
+        // negative number on the left of `^` needs parentheses.
+        let prefixed = match self {
+            Expr::Number(n) => *n < 0,
+            Expr::Neg(_) => true,
+            _ => false,
+        };
+        if prefixed && min_precedence > BinOp::Pow.precedence() {
+            write!(f, "(")?;
+            self.write(f, 0)?;
+            return write!(f, ")");
+        }
+        let (op, left, right) = match self {
+            Expr::Number(n) => return write!(f, "{}", n),
+            Expr::Neg(operand) => {
+                write!(f, "-")?;
+                return match **operand {
+                    // `-3` would read back as a number.
+                    Expr::Number(n) if n >= 0 => write!(f, "({})", n),
+                    _ => operand.write(f, PREFIX_PRECEDENCE),
+                };
+            }
+            Expr::Add(left, right) => (BinOp::Add, left, right),
+            Expr::Sub(left, right) => (BinOp::Sub, left, right),
+            Expr::Mul(left, right) => (BinOp::Mul, left, right),
+            Expr::Div(left, right) => (BinOp::Div, left, right),
+            Expr::Rem(left, right) => (BinOp::Rem, left, right),
+            Expr::Pow(left, right) => (BinOp::Pow, left, right),
+        };
+        let parenthesized = op.precedence() < min_precedence;
+        if parenthesized {
+            write!(f, "(")?;
+        }
+        let (left_precedence, right_precedence) = op.operand_precedences();
+        left.write(f, left_precedence)?;
+        write!(f, " {} ", op.symbol())?;
+        right.write(f, right_precedence)?;
+        if parenthesized {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
@@ -1,3 +1,5 @@
+use exercises_day_5::to_do_list::ToDoError;
+
 struct Task {
     //40 characters
     description: String,
@@ -16,13 +18,20 @@ impl ToDoList {
     }
 
     // Add a task to the list
-    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<(), &str> {
+    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<(), ToDoError> {
         if description.len() > 40 {
-            return Err("Description is too long");
+            return Err(ToDoError::DescriptionTooLong {
+                len: description.len(),
+                max: 40,
+            });
         }
 
-        if priority < 1 || priority > 5 {
-            return Err("Priority must be between 1 and 5");
+        if !(1..=5).contains(&priority) {
+            return Err(ToDoError::PriorityOutOfRange {
+                priority,
+                min: 1,
+                max: 5,
+            });
         }
 
         let new_tasl = Task {
@@ -51,25 +60,30 @@ impl ToDoList {
         }
     }
 
-    // Merge another list into this one
-    pub fn merge(&mut self, other: &ToDoList) {
+    // Merge another list into this one, returning the tasks that were rejected
+    pub fn merge(&mut self, other: &ToDoList) -> Vec<(String, ToDoError)> {
+        let mut rejected = Vec::new();
         for task in &other.list {
-            self.add_task(&task.description, task.priority).ok();
+            if let Err(err) = self.add_task(&task.description, task.priority) {
+                rejected.push((task.description.clone(), err));
+            }
         }
+        rejected
     }
 
     // Mark a task as done
-    pub fn mark_done(&mut self, description: &str) {
+    pub fn mark_done(&mut self, description: &str) -> Result<(), ToDoError> {
         for task in &mut self.list {
             if task.description == description && !task.done {
                 task.done = true;
-                break;
+                return Ok(());
             }
         }
+        Err(ToDoError::NoOpenTask(description.to_string()))
     }
 
     // Remove all done tasks
-    pub fn remove_done(&mut self) {
+    pub fn remove_done(&mut self) -> Result<(), ToDoError> {
         //self.list.retain(|task| !task.done);
         let mut i = 0;
         while i < self.list.len() {
@@ -79,5 +93,6 @@ impl ToDoList {
                 i += 1;
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,69 @@
+//! HTTP/JSON front end for `ToDoList`. See the `server` module for the
+//! endpoints. Run `todo-server --help` for usage.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use exercises_day_5::server::Server;
+use exercises_day_5::to_do_list::default_file;
+
+const DEFAULT_PORT: u16 = 7878;
+
+const USAGE: &str = "\
+Usage: todo-server [--file <path>] [--port <port>]
+
+Serves the list over HTTP on 127.0.0.1 only.
+
+Options:
+  -f, --file <path>   List file to use (default: $TODO_FILE, then ./todo.json)
+  -p, --port <port>   Port to listen on (default: 7878, 0 picks a free one)
+  -h, --help          Show this help";
+
+fn main() -> ExitCode {
+    let mut file = None;
+    let mut port = DEFAULT_PORT;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("-h" | "--help", _) => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            ("-f" | "--file", Some(value)) => file = Some(PathBuf::from(value)),
+            ("-p" | "--port", Some(value)) => match value.parse() {
+                Ok(value) => port = value,
+                Err(_) => return usage(&format!("invalid port '{}'", value)),
+            },
+            ("-f" | "--file" | "-p" | "--port", None) => {
+                return usage(&format!("option '{}' needs a value", arg))
+            }
+            _ => return usage(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+    let file = file.unwrap_or_else(default_file);
+
+    let server = match Server::bind(port, &file) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("todo-server: cannot start on {}: {}", file.display(), err);
+            return ExitCode::from(1);
+        }
+    };
+    match server.local_addr() {
+        Ok(addr) => println!("Serving {} on http://{}", file.display(), addr),
+        Err(err) => eprintln!("todo-server: {}", err),
+    }
+    match server.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("todo-server: {}", err);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("todo-server: {}\n\n{}", message, USAGE);
+    ExitCode::from(2)
+}
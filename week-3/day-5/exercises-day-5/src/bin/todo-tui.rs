@@ -0,0 +1,141 @@
+//! Full-screen terminal front end for `ToDoList`. The screen itself is drawn
+//! by the `tui` module; this binary only talks to the terminal.
+//!
+//! Usage: `todo-tui [--file <path>]`. `q`, `Esc` and `Ctrl+C` all leave the
+//! list view, and each of them saves the list if anything changed; while
+//! editing, `Esc` and `Ctrl+C` only cancel the edit.
+
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, execute, queue, terminal};
+
+use exercises_day_5::to_do_list::{default_file, ToDoList};
+use exercises_day_5::tui::{App, Key};
+
+/// Puts the terminal in full-screen mode and restores it when dropped, even
+/// if the UI fails.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let file = match (args.next().as_deref(), args.next(), args.next()) {
+        (None, _, _) => default_file(),
+        (Some("-f" | "--file"), Some(path), None) => PathBuf::from(path),
+        _ => {
+            eprintln!("Usage: todo-tui [--file <path>]");
+            return ExitCode::from(2);
+        }
+    };
+
+    let list = match ToDoList::load_or_new(&file) {
+        Ok(list) => list,
+        Err(err) => {
+            eprintln!("todo-tui: cannot read {}: {}", file.display(), err);
+            return ExitCode::from(1);
+        }
+    };
+    let app = match run(App::new(list)) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("todo-tui: {}", err);
+            return ExitCode::from(1);
+        }
+    };
+    if app.is_changed() {
+        if let Err(err) = app.list().save(&file) {
+            eprintln!("todo-tui: cannot save {}: {}", file.display(), err);
+            return ExitCode::from(1);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run(mut app: App) -> io::Result<App> {
+    let _terminal = Terminal::enter()?;
+    let (width, height) = terminal::size()?;
+    app.resize(width.into(), height.into());
+    while !app.should_quit() {
+        draw(&app)?;
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                if let Some(key) = translate(key) {
+                    app.handle_key(key);
+                }
+            }
+            Event::Resize(width, height) => app.resize(width.into(), height.into()),
+            _ => {}
+        }
+    }
+    Ok(app)
+}
+
+fn draw(app: &App) -> io::Result<()> {
+    let screen = app.render();
+    let mut out = io::stdout().lock();
+    queue!(out, cursor::Hide)?;
+    for (index, line) in screen.lines.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, index as u16))?;
+        if screen.highlight == Some(index) {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(line))?;
+        }
+    }
+    if let Some((column, line)) = screen.cursor {
+        queue!(
+            out,
+            cursor::MoveTo(column as u16, line as u16),
+            cursor::Show
+        )?;
+    }
+    out.flush()
+}
+
+fn translate(key: KeyEvent) -> Option<Key> {
+    // The terminal is in raw mode, so Ctrl+C arrives as a key and not as a
+    // signal. It does what `Esc` does; other Ctrl keys are not plain letters.
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        return match key.code {
+            KeyCode::Char('c') => Some(Key::Esc),
+            _ => None,
+        };
+    }
+    Some(match key.code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    })
+}
@@ -0,0 +1,352 @@
+//! Command-line front end for `ToDoList`.
+//!
+//! Every invocation loads the list file, applies one subcommand and saves
+//! the result back. Run `todo --help` for usage.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use chrono::{Duration, NaiveDate, Utc};
+use exercises_day_5::to_do_list::{
+    default_file, ordering_named, Filter, Recurrence, StorageError, TaskOrdering, ToDoError,
+    ToDoList,
+};
+
+const USAGE: &str = "\
+Usage: todo [--file <path>] <command> [args]
+
+Commands:
+  add <description> <priority>   Add a task (priority 1-5, 1 is most urgent)
+  list [--format text|json]      Show all tasks
+  done <description>             Mark the first open task with this description as done
+  search <query>                 Show the tasks matching the words of the query, best first
+  done-matching <query>          Mark the open task that best matches the query as done
+  remove-done                    Delete every completed task
+  merge <file>                   Add all tasks from another list file
+  report [<from> <to>]           Show counts per priority and a burndown chart over the
+                                 days from <from> to <to> (YYYY-MM-DD, default: last 14 days)
+
+Options:
+  -f, --file <path>   List file to use (default: $TODO_FILE, then ./todo.json)
+      --filter <expr> Only act on matching tasks (list and remove-done), e.g.
+                      'tag:backend and priority<=2 and not done'
+      --sort <name>   Order of the tasks (list only): priority (default), due,
+                      created, alphabetical or urgency
+      --repeat <rule> Make the added task recur (add only), e.g.
+                      'FREQ=WEEKLY;BYDAY=MO,TH' or 'FREQ=DAILY;INTERVAL=3;X-FROM=COMPLETION'
+  -h, --help          Show this help
+
+Exit codes:
+  0  success
+  1  the command failed (bad task, unknown task, unreadable file)
+  2  invalid command line";
+
+/// Ways a command can fail, each mapped to its own exit code.
+enum Failure {
+    Usage(String),
+    Command(String),
+}
+
+impl From<StorageError> for Failure {
+    fn from(err: StorageError) -> Self {
+        Failure::Command(err.to_string())
+    }
+}
+
+impl From<ToDoError> for Failure {
+    fn from(err: ToDoError) -> Self {
+        Failure::Command(err.to_string())
+    }
+}
+
+enum Format {
+    Text,
+    Json,
+}
+
+enum Command {
+    Add {
+        description: String,
+        priority: u8,
+        repeat: Option<Recurrence>,
+    },
+    List {
+        format: Format,
+        filter: Option<Filter>,
+        sort: Option<Box<dyn TaskOrdering>>,
+    },
+    Done {
+        description: String,
+    },
+    Search {
+        query: String,
+    },
+    DoneMatching {
+        query: String,
+    },
+    RemoveDone {
+        filter: Option<Filter>,
+    },
+    Merge {
+        file: PathBuf,
+    },
+    Report {
+        days: Option<(NaiveDate, NaiveDate)>,
+    },
+}
+
+struct Args {
+    file: PathBuf,
+    command: Command,
+}
+
+fn main() -> ExitCode {
+    let result = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => run(args),
+        Ok(None) => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(msg)) => {
+            eprintln!("todo: {}\n\n{}", msg, USAGE);
+            ExitCode::from(2)
+        }
+        Err(Failure::Command(msg)) => {
+            eprintln!("todo: {}", msg);
+            ExitCode::from(1)
+        }
+    }
+}
+
+/// Parses the command line; `None` means help was requested.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Args>, Failure> {
+    let mut file = None;
+    let mut format = None;
+    let mut filter = None;
+    let mut repeat = None;
+    let mut sort = None;
+    let mut positional = Vec::new();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--file" => {
+                let value = args.next().ok_or_else(|| missing_value(&arg))?;
+                file = Some(PathBuf::from(value));
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Some(Format::Text),
+                    Some("json") => Some(Format::Json),
+                    Some(other) => {
+                        return Err(Failure::Usage(format!("unknown format '{}'", other)))
+                    }
+                    None => return Err(missing_value(&arg)),
+                }
+            }
+            "--filter" => {
+                let value = args.next().ok_or_else(|| missing_value(&arg))?;
+                let parsed = Filter::parse(&value)
+                    .map_err(|err| Failure::Usage(format!("invalid filter {}", err)))?;
+                filter = Some(parsed);
+            }
+            "--sort" => {
+                let value = args.next().ok_or_else(|| missing_value(&arg))?;
+                let ordering = ordering_named(&value)
+                    .ok_or_else(|| Failure::Usage(format!("unknown ordering '{}'", value)))?;
+                sort = Some(ordering);
+            }
+            "--repeat" => {
+                let value = args.next().ok_or_else(|| missing_value(&arg))?;
+                let parsed = Recurrence::parse(&value)
+                    .map_err(|err| Failure::Usage(format!("invalid rule: {}", err)))?;
+                repeat = Some(parsed);
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(Failure::Usage(format!("unknown option '{}'", arg)))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional
+        .next()
+        .ok_or_else(|| Failure::Usage("missing command".to_string()))?;
+    let rest: Vec<String> = positional.collect();
+
+    if format.is_some() && name != "list" {
+        return Err(Failure::Usage(
+            "--format is only valid with 'list'".to_string(),
+        ));
+    }
+    if sort.is_some() && name != "list" {
+        return Err(Failure::Usage(
+            "--sort is only valid with 'list'".to_string(),
+        ));
+    }
+    if repeat.is_some() && name != "add" {
+        return Err(Failure::Usage(
+            "--repeat is only valid with 'add'".to_string(),
+        ));
+    }
+    if filter.is_some() && name != "list" && name != "remove-done" {
+        return Err(Failure::Usage(
+            "--filter is only valid with 'list' and 'remove-done'".to_string(),
+        ));
+    }
+
+    let command = match (name.as_str(), rest.as_slice()) {
+        ("add", [description, priority]) => Command::Add {
+            description: description.clone(),
+            priority: priority
+                .parse()
+                .map_err(|_| Failure::Usage(format!("invalid priority '{}'", priority)))?,
+            repeat,
+        },
+        ("list", []) => Command::List {
+            format: format.unwrap_or(Format::Text),
+            filter,
+            sort,
+        },
+        ("done", [description]) => Command::Done {
+            description: description.clone(),
+        },
+        ("search", [query]) => Command::Search {
+            query: query.clone(),
+        },
+        ("done-matching", [query]) => Command::DoneMatching {
+            query: query.clone(),
+        },
+        ("remove-done", []) => Command::RemoveDone { filter },
+        ("merge", [file]) => Command::Merge {
+            file: PathBuf::from(file),
+        },
+        ("report", []) => Command::Report { days: None },
+        ("report", [from, to]) => Command::Report {
+            days: Some((parse_date(from)?, parse_date(to)?)),
+        },
+        (
+            "add" | "list" | "done" | "search" | "done-matching" | "remove-done" | "merge"
+            | "report",
+            _,
+        ) => {
+            return Err(Failure::Usage(format!(
+                "wrong number of arguments for '{}'",
+                name
+            )))
+        }
+        _ => return Err(Failure::Usage(format!("unknown command '{}'", name))),
+    };
+
+    let file = file.unwrap_or_else(default_file);
+
+    Ok(Some(Args { file, command }))
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, Failure> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|_| Failure::Usage(format!("invalid date '{}'", text)))
+}
+
+fn missing_value(option: &str) -> Failure {
+    Failure::Usage(format!("option '{}' needs a value", option))
+}
+
+fn run(args: Args) -> Result<(), Failure> {
+    let mut list = ToDoList::load_or_new(&args.file)
+        .map_err(|err| Failure::Command(format!("cannot read {}: {}", args.file.display(), err)))?;
+
+    match args.command {
+        Command::Add {
+            description,
+            priority,
+            repeat,
+        } => {
+            let id = list.add_task(&description, priority)?;
+            if repeat.is_some() {
+                list.set_recurrence(id, repeat)?;
+            }
+        }
+        Command::List {
+            format,
+            filter,
+            sort,
+        } => {
+            match (format, &filter, &sort) {
+                (Format::Text, Some(filter), Some(sort)) => list.print_where_by(filter, &**sort),
+                (Format::Text, Some(filter), None) => list.print_where(filter),
+                (Format::Text, None, Some(sort)) => list.print_by(&**sort),
+                (Format::Text, None, None) => list.print(),
+                (Format::Json, _, _) => {
+                    let tasks: Vec<_> = match &sort {
+                        Some(sort) => list.sorted_by(&**sort),
+                        None => list.iter().collect(),
+                    };
+                    let tasks: Vec<_> = tasks
+                        .into_iter()
+                        .filter(|task| filter.as_ref().is_none_or(|f| f.matches(task)))
+                        .collect();
+                    let json = serde_json::to_string_pretty(&tasks)
+                        .map_err(|err| Failure::Command(err.to_string()))?;
+                    println!("{}", json);
+                }
+            }
+            return Ok(());
+        }
+        Command::Done { description } => {
+            list.mark_done(&description)?;
+        }
+        Command::Search { query } => {
+            for hit in list.search(&query) {
+                let marker = if hit.task.is_done() { "[X]" } else { "[ ]" };
+                let (id, priority) = (hit.task.id(), hit.task.priority());
+                println!(
+                    "{} {} {} - {}",
+                    id,
+                    marker,
+                    priority,
+                    hit.task.description()
+                );
+            }
+            return Ok(());
+        }
+        Command::DoneMatching { query } => {
+            list.mark_done_matching(&query)?;
+        }
+        Command::RemoveDone { filter } => {
+            match filter {
+                Some(filter) => list.remove_done_where(&filter)?,
+                None => list.remove_done()?,
+            };
+        }
+        Command::Merge { file } => {
+            let other = ToDoList::load(&file).map_err(|err| {
+                Failure::Command(format!("cannot read {}: {}", file.display(), err))
+            })?;
+            let report = list.merge(&other);
+            for (id, err) in &report.rejected {
+                eprintln!("todo: skipped task {} of {}: {}", id, file.display(), err);
+            }
+        }
+        Command::Report { days } => {
+            let (from, to) = days.unwrap_or_else(|| {
+                let today = Utc::now().date_naive();
+                (today - Duration::days(13), today)
+            });
+            let report = list.report(from, to);
+            println!("{}", report.to_table());
+            print!("{}", report.burndown(8));
+            return Ok(());
+        }
+    }
+
+    list.save(&args.file)?;
+    Ok(())
+}
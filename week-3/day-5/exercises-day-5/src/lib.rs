@@ -0,0 +1,3 @@
+pub mod server;
+pub mod to_do_list;
+pub mod tui;
@@ -1,5 +1,5 @@
 //import for ToDoList
-mod to_do_list;
+use exercises_day_5::to_do_list;
 
 fn main() {
     // --- FIRST PART ---
@@ -28,16 +28,1786 @@ fn main() {
     another_list.add_task("Task3", 3).ok();
     another_list.add_task("Task4", 4).ok();
 
-    tdl.merge(&another_list);
+    let report = tdl.merge(&another_list);
+    assert!(report.added.len() == 4 && report.rejected.is_empty());
     tdl.print();
 
     println!("\nTest mark_done");
-    tdl.mark_done("Task1");
-    tdl.mark_done("Task2");
-    tdl.mark_done("Task2");
+    tdl.mark_done("Task1").ok();
+    tdl.mark_done("Task2").ok();
+    tdl.mark_done("Task2").ok();
     tdl.print();
 
     println!("\nTest remove_done");
-    tdl.remove_done();
+    tdl.remove_done().ok();
     tdl.print();
+
+    // --- THIRD PART ---
+    println!("\n--- THIRD PART ---");
+    println!("Test save and load");
+
+    let path = std::env::temp_dir().join("to_do_list_demo.json");
+    tdl.mark_done("Task3").ok();
+    tdl.save(&path).expect("save failed");
+
+    let loaded = to_do_list::ToDoList::load(&path).expect("load failed");
+    loaded.print();
+    assert!(loaded.iter().eq(tdl.iter()));
+
+    std::fs::write(
+        &path,
+        r#"{"version": 1, "tasks": [
+        {"description": "ok", "priority": 1, "done": false},
+        {"description": "bad", "priority": 9, "done": false}
+    ]}"#,
+    )
+    .unwrap();
+    match to_do_list::ToDoList::load(&path) {
+        Err(err) => println!("{}", err),
+        Ok(_) => panic!("invalid priority was accepted"),
+    }
+
+    std::fs::write(&path, r#"{"version": 99, "tasks": []}"#).unwrap();
+    assert!(matches!(
+        to_do_list::ToDoList::load(&path),
+        Err(to_do_list::StorageError::UnsupportedVersion(99))
+    ));
+    // A file that is there but unreadable is still an error.
+    assert!(to_do_list::ToDoList::load_or_new(&path).is_err());
+    std::fs::remove_file(&path).ok();
+    let fresh = to_do_list::ToDoList::load_or_new(&path).unwrap();
+    assert!(fresh.iter().next().is_none());
+
+    // --- FOURTH PART ---
+    println!("\n--- FOURTH PART ---");
+    println!("Test task IDs");
+
+    let mut ids = to_do_list::ToDoList::new();
+    let first = ids.add_task("Duplicate", 2).unwrap();
+    let second = ids.add_task("Duplicate", 2).unwrap();
+    let other = ids.add_task("Other", 3).unwrap();
+    assert!(first != second);
+
+    ids.mark_done_by_id(second).unwrap();
+    assert!(!ids.get(first).unwrap().is_done());
+    assert!(ids.get(second).unwrap().is_done());
+
+    ids.remove_done().ok();
+    ids.merge(&another_list);
+    assert!(ids.get(first).is_some() && ids.get(other).is_some());
+    assert!(ids.get(second).is_none());
+
+    ids.set_priority(other, 1).unwrap();
+    ids.edit_description(first, "Renamed").unwrap();
+    assert!(ids.get(other).unwrap().priority() == 1);
+    assert!(ids.get(first).unwrap().description() == "Renamed");
+    assert!(ids.set_priority(first, 0).is_err());
+    assert!(ids.remove(second) == Err(to_do_list::ToDoError::NotFound(second)));
+    ids.remove(other).unwrap();
+    for task in ids.iter() {
+        println!("{} {}", task.id(), task.description());
+    }
+
+    // --- FIFTH PART ---
+    println!("\n--- FIFTH PART ---");
+    println!("Test errors");
+
+    use to_do_list::ToDoError;
+    let mut errors = to_do_list::ToDoList::new();
+    let long = "x".repeat(41);
+    assert!(errors.add_task(&long, 1) == Err(ToDoError::DescriptionTooLong { len: 41, max: 40 }));
+    assert!(
+        errors.add_task("Task", 6)
+            == Err(ToDoError::PriorityOutOfRange {
+                priority: 6,
+                min: 1,
+                max: 5
+            })
+    );
+    assert!(errors.mark_done("Task") == Err(ToDoError::NoOpenTask("Task".to_string())));
+
+    let mut other = to_do_list::ToDoList::new();
+    other.add_task("Short", 1).ok();
+    other.add_task(&"y".repeat(40), 2).ok();
+    let report = errors.merge(&other);
+    assert!(report.added.len() == 2 && report.rejected.is_empty());
+    assert!(errors.remove(to_do_list::TaskId::from(99)) == Err(ToDoError::NotFound(99.into())));
+    println!("{}", ToDoError::DescriptionTooLong { len: 41, max: 40 });
+
+    // --- SIXTH PART ---
+    println!("\n--- SIXTH PART ---");
+    println!("Test due dates");
+
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+    use std::sync::Arc;
+
+    let clock = Arc::new(to_do_list::FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap(),
+    ));
+    let date = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+    let mut dated = to_do_list::ToDoList::new().with_clock(clock.clone());
+
+    let late = dated.add_task("Late", 3).unwrap();
+    let today = dated.add_task("Today", 2).unwrap();
+    let soon = dated.add_task("Soon", 1).unwrap();
+    let later = dated.add_task("Later", 1).unwrap();
+    let undated = dated.add_task("Undated", 1).unwrap();
+    dated.set_due(late, Some(date(7))).unwrap();
+    dated.set_due(today, Some(date(10))).unwrap();
+    dated.set_due(soon, Some(date(12))).unwrap();
+    dated.set_due(later, Some(date(20))).unwrap();
+
+    let ids = |tasks: Vec<&to_do_list::Task>| tasks.iter().map(|t| t.id()).collect::<Vec<_>>();
+    assert!(ids(dated.overdue().collect()) == vec![late]);
+    assert!(ids(dated.due_today().collect()) == vec![today]);
+    assert!(ids(dated.due_within(3).collect()) == vec![soon, today]);
+    assert!(ids(dated.by_due_date()) == vec![late, today, soon, later, undated]);
+
+    clock.advance(Duration::hours(2));
+    dated.mark_done_by_id(late).unwrap();
+    let task = dated.get(late).unwrap();
+    assert!(task.created() == Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap());
+    assert!(task.completed() == Some(Utc.with_ymd_and_hms(2025, 3, 10, 11, 0, 0).unwrap()));
+    assert!(dated.overdue().next().is_none());
+
+    clock.advance(Duration::days(5));
+    assert!(ids(dated.overdue().collect()) == vec![soon, today]);
+    for task in dated.by_due_date() {
+        println!("{:?} {}", task.due(), task.description());
+    }
+
+    // --- SEVENTH PART ---
+    println!("\n--- SEVENTH PART ---");
+    println!("Test tags and filters");
+
+    use to_do_list::Filter;
+    let mut tagged = to_do_list::ToDoList::new();
+    let api = tagged.add_task("Fix API", 1).unwrap();
+    let db = tagged.add_task("Migrate DB", 2).unwrap();
+    let docs = tagged.add_task("Write docs", 3).unwrap();
+    tagged.add_tag(api, "backend").unwrap();
+    tagged.add_tag(db, "backend").unwrap();
+    tagged.add_tag(docs, "docs").unwrap();
+    assert!(tagged.add_tag(docs, "two words").is_err());
+
+    let filter = Filter::parse("tag:backend and priority<=2 and not done").unwrap();
+    let selected: Vec<_> = tagged.iter_where(&filter).map(|t| t.id()).collect();
+    assert!(selected == vec![api, db]);
+    tagged.print_where(&filter);
+
+    tagged.mark_done_by_id(api).unwrap();
+    tagged.mark_done_by_id(docs).unwrap();
+    let selected: Vec<_> = tagged.iter_where(&filter).map(|t| t.id()).collect();
+    assert!(selected == vec![db]);
+
+    let backend: Filter = "TAG:backend OR (text:DOCS and priority = 9)"
+        .parse()
+        .unwrap();
+    assert!(tagged.remove_done_where(&backend) == Ok(1));
+    assert!(tagged.get(api).is_none() && tagged.get(docs).is_some());
+    assert!(tagged.mark_done_where(&backend) == Ok(vec![db]));
+
+    let err = Filter::parse("tag:backend and (priority <").unwrap_err();
+    println!("{}", err);
+    assert!(err.offset == 27);
+    assert!(Filter::parse("priority >> 2").unwrap_err().offset == 10);
+    assert!(Filter::parse("done done").is_err());
+    // Deep nesting is refused instead of overflowing the stack.
+    let err = Filter::parse(&"(".repeat(100_000)).unwrap_err();
+    assert!(err.offset == Filter::MAX_DEPTH);
+    let err = Filter::parse(&"not ".repeat(100_000)).unwrap_err();
+    assert!(err.offset == 4 * Filter::MAX_DEPTH);
+    assert!(Filter::parse(&"done or ".repeat(100_000)).is_err());
+    let deep = format!("{}done{}", "(".repeat(200), ")".repeat(200));
+    assert!(Filter::parse(&deep).unwrap() == Filter::Done);
+
+    // --- EIGHTH PART ---
+    println!("\n--- EIGHTH PART ---");
+    println!("Test undo and redo");
+
+    let mut history = to_do_list::ToDoList::new();
+    history.add_task("Keep", 2).ok();
+    history.add_task("Finish", 1).ok();
+    history.mark_done("Finish").ok();
+    history.remove_done().ok();
+    assert!(history.undo() == Some("remove done"));
+    assert!(history
+        .iter()
+        .any(|t| t.description() == "Finish" && t.is_done()));
+    assert!(history.undo() == Some("mark done"));
+    assert!(history.redo() == Some("mark done"));
+    history.merge(&another_list);
+    assert!(!history.can_redo());
+    assert!(history.undo() == Some("merge") && history.iter().count() == 2);
+
+    let mut bounded = to_do_list::ToDoList::new().with_history_limit(2);
+    for i in 0..5 {
+        bounded.add_task(&format!("Task{}", i), 1).ok();
+    }
+    assert!(bounded.undo().is_some() && bounded.undo().is_some());
+    assert!(bounded.undo().is_none() && bounded.iter().count() == 3);
+
+    // --- NINTH PART ---
+    println!("\n--- NINTH PART ---");
+    println!("Test todo.txt import and export");
+
+    let todo_txt = "\
+(A) 2025-03-01 Call the bank +finance @phone due:2025-03-05
+x 2025-03-04 2025-03-02 Pay rent +finance pri:B
+
+Water plants
+(F) Someday maybe
+(C) Renew passport due:2025-02-30
+(B) Fix the build tag:backend
+";
+    let mut imported = to_do_list::ToDoList::new().with_clock(clock.clone());
+    let report = imported.import_todo_txt(todo_txt);
+    assert!(report.added.len() == 4);
+    for (line, err) in &report.skipped {
+        println!("line {}: {}", line, err);
+    }
+    assert!(report.skipped.iter().map(|(line, _)| *line).eq([5, 6]));
+
+    let exported = imported.to_todo_txt();
+    print!("{}", exported);
+    let call = imported.get(report.added[0]).unwrap();
+    assert!(call.priority() == 1 && call.due() == Some(date(5)));
+    assert!(call.tags().eq(["+finance", "@phone"]));
+    let rent = imported.get(report.added[1]).unwrap();
+    assert!(rent.is_done() && rent.priority() == 2);
+    assert!(imported.get(report.added[2]).unwrap().priority() == 5);
+
+    let mut reimported = to_do_list::ToDoList::new().with_clock(clock.clone());
+    assert!(reimported.import_todo_txt(&exported).skipped.is_empty());
+    assert!(reimported.to_todo_txt() == exported);
+    assert!(imported.undo() == Some("import") && imported.iter().count() == 0);
+
+    // --- TENTH PART ---
+    println!("\n--- TENTH PART ---");
+    println!("Test iCalendar VTODO export and import");
+
+    let mut calendar = to_do_list::ToDoList::new().with_clock(clock.clone());
+    let tricky = calendar
+        .add_task("Perché; a, b \\ c, d; e, f; g, h; i", 2)
+        .unwrap();
+    calendar.add_tag(tricky, "+rust-roadmap").unwrap();
+    calendar.add_tag(tricky, "@home").unwrap();
+    calendar
+        .add_tag(tricky, "a-really-long-tag-name-to-force-folding")
+        .unwrap();
+    calendar.add_tag(tricky, "another-tag").unwrap();
+    calendar.set_due(tricky, Some(date(14))).unwrap();
+    let finished = calendar.add_task("Finished", 4).unwrap();
+    calendar.mark_done_by_id(finished).unwrap();
+
+    let ics = calendar.to_ical();
+    print!("{}", ics);
+    assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+    assert!(ics.contains("\r\n "));
+    assert!(ics.contains("PRIORITY:3\r\n") && ics.contains("STATUS:COMPLETED\r\n"));
+
+    let mut from_ics = to_do_list::ToDoList::new();
+    let report = from_ics.import_ical(&ics).unwrap();
+    assert!(report.added.len() == 2 && report.skipped.is_empty());
+    let same = |a: &to_do_list::Task, b: &to_do_list::Task| {
+        a.description() == b.description()
+            && a.priority() == b.priority()
+            && a.is_done() == b.is_done()
+            && a.due() == b.due()
+            && a.created() == b.created()
+            && a.completed() == b.completed()
+            && a.tags().eq(b.tags())
+    };
+    assert!(from_ics
+        .iter()
+        .zip(calendar.iter())
+        .all(|(a, b)| same(a, b)));
+    // A completed VTODO is no duplicate of an open task.
+    let mut unique = to_do_list::ToDoList::new().with_policy(to_do_list::ValidationPolicy {
+        allow_duplicates: false,
+        ..Default::default()
+    });
+    unique.add_task("Finished", 4).unwrap();
+    assert!(unique.import_ical(&ics).unwrap().added.len() == 2);
+
+    let mixed = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+BEGIN:VEVENT\r\nSUMMARY:Week 1 - Day 1\r\nEND:VEVENT\r\n\
+BEGIN:VTODO\r\nSUMMARY:Read chapter\r\n  4\r\nPRIORITY:1\r\nX-APPLE-SORT:3\r\nEND:VTODO\r\n\
+BEGIN:VTODO\r\nSUMMARY:Yearly review\r\nRRULE:FREQ=YEARLY\r\nEND:VTODO\r\n\
+BEGIN:VTODO\r\nSUMMARY:Bad\r\nPRIORITY:12\r\nEND:VTODO\r\n\
+END:VCALENDAR\r\n";
+    let report = from_ics.import_ical(mixed).unwrap();
+    assert!(report.added.len() == 1);
+    let read = from_ics.get(report.added[0]).unwrap();
+    assert!(read.description() == "Read chapter 4" && read.priority() == 1);
+    for (line, err) in &report.skipped {
+        println!("VTODO at line {}: {}", line, err);
+    }
+    assert!(report.skipped.iter().map(|(line, _)| *line).eq([12, 16]));
+
+    let broken = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:x\r\nEND:VCALENDAR\r\n";
+    let err = from_ics.import_ical(broken).unwrap_err();
+    println!("{}", err);
+
+    // --- ELEVENTH PART ---
+    println!("\n--- ELEVENTH PART ---");
+    println!("Test subtasks");
+
+    let mut project = to_do_list::ToDoList::new();
+    let week = project.add_task("Week 3", 2).unwrap();
+    let day = project.add_subtask(week, "Day 5", 3).unwrap();
+    let exercise = project.add_subtask(day, "Exercises", 1).unwrap();
+    let reading = project.add_subtask(day, "Reading", 4).unwrap();
+    let other = project.add_task("Unrelated", 1).unwrap();
+    project.print();
+    let depths: Vec<_> = project
+        .tree()
+        .iter()
+        .map(|(depth, task)| (*depth, task.id()))
+        .collect();
+    assert!(depths == [(0, other), (0, week), (1, day), (2, exercise), (2, reading)]);
+
+    // A parent is only done once all its children are.
+    assert!(project.mark_done_by_id(day) == Err(to_do_list::ToDoError::OpenSubtasks(day)));
+    project.mark_done_by_id(exercise).unwrap();
+    project.mark_done_by_id(reading).unwrap();
+    project.mark_done_by_id(day).unwrap();
+    assert!(!project.get(week).unwrap().is_done());
+    // An open subtask under a done task reopens it and its done ancestors.
+    let extra = project.add_subtask(exercise, "Extra", 5).unwrap();
+    assert!(!project.get(exercise).unwrap().is_done() && !project.get(day).unwrap().is_done());
+    project.complete_tree(week).unwrap();
+    assert!(project.descendants(week).unwrap().len() == 4);
+    assert!(project.iter().filter(|t| t.is_done()).count() == 5);
+    project.undo();
+    assert!(project.iter().filter(|t| t.is_done()).count() == 1);
+
+    // Moving a task under its own subtree is refused.
+    let cycle = project.reparent(day, Some(extra));
+    assert!(
+        cycle
+            == Err(to_do_list::ToDoError::Cycle {
+                task: day,
+                parent: extra
+            })
+    );
+    project.reparent(reading, Some(other)).unwrap();
+    assert!(project.parent(reading) == Some(other));
+    project.reparent(reading, None).unwrap();
+    assert!(project.parent(reading).is_none());
+
+    // Removing a middle node hands its children to the grandparent.
+    project.remove(day).unwrap();
+    assert!(project.parent(exercise) == Some(week));
+    assert!(project.children(week).map(|t| t.id()).eq([exercise]));
+    project.undo();
+    assert!(project.parent(exercise) == Some(day));
+    assert!(project.children(week).map(|t| t.id()).eq([day]));
+    assert!(project.children(day).map(|t| t.id()).eq([exercise]));
+
+    // Done subtrees go away as a whole, even if only the root is selected.
+    project.complete_tree(day).unwrap();
+    let only_day: to_do_list::Filter = "text:day".parse().unwrap();
+    assert!(project.remove_done_where(&only_day) == Ok(3));
+    assert!(project.iter().map(|t| t.id()).eq([other, week, reading]));
+    project.undo();
+
+    // Merging keeps the shape of the tree under fresh IDs.
+    let mut copy = to_do_list::ToDoList::new();
+    copy.add_task("Already here", 1).unwrap();
+    let report = copy.merge(&project);
+    assert!(report.added.len() == 6);
+    let copied_day = copy.iter().find(|t| t.description() == "Day 5").unwrap();
+    let copied_week = copy.iter().find(|t| t.description() == "Week 3").unwrap();
+    assert!(copied_day.parent() == Some(copied_week.id()));
+    assert!(copy.descendants(copied_week.id()).unwrap().len() == 3);
+    copy.print();
+
+    // Only selected tasks are merged; subtasks of a skipped task move up.
+    let mut partial = to_do_list::ToDoList::new();
+    let no_day: to_do_list::Filter = "not text:day".parse().unwrap();
+    partial.merge_where(&project, &no_day);
+    let partial_week = partial
+        .iter()
+        .find(|t| t.description() == "Week 3")
+        .unwrap()
+        .id();
+    assert!(partial.children(partial_week).count() == 1);
+    assert!(partial.tree().iter().all(|(depth, _)| *depth <= 2));
+
+    let tree_file = std::env::temp_dir().join("todo_tree_demo.json");
+    project.save(&tree_file).unwrap();
+    let loaded = to_do_list::ToDoList::load(&tree_file).unwrap();
+    assert!(loaded.iter().eq(project.iter()));
+    let _ = std::fs::remove_file(&tree_file);
+
+    // --- TWELFTH PART ---
+    println!("\n--- TWELFTH PART ---");
+    println!("Test recurring tasks");
+
+    use to_do_list::{Recurrence, RecurrenceError};
+    let rule = |text: &str| text.parse::<Recurrence>().unwrap();
+    let gym_rule = rule("RRULE:freq=weekly;byday=TH,MO");
+    println!("{}", gym_rule);
+    assert!(gym_rule.to_string() == "FREQ=WEEKLY;BYDAY=MO,TH");
+    assert!(rule(&gym_rule.to_string()) == gym_rule);
+    assert!(matches!(
+        Recurrence::parse("FREQ=YEARLY"),
+        Err(RecurrenceError::InvalidValue { .. })
+    ));
+    assert!(
+        Recurrence::parse("FREQ=DAILY;BYDAY=MO")
+            == Err(RecurrenceError::Misplaced("BYDAY".to_string()))
+    );
+    assert!(Recurrence::parse("INTERVAL=2") == Err(RecurrenceError::MissingFrequency));
+    assert!(
+        Recurrence::parse("FREQ=DAILY;COUNT=3")
+            == Err(RecurrenceError::UnsupportedPart("COUNT".to_string()))
+    );
+
+    let day = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+    assert!(gym_rule.next(day(3, 10), day(3, 10)) == Some(day(3, 13)));
+    assert!(gym_rule.next(day(3, 10), day(3, 13)) == Some(day(3, 17)));
+    assert!(rule("FREQ=DAILY;INTERVAL=3").next(day(3, 10), day(3, 20)) == Some(day(3, 22)));
+    // Months without a 31st are skipped; negative days count from the end.
+    let month_end = rule("FREQ=MONTHLY;BYMONTHDAY=31");
+    assert!(month_end.next(day(1, 31), day(1, 31)) == Some(day(3, 31)));
+    let last_day = rule("FREQ=MONTHLY;BYMONTHDAY=-1");
+    assert!(last_day.next(day(1, 31), day(1, 31)) == Some(day(2, 28)));
+    assert!(rule("FREQ=DAILY;UNTIL=20250311")
+        .next(day(3, 10), day(3, 11))
+        .is_none());
+
+    let chores_clock = Arc::new(to_do_list::FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap(),
+    ));
+    let mut chores = to_do_list::ToDoList::new().with_clock(chores_clock.clone());
+    let gym = chores.add_task("Gym", 2).unwrap();
+    chores.set_due(gym, Some(day(3, 10))).unwrap();
+    chores.set_recurrence(gym, Some(gym_rule.clone())).unwrap();
+    let plants = chores.add_task("Water plants", 3).unwrap();
+    chores.add_tag(plants, "home").unwrap();
+    let plants_rule = rule("FREQ=DAILY;INTERVAL=3;X-FROM=COMPLETION");
+    chores
+        .set_recurrence(plants, Some(plants_rule.clone()))
+        .unwrap();
+
+    // Completing a recurring task adds the next occurrence and moves the rule.
+    let next_gym = chores.mark_done_by_id(gym).unwrap().unwrap();
+    assert!(chores
+        .iter()
+        .filter(|t| !t.is_done() && t.description() == "Gym")
+        .all(|t| t.id() == next_gym));
+    assert!(chores.get(next_gym).unwrap().due() == Some(day(3, 13)));
+    assert!(chores.get(gym).unwrap().recurrence().is_none());
+    assert!(chores.get(next_gym).unwrap().recurrence() == Some(&gym_rule));
+
+    // Without a due date, or counted from completion, it starts from today.
+    chores.mark_done("Water plants").unwrap();
+    let next_plants = chores.iter().find(|t| !t.is_done() && t.has_tag("home"));
+    assert!(next_plants.unwrap().due() == Some(day(3, 13)));
+
+    // Missed occurrences are skipped: the next one is never in the past.
+    chores_clock.advance(Duration::days(10));
+    chores.mark_done_by_id(next_gym).unwrap();
+    let latest = chores
+        .iter()
+        .find(|t| !t.is_done() && t.description() == "Gym");
+    assert!(latest.unwrap().due() == Some(day(3, 20)));
+    chores.print();
+
+    // Undoing the completion also takes back the new occurrence.
+    let before = chores.iter().count();
+    chores.undo();
+    assert!(chores.iter().count() == before - 1);
+    assert!(chores.get(next_gym).unwrap().recurrence() == Some(&gym_rule));
+    chores.redo();
+
+    // A parent with a recurring subtask can be completed as a whole; the
+    // next occurrence moves up to the nearest open ancestor.
+    let week = chores.add_task("Week plan", 1).unwrap();
+    let daily = chores.add_subtask(week, "Journal", 2).unwrap();
+    chores
+        .set_recurrence(daily, Some(rule("FREQ=DAILY")))
+        .unwrap();
+    chores.complete_tree(week).unwrap();
+    assert!(chores.get(week).unwrap().is_done());
+    let next_journal = chores
+        .iter()
+        .find(|t| !t.is_done() && t.description() == "Journal")
+        .unwrap();
+    assert!(next_journal.parent().is_none() && next_journal.recurrence().is_some());
+    chores.undo();
+    assert!(!chores.get(week).unwrap().is_done());
+    chores.remove_tree(week).unwrap();
+
+    let chores_file = std::env::temp_dir().join("todo_chores_demo.json");
+    chores.save(&chores_file).unwrap();
+    let loaded = to_do_list::ToDoList::load(&chores_file).unwrap();
+    assert!(loaded.iter().eq(chores.iter()));
+    let _ = std::fs::remove_file(&chores_file);
+
+    let ics = chores.to_ical();
+    assert!(ics.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TH\r\n"));
+    let mut from_ics = to_do_list::ToDoList::new();
+    from_ics.import_ical(&ics).unwrap();
+    assert!(from_ics
+        .iter()
+        .filter_map(|t| t.recurrence())
+        .eq([&gym_rule, &plants_rule]));
+
+    // Subtasks and recurrence survive both text formats.
+    let mut plan = to_do_list::ToDoList::new();
+    let week = plan.add_task("Week plan", 1).unwrap();
+    let journal = plan.add_subtask(week, "Journal", 2).unwrap();
+    plan.set_recurrence(journal, Some(rule("FREQ=DAILY")))
+        .unwrap();
+    plan.add_subtask(journal, "Gratitude", 3).unwrap();
+    let shape = |list: &to_do_list::ToDoList| -> Vec<(usize, String, Option<String>)> {
+        list.tree()
+            .into_iter()
+            .map(|(depth, t)| {
+                let rule = t.recurrence().map(|r| r.to_string());
+                (depth, t.description().to_string(), rule)
+            })
+            .collect()
+    };
+    let todo_txt = plan.to_todo_txt();
+    assert!(todo_txt.contains(" rec:FREQ=DAILY") && todo_txt.contains(" parent:"));
+    let mut from_txt = to_do_list::ToDoList::new();
+    assert!(from_txt.import_todo_txt(&todo_txt).skipped.is_empty());
+    assert!(shape(&from_txt) == shape(&plan));
+    let ics = plan.to_ical();
+    assert!(ics.contains("RELATED-TO;RELTYPE=PARENT:"));
+    let mut from_ics = to_do_list::ToDoList::new();
+    assert!(from_ics.import_ical(&ics).unwrap().skipped.is_empty());
+    assert!(shape(&from_ics) == shape(&plan));
+
+    // --- THIRTEENTH PART ---
+    println!("\n--- THIRTEENTH PART ---");
+    println!("Test a list shared between threads");
+
+    let shared = to_do_list::SharedToDoList::new(to_do_list::ToDoList::new());
+    std::thread::scope(|scope| {
+        for writer in 0..2 {
+            let shared = shared.clone();
+            scope.spawn(move || {
+                for n in 0..3u8 {
+                    let id = shared
+                        .add_task(&format!("w{} t{}", writer, n), n + 1)
+                        .unwrap();
+                    if n == 0 {
+                        shared.mark_done_by_id(id).unwrap();
+                    }
+                }
+            });
+        }
+    });
+    for task in shared.snapshot() {
+        println!(
+            "{} {} done: {}",
+            task.priority(),
+            task.description(),
+            task.is_done()
+        );
+    }
+    assert!(shared.remove_done() == Ok(2));
+
+    // --- FOURTEENTH PART ---
+    println!("\n--- FOURTEENTH PART ---");
+    println!("Test the HTTP API over loopback");
+
+    use std::io::{Read, Write};
+    // Sends one request as JSON and returns the status code and the JSON body.
+    fn http(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    let api_file = std::env::temp_dir().join("todo_api_demo.json");
+    let _ = std::fs::remove_file(&api_file);
+    let server = exercises_day_5::server::Server::bind(0, &api_file).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+    println!("Server listening on {}", addr);
+
+    for body in [
+        r#"{"description":"Ship API","priority":2}"#,
+        r#"{"description":"Urgent","priority":1}"#,
+        r#"{"description":"Bad","priority":9}"#,
+    ] {
+        let (status, reply) = http(addr, "POST", "/tasks", body);
+        println!("POST /tasks {} -> {} {}", body, status, reply);
+    }
+    let (status, tasks) = http(addr, "GET", "/tasks", "");
+    println!("GET /tasks -> {} {}", status, tasks);
+    assert!(status == 200 && tasks.as_array().unwrap().len() == 2);
+    let _ = std::fs::remove_file(&api_file);
+
+    // --- FIFTEENTH PART ---
+    println!("\n--- FIFTEENTH PART ---");
+    println!("Test the terminal UI headlessly");
+
+    use exercises_day_5::tui::{App, Key};
+    let mut board = to_do_list::ToDoList::new();
+    let report_id = board.add_task("Write report", 2).unwrap();
+    board.add_task("Buy milk", 1).unwrap();
+    board.add_task("Call Bob", 3).unwrap();
+    board.add_subtask(report_id, "Outline", 2).unwrap();
+    board.add_tag(report_id, "work").unwrap();
+    let mut app = App::new(board);
+    app.resize(60, 8);
+    let show = |app: &App| {
+        let screen = app.render();
+        for line in &screen.lines {
+            println!("|{}|", line);
+        }
+        screen
+    };
+    let text = |screen: &exercises_day_5::tui::Screen| -> Vec<String> {
+        screen
+            .lines
+            .iter()
+            .map(|l| l.trim_end().to_string())
+            .collect()
+    };
+    let screen = show(&app);
+    assert!(screen.lines.iter().all(|l| l.chars().count() == 60));
+    assert!(
+        text(&screen)
+            == [
+                "ToDoList - 4 tasks",
+                "> [ ] 1 - Buy milk",
+                "  [ ] 2 - Write report  #work",
+                "    [ ] 2 - Outline",
+                "  [ ] 3 - Call Bob",
+                "",
+                "j/k: move  space: done  e: edit  p: priority  /: filter  u/r",
+                "P1: 1  P2: 2  P3: 1  P4: 0  P5: 0 | done 0/4",
+            ]
+    );
+    assert!(screen.highlight == Some(1) && screen.cursor.is_none());
+
+    let press = |app: &mut App, keys: &str| keys.chars().for_each(|c| app.handle_key(Key::Char(c)));
+    // A parent cannot be done before its subtask.
+    press(&mut app, "j ");
+    assert!(text(&app.render())[6] == "task #1 has open subtasks");
+    press(&mut app, "j k ");
+    let screen = show(&app);
+    assert!(screen.lines[2].starts_with("> [X] 2 - Write report"));
+    assert!(screen.lines[3].starts_with("    [X] 2 - Outline"));
+    assert!(screen.lines[7].starts_with("P1: 1  P2: 2  P3: 1  P4: 0  P5: 0 | done 2/4"));
+
+    // Inline priority edit; the selection follows the task as it moves.
+    press(&mut app, "p1");
+    let screen = app.render();
+    assert!(screen.lines[2].starts_with("> [X] 1 - Write report"));
+    assert!(screen.cursor == Some((7, 2)));
+    app.handle_key(Key::Enter);
+    let screen = show(&app);
+    assert!(screen.lines[2].starts_with("> [X] 1 - Write report") && screen.highlight == Some(2));
+
+    // Inline description edit, with errors shown and the edit kept.
+    press(&mut app, "e");
+    (0..6).for_each(|_| app.handle_key(Key::Backspace));
+    press(&mut app, "memo");
+    assert!(app.render().cursor == Some((20, 2)));
+    app.handle_key(Key::Enter);
+    assert!(app.list().iter().any(|t| t.description() == "Write memo"));
+    press(&mut app, "e and a description that is far too long");
+    app.handle_key(Key::Enter);
+    let screen = show(&app);
+    assert!(screen.lines[6].starts_with("description is too long"));
+    app.handle_key(Key::Esc);
+    assert!(!app.should_quit() && app.render().cursor.is_none());
+
+    // Filter bar.
+    press(&mut app, "/not done");
+    assert!(app.render().lines[6].starts_with("Filter: not done"));
+    app.handle_key(Key::Enter);
+    let screen = show(&app);
+    assert!(
+        text(&screen)[..4]
+            == [
+                "ToDoList - 2 of 4 tasks - filter: not done",
+                "> [ ] 1 - Buy milk",
+                "  [ ] 3 - Call Bob",
+                "",
+            ]
+    );
+    press(&mut app, "/");
+    (0..8).for_each(|_| app.handle_key(Key::Backspace));
+    press(&mut app, "priority<");
+    app.handle_key(Key::Enter);
+    assert!(text(&show(&app))[6].starts_with("Filter: priority<  (filter at offset 9"));
+    (0..9).for_each(|_| app.handle_key(Key::Backspace));
+    app.handle_key(Key::Enter);
+    assert!(text(&app.render())[0] == "ToDoList - 4 tasks");
+
+    press(&mut app, "u");
+    assert!(text(&app.render())[6] == "undo: edit description");
+    assert!(app.list().iter().any(|t| t.description() == "Write report"));
+    assert!(app.is_changed());
+
+    // Long lists scroll to keep the selection on screen.
+    let mut long = to_do_list::ToDoList::new();
+    for n in 1..=30 {
+        long.add_task(&format!("Task {}", n), 3).unwrap();
+    }
+    let mut app = App::new(long);
+    app.resize(30, 8);
+    app.handle_key(Key::End);
+    let screen = show(&app);
+    assert!(screen.lines[1].starts_with("  [ ] 3 - Task 26"));
+    assert!(screen.lines[5].starts_with("> [ ] 3 - Task 30") && screen.highlight == Some(5));
+    app.handle_key(Key::PageUp);
+    assert!(app.render().lines[1].starts_with("> [ ] 3 - Task 25"));
+    press(&mut app, "q");
+    assert!(app.should_quit() && !app.is_changed());
+
+    // Lines are fitted by terminal columns, never cutting a character.
+    use unicode_width::UnicodeWidthStr;
+    let mut wide = to_do_list::ToDoList::new();
+    wide.add_task("日本語の本", 1).unwrap();
+    wide.add_task("Cafe\u{301}s", 1).unwrap();
+    let mut app = App::new(wide);
+    app.resize(15, 5);
+    let screen = show(&app);
+    assert!(screen.lines.iter().all(|l| l.width() == 15));
+    assert!(screen.lines[1] == "> [ ] 1 - 日本 ");
+    app.resize(14, 5);
+    let screen = app.render();
+    assert!(screen.lines[2] == "  [ ] 1 - Cafe\u{301}");
+
+    // --- SIXTEENTH PART ---
+    println!("\n--- SIXTEENTH PART ---");
+    println!("Test three-way merge");
+
+    use to_do_list::{ChangeKind, ConflictKind, Field, MergeStrategy, ToDoList};
+    let copy = |list: &ToDoList| ToDoList::from_json(&list.to_json().unwrap()).unwrap();
+    let mut base = ToDoList::new();
+    let milk = base.add_task("Buy milk", 2).unwrap();
+    let report = base.add_task("Write report", 3).unwrap();
+    let bob = base.add_task("Call Bob", 1).unwrap();
+    let old = base.add_task("Old task", 4).unwrap();
+    let desk = base.add_task("Tidy desk", 5).unwrap();
+
+    let mut ours = copy(&base);
+    ours.mark_done_by_id(milk).unwrap();
+    ours.set_priority(report, 1).unwrap();
+    ours.add_task("Both added", 3).unwrap();
+    ours.edit_description(bob, "Call Bob now").unwrap();
+    ours.remove(old).unwrap();
+    ours.add_tag(desk, "home").unwrap();
+
+    let mut theirs = copy(&base);
+    theirs.edit_description(milk, "Buy oat milk").unwrap();
+    theirs.set_priority(report, 2).unwrap();
+    theirs.remove(desk).unwrap();
+    theirs.edit_description(old, "Old task, revisited").unwrap();
+    let their_new = theirs.add_task("Theirs added", 2).unwrap();
+    theirs.add_task("Both added", 3).unwrap();
+    theirs.edit_description(bob, "Call Bob later").unwrap();
+
+    let describe = |list: &ToDoList, id| {
+        list.get(id)
+            .map(|t| (t.description().to_string(), t.priority(), t.is_done()))
+    };
+    let mut merged = copy(&ours);
+    let result = merged.merge3(&base, &theirs, MergeStrategy::Ours);
+    merged.print();
+    for conflict in &result.conflicts {
+        match &conflict.kind {
+            ConflictKind::Edited { fields, .. } => {
+                println!("conflict on {}: both changed {:?}", conflict.id, fields)
+            }
+            ConflictKind::EditedRemoved { .. } => {
+                println!("conflict on {}: changed here, removed there", conflict.id)
+            }
+            ConflictKind::RemovedEdited { .. } => {
+                println!("conflict on {}: removed here, changed there", conflict.id)
+            }
+        }
+    }
+    assert!(describe(&merged, milk) == Some(("Buy oat milk".to_string(), 2, true)));
+    assert!(describe(&merged, report).unwrap().1 == 1);
+    assert!(describe(&merged, bob).unwrap().0 == "Call Bob now");
+    assert!(merged.get(old).is_none() && merged.get(desk).unwrap().has_tag("home"));
+    assert!(
+        merged
+            .iter()
+            .filter(|t| t.description() == "Both added")
+            .count()
+            == 1
+    );
+    assert!(result.added.len() == 1);
+    assert!(describe(&merged, result.added[0]).unwrap().0 == "Theirs added");
+    let kinds: Vec<_> = result.conflicts.iter().map(|c| (c.id, &c.kind)).collect();
+    assert!(kinds.len() == 4);
+    assert!(matches!(kinds[0], (id, ConflictKind::Edited { fields, .. })
+        if id == bob && fields == &[Field::Description]));
+    assert!(matches!(kinds[1], (id, ConflictKind::Edited { fields, .. })
+        if id == report && fields == &[Field::Priority]));
+    assert!(matches!(kinds[2], (id, ConflictKind::RemovedEdited { .. }) if id == old));
+    assert!(matches!(kinds[3], (id, ConflictKind::EditedRemoved { .. }) if id == desk));
+    let theirs_changes: Vec<_> = result
+        .theirs
+        .iter()
+        .map(|c| (c.id, c.kind.clone()))
+        .collect();
+    assert!(theirs_changes.contains(&(milk, ChangeKind::Edited(Field::Description))));
+    assert!(theirs_changes.contains(&(report, ChangeKind::Reprioritised { from: 3, to: 2 })));
+    assert!(theirs_changes.contains(&(desk, ChangeKind::Removed)));
+    assert!(theirs_changes.contains(&(their_new, ChangeKind::Added)));
+    assert!(result
+        .ours
+        .iter()
+        .any(|c| c.id == milk && c.kind == ChangeKind::Completed));
+    // The whole merge is one step to undo.
+    merged.undo();
+    assert!(merged.iter().eq(ours.iter()));
+
+    let mut merged = copy(&ours);
+    merged.merge3(&base, &theirs, MergeStrategy::Theirs);
+    assert!(describe(&merged, report).unwrap().1 == 2);
+    assert!(describe(&merged, bob).unwrap().0 == "Call Bob later");
+    assert!(describe(&merged, old).unwrap().0 == "Old task, revisited");
+    assert!(merged.get(desk).is_none());
+
+    let mut merged = copy(&ours);
+    let result = merged.merge3(&base, &theirs, MergeStrategy::Both);
+    assert!(describe(&merged, bob).unwrap().0 == "Call Bob now");
+    assert!(merged.iter().any(|t| t.description() == "Call Bob later"));
+    assert!(
+        merged
+            .iter()
+            .filter(|t| t.description() == "Write report")
+            .count()
+            == 2
+    );
+    assert!(merged.get(old).is_some() && merged.get(desk).is_some());
+    assert!(result.added.len() == 3);
+
+    // Moves that would put two tasks under each other are not taken.
+    let mut base = ToDoList::new();
+    let p = base.add_task("P", 1).unwrap();
+    let q = base.add_task("Q", 1).unwrap();
+    let mut ours = copy(&base);
+    ours.reparent(q, Some(p)).unwrap();
+    let mut theirs = copy(&base);
+    theirs.reparent(p, Some(q)).unwrap();
+    let result = ours.merge3(&base, &theirs, MergeStrategy::Theirs);
+    assert!(ours.parent(q) == Some(p) && ours.parent(p).is_none());
+    assert!(matches!(&result.conflicts[..], [c] if c.id == p
+        && matches!(&c.kind, ConflictKind::Edited { fields, .. } if fields == &[Field::Parent])));
+
+    // --- SEVENTEENTH PART ---
+    println!("\n--- SEVENTEENTH PART ---");
+    println!("Test replicated lists");
+
+    use to_do_list::{ListState, ReplicaId, ReplicatedToDoList};
+    let mut laptop = ReplicatedToDoList::new(ReplicaId(1));
+    let mut phone = ReplicatedToDoList::new(ReplicaId(2));
+    let milk = laptop.add_task("Buy milk", 2).unwrap();
+    let report = laptop.add_task("Write report", 3).unwrap();
+    phone.join(laptop.state());
+
+    // Offline on both sides.
+    laptop.set_priority(report, 1).unwrap();
+    phone.set_priority(report, 4).unwrap();
+    phone.edit_description(milk, "Buy oat milk").unwrap();
+    laptop.remove(milk).unwrap();
+    phone.add_task("Call Bob", 1).unwrap();
+    laptop.add_task("Call Bob", 1).unwrap();
+    assert!(phone.add_task("Call Bob", 9).is_err());
+    assert!(laptop.mark_done(milk) == Err(to_do_list::ToDoError::UnknownDot(milk)));
+
+    laptop.join(phone.state());
+    phone.join(laptop.state());
+    laptop.print();
+    assert!(laptop.state() == phone.state());
+    // Equal clocks: the higher replica ID wins. The removal wins over the edit.
+    assert!(laptop.get(report).unwrap().priority() == 4);
+    assert!(laptop.get(milk).is_none());
+    assert!(
+        laptop
+            .iter()
+            .filter(|(_, t)| t.description() == "Call Bob")
+            .count()
+            == 2
+    );
+    // A write made after seeing another one wins over it.
+    laptop.set_priority(report, 2).unwrap();
+    phone.join(laptop.state());
+    assert!(phone.get(report).unwrap().priority() == 2);
+
+    phone.mark_done(report).unwrap();
+    assert!(phone.remove_done() == 1);
+    laptop.join(phone.state());
+    assert!(laptop.get(report).is_none());
+    let json = serde_json::to_string(&laptop).unwrap();
+    let restored: ReplicatedToDoList = serde_json::from_str(&json).unwrap();
+    assert!(restored == laptop);
+
+    // Property test: replicas making random changes and exchanging states at
+    // random. `join` must be commutative, associative and idempotent, and
+    // every replica must end up with the same state whatever order the
+    // states are delivered in.
+    let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut random = move |n: usize| xorshift(&mut seed, n);
+    for _ in 0..50 {
+        let mut replicas: Vec<ReplicatedToDoList> = (1..=3)
+            .map(|id| ReplicatedToDoList::new(ReplicaId(id)))
+            .collect();
+        let mut sent: Vec<ListState> = Vec::new();
+        for _ in 0..30 {
+            let r = random(replicas.len());
+            let replica = &mut replicas[r];
+            let dots: Vec<_> = replica.iter().map(|(dot, _)| dot).collect();
+            let pick = |i: usize| dots.get(i % dots.len().max(1)).copied();
+            match (random(7), pick(random(8))) {
+                (0 | 1, _) | (_, None) => {
+                    replica
+                        .add_task(&format!("Task{}", random(4)), (random(5) + 1) as u8)
+                        .unwrap();
+                }
+                (2, Some(dot)) => replica.set_priority(dot, (random(5) + 1) as u8).unwrap(),
+                (3, Some(dot)) => replica
+                    .edit_description(dot, &format!("Edited{}", random(4)))
+                    .unwrap(),
+                (4, Some(dot)) => replica.mark_done(dot).unwrap(),
+                (5, Some(dot)) => replica.remove(dot).unwrap(),
+                (_, Some(_)) => {
+                    let other = random(replicas.len());
+                    let state = replicas[other].state().clone();
+                    replicas[r].join(&state);
+                }
+            }
+            sent.push(replicas[r].state().clone());
+        }
+
+        for a in &sent {
+            assert!(a.join(a) == *a);
+            let b = &sent[random(sent.len())];
+            let c = &sent[random(sent.len())];
+            assert!(a.join(b) == b.join(a));
+            assert!(a.join(b).join(c) == a.join(&b.join(c)));
+        }
+
+        let everything = sent.iter().fold(ListState::default(), |all, s| all.join(s));
+        for id in 10..15 {
+            let mut late = ReplicatedToDoList::new(ReplicaId(id));
+            let mut pending = sent.clone();
+            while !pending.is_empty() {
+                let state = pending.swap_remove(random(pending.len()));
+                late.join(&state);
+                // Some states arrive more than once.
+                if random(4) == 0 {
+                    late.join(&state);
+                }
+            }
+            assert!(*late.state() == everything);
+        }
+    }
+
+    // --- EIGHTEENTH PART ---
+    println!("\n--- EIGHTEENTH PART ---");
+    println!("Test the event log");
+
+    use to_do_list::{EventKind, EventLogError, EventSourcedToDoList};
+    let log_file = std::env::temp_dir().join("todo_events_demo.log");
+    let snapshot_file = std::env::temp_dir().join("todo_events_demo.log.snapshot");
+    let _ = std::fs::remove_file(&log_file);
+    let _ = std::fs::remove_file(&snapshot_file);
+    let log_clock = Arc::new(to_do_list::FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 3, 10, 9, 0, 0).unwrap(),
+    ));
+    let open = || {
+        EventSourcedToDoList::open(&log_file)
+            .unwrap()
+            .with_clock(log_clock.clone())
+            .with_snapshot_every(4)
+    };
+
+    let mut logged = open().with_actor("alice");
+    let milk = logged.add_task("Buy milk", 2).unwrap();
+    log_clock.advance(Duration::hours(1));
+    let report = logged.add_task("Write report", 1).unwrap();
+    assert!(matches!(
+        logged.add_task("Write report", 9),
+        Err(EventLogError::Task(ToDoError::PriorityOutOfRange {
+            priority: 9,
+            ..
+        }))
+    ));
+    let mut routine = ToDoList::new();
+    let stretch = routine.add_task("Stretch", 3).unwrap();
+    routine
+        .set_recurrence(stretch, Some("FREQ=DAILY".parse().unwrap()))
+        .unwrap();
+    let stretch = logged.merge(&routine).unwrap().added[0];
+
+    let mut logged = logged.with_actor("bob");
+    log_clock.advance(Duration::days(1));
+    logged.mark_done("Buy milk").unwrap();
+    logged.mark_done_by_id(stretch).unwrap();
+    // Already done: nothing happens and nothing is logged.
+    logged.mark_done_by_id(milk).unwrap();
+    assert!(snapshot_file.exists());
+    assert!(logged.remove_done().unwrap() == 2);
+    logged.list().print();
+
+    for event in logged.history(milk).unwrap() {
+        println!("{} {} {:?}", event.at, event.actor, event.kind);
+    }
+    let lifecycle: Vec<_> = logged
+        .history(milk)
+        .unwrap()
+        .into_iter()
+        .map(|e| (e.seq, e.actor, e.kind))
+        .collect();
+    assert!(matches!(&lifecycle[..], [
+        (1, alice, EventKind::Added { .. }),
+        (4, bob, EventKind::Done { next: None, .. }),
+        (6, _, EventKind::RemovedDone { tasks }),
+    ] if alice == "alice" && bob == "bob" && tasks.contains(&milk)));
+    let next_stretch = match &logged.history(stretch).unwrap()[..] {
+        [merged, done, removed] => {
+            assert!(matches!(removed.kind, EventKind::RemovedDone { .. }));
+            assert!(matches!(merged.kind, EventKind::Merged { .. }));
+            match done.kind {
+                EventKind::Done {
+                    next: Some(next), ..
+                } => next,
+                _ => panic!("completing a recurring task spawns the next one"),
+            }
+        }
+        _ => panic!("unexpected history"),
+    };
+    assert!(logged
+        .list()
+        .get(next_stretch)
+        .unwrap()
+        .recurrence()
+        .is_some());
+
+    // Reopening replays the events after the snapshot, or all of them
+    // without one, and gives the same list, timestamps included.
+    assert!(open().list().iter().eq(logged.list().iter()));
+    std::fs::remove_file(&snapshot_file).unwrap();
+    assert!(open().list().iter().eq(logged.list().iter()));
+
+    // Compacting forgets the tasks that are gone.
+    assert!(logged.compact().unwrap() == 4);
+    assert!(logged.history(milk).unwrap().is_empty());
+    assert!(logged.history(report).unwrap().len() == 1);
+    let mut reopened = open();
+    assert!(reopened.list().iter().eq(logged.list().iter()));
+    let bills = reopened.add_task("Pay bills", 2).unwrap();
+    assert!(reopened.history(bills).unwrap()[0].seq == 7);
+    assert!(open().list().get(bills).is_some());
+
+    // A log that does not replay to what it recorded is refused.
+    std::fs::write(
+        &log_file,
+        concat!(
+            r#"{"seq":1,"at":"2025-03-10T09:00:00Z","actor":"eve","#,
+            r#""type":"added","task":7,"description":"Forged","priority":1}"#,
+            "\n"
+        ),
+    )
+    .unwrap();
+    std::fs::remove_file(&snapshot_file).unwrap();
+    assert!(matches!(
+        EventSourcedToDoList::open(&log_file),
+        Err(EventLogError::Diverged { seq: 1 })
+    ));
+    let _ = std::fs::remove_file(&log_file);
+
+    // --- NINETEENTH PART ---
+    println!("\n--- NINETEENTH PART ---");
+    println!("Test validation policies");
+
+    use to_do_list::{PolicyError, ValidationPolicy};
+    // Lengths count characters as a reader sees them, not bytes.
+    let mut italian = ToDoList::new();
+    let coffee = "Perché è già ora: più caffè, e poi tè 🇮🇹";
+    assert!(coffee.len() > 40 && italian.add_task(coffee, 2).is_ok());
+    assert!(matches!(
+        italian.add_task(&format!("{}!", "è".repeat(40)), 2),
+        Err(ToDoError::DescriptionTooLong { len: 41, max: 40 })
+    ));
+
+    let policy_file = std::env::temp_dir().join("todo_policy_demo.json");
+    std::fs::write(
+        &policy_file,
+        r#"{ "max_description_len": 20, "max_priority": 9,
+             "allow_duplicates": false, "allow_blank": false }"#,
+    )
+    .unwrap();
+    let policy = ValidationPolicy::load(&policy_file).unwrap();
+    let _ = std::fs::remove_file(&policy_file);
+    assert!(policy.priorities() == (1..=9) && policy.max_description_len == 20);
+
+    let mut strict = ToDoList::new().with_policy(policy.clone());
+    let tea = strict.add_task("Buy tea", 9).unwrap();
+    assert!(strict.add_task("   ", 1) == Err(ToDoError::BlankDescription));
+    assert!(
+        strict.add_task("Buy tea", 1) == Err(ToDoError::DuplicateDescription("Buy tea".into()))
+    );
+    assert!(matches!(
+        strict.add_task("Call the plumber today", 1),
+        Err(ToDoError::DescriptionTooLong { len: 22, max: 20 })
+    ));
+    assert!(matches!(
+        strict.add_task("Nap", 10),
+        Err(ToDoError::PriorityOutOfRange { max: 9, .. })
+    ));
+    let water = strict.add_task("Water plants", 3).unwrap();
+    assert!(strict.edit_description(water, "Buy tea").is_err());
+    assert!(strict.set_priority(tea, 8).is_ok());
+    // A done task is no duplicate, so the same errand can come back.
+    strict.mark_done_by_id(tea).unwrap();
+    let tea_again = strict.add_task("Buy tea", 9).unwrap();
+
+    // Merges and imports follow the same policy.
+    let mut incoming = ToDoList::new();
+    incoming.add_task("Buy tea", 1).unwrap();
+    incoming.add_task("", 2).unwrap();
+    incoming.add_task("Fix the bike", 4).unwrap();
+    let report = strict.merge(&incoming);
+    assert!(report.added.len() == 1 && report.rejected.len() == 2);
+    let report = strict.import_todo_txt("(F) Call mum\n(Z) Later\n(A) Water plants\n");
+    assert!(report.added.len() == 1 && report.skipped.len() == 2);
+    assert!(strict
+        .iter()
+        .any(|t| t.description() == "Call mum" && t.priority() == 6));
+    // A done item is no duplicate of an open task.
+    let report = strict.import_todo_txt("x 2025-03-01 Buy tea\nBuy tea\n");
+    assert!(report.added.len() == 1 && report.skipped.len() == 1);
+
+    // So do three-way merges: refused changes keep our version, and the
+    // subtasks of a refused new task move up.
+    let mut base = ToDoList::new();
+    let trip = base.add_task("Plan trip", 1).unwrap();
+    let pack = base.add_task("Pack", 2).unwrap();
+    let mut ours = copy(&base).with_policy(policy.clone());
+    let mut theirs = copy(&base);
+    theirs
+        .edit_description(trip, "Plan the trip to the coast")
+        .unwrap();
+    theirs.edit_description(pack, "Pack bags").unwrap();
+    let twin = theirs.add_task("Pack bags", 3).unwrap();
+    let hotel = theirs.add_subtask(twin, "Book hotel", 2).unwrap();
+    let result = ours.merge3(&base, &theirs, MergeStrategy::Theirs);
+    let rejected: Vec<_> = result.rejected.iter().map(|(id, _)| *id).collect();
+    assert!(rejected == [trip, twin]);
+    assert!(matches!(
+        result.rejected[0].1,
+        ToDoError::DescriptionTooLong { len: 26, max: 20 }
+    ));
+    assert!(ours.get(trip).unwrap().description() == "Plan trip");
+    assert!(ours.get(pack).unwrap().description() == "Pack bags");
+    assert!(result.added.len() == 1 && ours.parent(result.added[0]).is_none());
+    assert!(ours.get(result.added[0]).unwrap().description() == "Book hotel");
+    assert!(theirs.parent(hotel) == Some(twin));
+    strict.print();
+
+    // Saved lists are checked again when loaded, against a policy that
+    // allows what they hold.
+    let strict_file = std::env::temp_dir().join("todo_policy_list_demo.json");
+    strict.save(&strict_file).unwrap();
+    assert!(matches!(
+        ToDoList::load(&strict_file),
+        Err(to_do_list::StorageError::InvalidTask { .. })
+    ));
+    let loaded = ToDoList::load_with_policy(&strict_file, policy).unwrap();
+    let _ = std::fs::remove_file(&strict_file);
+    assert!(loaded.iter().eq(strict.iter()));
+    assert!(loaded.get(tea_again).is_some() && loaded.policy().max_priority == 9);
+
+    assert!(matches!(
+        ValidationPolicy::from_json(r#"{ "min_priority": 4, "max_priority": 2 }"#),
+        Err(PolicyError::EmptyPriorityRange { min: 4, max: 2 })
+    ));
+    assert!(matches!(
+        ValidationPolicy::from_json(r#"{ "max_lenght": 10 }"#),
+        Err(PolicyError::Parse(_))
+    ));
+    assert!(ValidationPolicy::from_json("{}").unwrap() == ValidationPolicy::default());
+
+    // --- TWENTIETH PART ---
+    println!("\n--- TWENTIETH PART ---");
+    println!("Test search");
+
+    use to_do_list::{SearchHit, TaskId};
+    let mut found = ToDoList::new();
+    let milk = found.add_task("Buy milk", 2).unwrap();
+    let bread = found.add_task("Buy bread and milk", 3).unwrap();
+    let plumber = found.add_task("Call the plumber", 1).unwrap();
+    let report = found.add_task("Write quarterly report", 2).unwrap();
+    found.add_tag(report, "+finance").unwrap();
+    let ids =
+        |hits: Vec<SearchHit>| -> Vec<TaskId> { hits.iter().map(|hit| hit.task.id()).collect() };
+    for hit in found.search("milk") {
+        println!(
+            "{} {} - {}",
+            hit.score,
+            hit.task.priority(),
+            hit.task.description()
+        );
+    }
+    assert!(ids(found.search("MILK")) == [milk, bread]);
+    assert!(ids(found.search("plumb")) == [plumber]);
+    assert!(ids(found.search("plumbre")) == [plumber]);
+    assert!(ids(found.search("quartelry")) == [report]);
+    assert!(ids(found.search("finance")) == [report]);
+    assert!(ids(found.search("buy milk")) == [milk, bread]);
+    assert!(ids(found.search("buy bread")) == [bread]);
+    assert!(found.search("car").is_empty() && found.search(" ,; ").is_empty());
+    // Exact words rank above prefixes, which rank above typos.
+    let mill = found.add_task("Visit the mill", 1).unwrap();
+    let hits = found.search("mill");
+    assert!(hits[0].task.id() == mill && hits[0].score > hits[1].score);
+
+    // The index follows every change, undo included.
+    found
+        .edit_description(plumber, "Call the electrician")
+        .unwrap();
+    assert!(found.search("plumber").is_empty());
+    assert!(ids(found.search("electric")) == [plumber]);
+    found.undo();
+    assert!(ids(found.search("plumber")) == [plumber]);
+    // Typos that drop a letter reach words one length up.
+    assert!(ids(found.search("plumbr")) == [plumber]);
+    found.remove(mill).unwrap();
+    assert!(ids(found.search("mill")) == [milk, bread]);
+
+    assert!(matches!(
+        found.mark_done_matching("buy"),
+        Err(ToDoError::AmbiguousMatch { candidates, .. }) if candidates == [milk, bread]
+    ));
+    assert!(found.mark_done_matching("buy milk").is_err());
+    assert!(found.mark_done_matching("bread") == Ok(bread));
+    // Done tasks are left out, so "buy" is no longer ambiguous.
+    assert!(found.mark_done_matching("buy") == Ok(milk));
+    assert!(found.mark_done_matching("buy") == Err(ToDoError::NoMatch("buy".into())));
+
+    // Property test: after random changes and undos, searching gives the
+    // same results as on a copy of the list indexed from scratch.
+    let words = [
+        "buy", "milk", "bread", "call", "mum", "fix", "bike", "report",
+    ];
+    let queries = [
+        "buy", "mil", "brad", "call mum", "bike", "reprot", "fix bik",
+    ];
+    let mut seed: u64 = 0x853c_49e6_748f_ea9b;
+    let mut random = move |n: usize| xorshift(&mut seed, n);
+    for _ in 0..100 {
+        let mut list = ToDoList::new();
+        for _ in 0..20 {
+            let ids: Vec<TaskId> = list.iter().map(|t| t.id()).collect();
+            let pick = ids.get(random(ids.len().max(1))).copied();
+            let phrase = format!("{} {}", words[random(8)], words[random(8)]);
+            match (random(7), pick) {
+                (0 | 1, _) | (_, None) => {
+                    list.add_task(&phrase, (random(5) + 1) as u8).unwrap();
+                }
+                (2, Some(id)) => list.edit_description(id, &phrase).unwrap(),
+                (3, Some(id)) => list.add_tag(id, words[random(8)]).unwrap(),
+                (4, Some(id)) => {
+                    list.remove(id).unwrap();
+                }
+                (5, Some(id)) => list.set_priority(id, (random(5) + 1) as u8).unwrap(),
+                (_, Some(_)) => {
+                    list.undo();
+                }
+            }
+        }
+        let fresh = ToDoList::from_json(&list.to_json().unwrap()).unwrap();
+        for query in queries {
+            assert!(list.search(query) == fresh.search(query));
+        }
+    }
+
+    // --- TWENTY-FIRST PART ---
+    println!("\n--- TWENTY-FIRST PART ---");
+    println!("Test reports");
+
+    use to_do_list::{DayRow, PriorityRow};
+    let standup_clock = Arc::new(to_do_list::FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap(),
+    ));
+    let mut standup = ToDoList::new().with_clock(standup_clock.clone());
+    let deploy = standup.add_task("Deploy the release", 1).unwrap();
+    let notes = standup.add_task("Write release notes", 2).unwrap();
+    standup.add_task("Update the changelog", 2).unwrap();
+    standup_clock.advance(Duration::days(1));
+    standup.add_task("Plan the retro", 3).unwrap();
+    standup.mark_done_by_id(deploy).unwrap();
+    standup_clock.advance(Duration::days(1));
+    standup.mark_done_by_id(notes).unwrap();
+    standup_clock.advance(Duration::days(1));
+
+    let date = |day| NaiveDate::from_ymd_opt(2025, 3, day).unwrap();
+    let stats = standup.report(date(1), date(5));
+    println!("{}", stats.to_table());
+    println!("{}", stats.burndown(3));
+    assert!(stats.as_of == Utc.with_ymd_and_hms(2025, 3, 4, 9, 0, 0).unwrap());
+    assert!(
+        stats.priorities[..3]
+            == [
+                PriorityRow {
+                    priority: 1,
+                    open: 0,
+                    done: 1
+                },
+                PriorityRow {
+                    priority: 2,
+                    open: 1,
+                    done: 1
+                },
+                PriorityRow {
+                    priority: 3,
+                    open: 1,
+                    done: 0
+                },
+            ]
+    );
+    assert!(stats.priorities.len() == 5);
+    // The open tasks are three and two days old.
+    assert!(stats.average_open_age == Some(Duration::hours(60)));
+    assert!(stats
+        .to_table()
+        .contains("Average age of open tasks: 2.5 days"));
+    let open: Vec<usize> = stats.days.iter().map(|d| d.open).collect();
+    assert!(open == [3, 3, 2, 2, 2]);
+    assert!(
+        stats.days[1]
+            == DayRow {
+                date: date(2),
+                created: 1,
+                completed: 1,
+                open: 3,
+                completion_rate: Some(0.25),
+            }
+    );
+    assert!(stats.priorities_csv().lines().nth(2) == Some("2,1,1"));
+    let csv = stats.days_csv();
+    assert!(csv.lines().next() == Some("date,created,completed,open,completion_rate"));
+    assert!(csv.lines().nth(3) == Some("2025-03-03,0,1,2,0.500"));
+    assert!(
+        stats.burndown(3)
+            == "Open tasks, 2025-03-01 to 2025-03-05\n\
+                3 |##\n  |#####\n  |#####\n0 +-----\n   03-01\n"
+    );
+
+    // The same list reports the same numbers whenever it is asked.
+    assert!(standup.report(date(1), date(5)) == stats);
+    // Days before the first task have no completion rate.
+    let early = standup.report(date(1) - Duration::days(1), date(1));
+    assert!(early.days[0].open == 0 && early.days[0].completion_rate.is_none());
+    assert!(early.days_csv().lines().nth(1) == Some("2025-02-28,0,0,0,"));
+    let empty = standup.report(date(5), date(1));
+    assert!(empty.days.is_empty() && empty.burndown(3) == "(no days)\n");
+    assert!(ToDoList::new()
+        .report(date(1), date(1))
+        .average_open_age
+        .is_none());
+
+    // --- TWENTY-SECOND PART ---
+    println!("\n--- TWENTY-SECOND PART ---");
+    println!("Test orderings");
+
+    use to_do_list::{Alphabetical, ByCreation, ByDueDate, ByPriority, TaskOrdering, Urgency};
+    let sort_clock = Arc::new(to_do_list::FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 4, 1, 9, 0, 0).unwrap(),
+    ));
+    let mut sorted = ToDoList::new()
+        .with_clock(sort_clock.clone())
+        .with_ordering(Urgency::default());
+    let water = sorted.add_task("water plants", 3).unwrap();
+    sort_clock.advance(Duration::days(1));
+    let rent = sorted.add_task("Pay rent", 2).unwrap();
+    sorted
+        .set_due(rent, NaiveDate::from_ymd_opt(2025, 4, 3))
+        .unwrap();
+    sort_clock.advance(Duration::days(1));
+    let milk = sorted.add_task("buy milk", 1).unwrap();
+    let flights = sorted.add_task("Book flights", 4).unwrap();
+    sorted
+        .set_due(flights, NaiveDate::from_ymd_opt(2025, 4, 2))
+        .unwrap();
+    let fertilize = sorted.add_subtask(water, "Fertilize", 5).unwrap();
+
+    let order = |list: &ToDoList, ordering: &dyn TaskOrdering| -> Vec<TaskId> {
+        list.sorted_by(ordering).iter().map(|t| t.id()).collect()
+    };
+    assert!(order(&sorted, &ByPriority) == [milk, rent, water, flights, fertilize]);
+    assert!(order(&sorted, &ByDueDate) == [flights, rent, milk, water, fertilize]);
+    assert!(order(&sorted, &ByCreation) == [water, rent, milk, flights, fertilize]);
+    assert!(order(&sorted, &Alphabetical) == [flights, milk, fertilize, rent, water]);
+    assert!(order(&sorted, &Urgency::default()) == [rent, flights, milk, water, fertilize]);
+    let now = Utc.with_ymd_and_hms(2025, 4, 3, 9, 0, 0).unwrap();
+    let score = |id| Urgency::default().score(sorted.get(id).unwrap(), now);
+    assert!(score(rent) == -6.25 && score(flights) == -13.25 && score(water) == -34.0);
+    sorted.print_by(&Urgency::default());
+    println!();
+    sorted.print_by(&Alphabetical);
+
+    // Done tasks go last, and the index follows undo.
+    sorted.mark_done_by_id(rent).unwrap();
+    assert!(order(&sorted, &Urgency::default()) == [flights, milk, water, fertilize, rent]);
+    sorted.undo();
+    assert!(order(&sorted, &Urgency::default())[0] == rent);
+    // Other weights make another ordering, with an index of its own.
+    let by_age = Urgency {
+        priority_weight: 0,
+        due_weight: 0,
+        ..Urgency::default()
+    };
+    sorted.keep_ordered(by_age);
+    sorted.keep_ordered(Urgency::default());
+    assert!(
+        sorted.active_orderings().collect::<Vec<_>>() == ["urgency(4,2,1,14)", "urgency(0,0,1,14)"]
+    );
+    assert!(order(&sorted, &by_age) == order(&sorted, &ByCreation));
+    assert!(sorted.forget_ordering("urgency(4,2,1,14)") && !sorted.forget_ordering("due"));
+    assert!(to_do_list::ordering_named("urgency").unwrap().name() == "urgency(4,2,1,14)");
+    assert!(to_do_list::ordering_named("size").is_none());
+
+    // Property test: after random changes, undos and redos, every kept index
+    // gives the same order as sorting a copy of the list that keeps none.
+    let orderings: [&dyn TaskOrdering; 6] = [
+        &ByPriority,
+        &ByDueDate,
+        &ByCreation,
+        &Alphabetical,
+        &Urgency::default(),
+        &by_age,
+    ];
+    let names = ["Tea", "tea", "Rent", "bike", "Mum", "plants"];
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move |n: usize| xorshift(&mut seed, n);
+    for _ in 0..100 {
+        let clock = Arc::new(to_do_list::FixedClock::new(
+            Utc.with_ymd_and_hms(2025, 4, 1, 9, 0, 0).unwrap(),
+        ));
+        let mut list = ToDoList::new()
+            .with_clock(clock.clone())
+            .with_ordering(ByDueDate)
+            .with_ordering(Alphabetical)
+            .with_ordering(Urgency::default())
+            .with_ordering(by_age);
+        for _ in 0..25 {
+            clock.advance(Duration::hours(random(30) as i64));
+            let ids: Vec<TaskId> = list.iter().map(|t| t.id()).collect();
+            let pick = ids.get(random(ids.len().max(1))).copied();
+            let due =
+                NaiveDate::from_ymd_opt(2025, 4, 1).map(|d| d + Duration::days(random(9) as i64));
+            match (random(9), pick) {
+                (0 | 1, _) | (_, None) => {
+                    list.add_task(names[random(6)], (random(5) + 1) as u8)
+                        .unwrap();
+                }
+                (2, Some(id)) => list.edit_description(id, names[random(6)]).unwrap(),
+                (3, Some(id)) => list.set_priority(id, (random(5) + 1) as u8).unwrap(),
+                (4, Some(id)) => list.set_due(id, due.filter(|_| random(3) > 0)).unwrap(),
+                (5, Some(id)) => {
+                    let _ = list.mark_done_by_id(id);
+                }
+                (6, Some(id)) => {
+                    list.remove(id).unwrap();
+                }
+                (7, Some(_)) => {
+                    list.undo();
+                }
+                (_, Some(_)) => {
+                    list.redo();
+                }
+            }
+        }
+        let fresh = ToDoList::from_json(&list.to_json().unwrap()).unwrap();
+        assert!(fresh.active_orderings().count() == 0);
+        for ordering in orderings {
+            assert!(order(&list, ordering) == order(&fresh, ordering));
+        }
+    }
+
+    // Adding and finding a task does not walk the list, so a large one
+    // stays quick to build.
+    let mut large = ToDoList::new().with_policy(ValidationPolicy {
+        allow_duplicates: false,
+        ..ValidationPolicy::default()
+    });
+    let ids: Vec<TaskId> = (0..20_000)
+        .map(|i| {
+            large
+                .add_task(&format!("Task {}", i), (i % 5 + 1) as u8)
+                .unwrap()
+        })
+        .collect();
+    assert!(large.add_task("Task 123", 1).is_err());
+    assert!(large.get(ids[12_345]).unwrap().description() == "Task 12345");
+    large.set_priority(ids[0], 5).unwrap();
+    assert!(large.iter().last().unwrap().id() == ids[0]);
+    large.undo();
+    assert!(large.iter().next().unwrap().id() == ids[0]);
+    for &id in &ids[..1000] {
+        large.mark_done_by_id(id).unwrap();
+    }
+    assert!(large.remove_done().unwrap() == 1000);
+    assert!(large.iter().count() == 19_000 && large.get(ids[999]).is_none());
+
+    // --- TWENTY-THIRD PART ---
+    println!("\n--- TWENTY-THIRD PART ---");
+    println!("Test task stores");
+
+    use to_do_list::{FileStore, SqliteStore, StoreError};
+    let file = std::env::temp_dir().join("exercises-day-5-store.json");
+    let _ = std::fs::remove_file(&file);
+    let mut stored = ToDoList::open(FileStore::open(&file).unwrap()).unwrap();
+    stored.add_task("Water plants", 3).unwrap();
+    stored.print();
+    // The file is a list like `save` writes.
+    assert!(ToDoList::load(&file).unwrap().iter().count() == 1);
+    let _ = std::fs::remove_file(&file);
+
+    // A database made by the first version of the schema is upgraded.
+    let db = std::env::temp_dir().join("exercises-day-5-store.db");
+    let _ = std::fs::remove_file(&db);
+    {
+        let old = rusqlite::Connection::open(&db).unwrap();
+        old.execute_batch(
+            "CREATE TABLE tasks (
+                 id INTEGER PRIMARY KEY,
+                 description TEXT NOT NULL,
+                 priority INTEGER NOT NULL,
+                 done INTEGER NOT NULL,
+                 created TEXT NOT NULL,
+                 completed TEXT
+             );
+             CREATE TABLE meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+             INSERT INTO meta (key, value) VALUES ('next_id', 8);
+             INSERT INTO tasks VALUES (7, 'Renew passport', 2, 0, '2025-05-01T10:00:00Z', NULL);
+             PRAGMA user_version = 1;",
+        )
+        .unwrap();
+    }
+    let upgraded = SqliteStore::open(&db).unwrap();
+    assert!(upgraded.schema_version().unwrap() == SqliteStore::SCHEMA_VERSION);
+    let mut passport = ToDoList::open(upgraded).unwrap();
+    passport.print();
+    let renew = passport.iter().next().unwrap();
+    assert!(renew.id() == TaskId::from(7) && renew.tags().count() == 0);
+    assert!(renew.created() == Utc.with_ymd_and_hms(2025, 5, 1, 10, 0, 0).unwrap());
+    passport.add_tag(TaskId::from(7), "errands").unwrap();
+    assert!(passport.add_task("Book photos", 3).unwrap() == TaskId::from(8));
+    drop(passport);
+    // And a database from a newer version is refused.
+    rusqlite::Connection::open(&db)
+        .unwrap()
+        .pragma_update(None, "user_version", 99)
+        .unwrap();
+    assert!(matches!(
+        SqliteStore::open(&db),
+        Err(StoreError::UnsupportedSchema(99))
+    ));
+    let _ = std::fs::remove_file(&db);
+
+    // When the store fails, an operation that can fail is undone and says so.
+    let dir = std::env::temp_dir().join("exercises-day-5-missing");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut offline = ToDoList::open(FileStore::open(dir.join("list.json")).unwrap()).unwrap();
+    let failed = offline.add_task("Call the bank", 1);
+    println!("{}", failed.as_ref().unwrap_err());
+    assert!(matches!(failed, Err(to_do_list::ToDoError::Store(_))));
+    assert!(offline.iter().count() == 0 && offline.is_synced());
+    // An import cannot fail, so its tasks stay in the list until the store
+    // works again.
+    let report = offline.import_todo_txt("(A) Call the bank\n");
+    assert!(report.added.len() == 1 && !offline.is_synced());
+    assert!(matches!(
+        offline.store_error(),
+        Some(StoreError::Storage(_))
+    ));
+    assert!(matches!(offline.sync(), Err(StoreError::Storage(_))));
+    std::fs::create_dir(&dir).unwrap();
+    offline.add_task("Pay the bill", 2).unwrap();
+    assert!(offline.is_synced() && offline.store_error().is_none());
+    let reopened = ToDoList::open(FileStore::open(dir.join("list.json")).unwrap()).unwrap();
+    assert!(reopened.iter().count() == 2);
+    assert!(reopened.iter().next().unwrap().id() == TaskId::from(1));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // --- TWENTY-FOURTH PART ---
+    println!("\n--- TWENTY-FOURTH PART ---");
+    println!("Test Markdown task lists");
+
+    let notes = "# Week 3
+
+Plans for the week, in no particular order.
+
+- [ ] Buy milk (P2)
+- [x] Call mum (P1) #phone
+
+## Garden work
+
+Only on dry days, see the [forecast](https://example.com).
+
+- [ ] Mow the lawn (P3)
+  - [x] Sharpen the blades (P4)
+  - [ ] Fill the tank
+* [ ] Rake leaves (P9)
+1. [X] Plant tulips (P2)
+- [x] Prune roses (P3)
+    - [ ] Buy shears (P1)
+- [ ] Water the pots (P300)
+
+```text
+- [ ] Not a task
+```
+
+- a plain bullet
+";
+    let line_of = |text: &str| notes.lines().position(|l| l.contains(text)).unwrap() + 1;
+    let mut garden = ToDoList::new().with_clock(clock.clone());
+    let report = garden.import_markdown(notes);
+    for (line, err) in &report.skipped {
+        println!("line {}: {}", line, err);
+    }
+    assert!(report.added.len() == 8);
+    assert!(report
+        .skipped
+        .iter()
+        .map(|(line, _)| *line)
+        .eq([line_of("Rake"), line_of("Water")]));
+    assert!(matches!(
+        report.skipped[1].1,
+        to_do_list::MarkdownError::InvalidPriority(_)
+    ));
+    garden.print();
+
+    let find = |list: &ToDoList, description: &str| {
+        list.iter()
+            .find(|t| t.description() == description)
+            .unwrap()
+            .id()
+    };
+    let call = garden.get(find(&garden, "Call mum")).unwrap();
+    assert!(call.is_done() && call.priority() == 1);
+    assert!(call.tags().eq(["phone", "week-3"]));
+    let mow = find(&garden, "Mow the lawn");
+    let sharpen = garden.get(find(&garden, "Sharpen the blades")).unwrap();
+    assert!(sharpen.parent() == Some(mow) && sharpen.tags().eq(["garden-work"]));
+    assert!(
+        garden
+            .get(find(&garden, "Fill the tank"))
+            .unwrap()
+            .priority()
+            == 5
+    );
+    assert!(garden.get(find(&garden, "Plant tulips")).unwrap().is_done());
+    // A done item with an open one inside it is reopened.
+    let prune = find(&garden, "Prune roses");
+    assert!(!garden.get(prune).unwrap().is_done());
+    assert!(garden.parent(find(&garden, "Buy shears")) == Some(prune));
+
+    let exported = garden.to_markdown();
+    print!("{}", exported);
+    let mut again = ToDoList::new().with_clock(clock.clone());
+    assert!(again.import_markdown(&exported).skipped.is_empty());
+    assert!(again.to_markdown() == exported);
+
+    // Descriptions that look like annotations, or hold line breaks, come
+    // back as they were.
+    let mut odd = ToDoList::new();
+    for description in [
+        "Fix bug #1 before (P1) release",
+        "Line one\nline two",
+        r"C:\temp \# and \n  twice",
+        "(p2) #tag",
+    ] {
+        odd.add_task(description, 3).unwrap();
+    }
+    let exported = odd.to_markdown();
+    print!("{}", exported);
+    assert!(exported.lines().count() == 4);
+    let mut again = ToDoList::new();
+    assert!(again.import_markdown(&exported).skipped.is_empty());
+    let fields = |list: &ToDoList| -> Vec<_> {
+        list.iter()
+            .map(|t| (t.description().to_string(), t.priority(), t.tags().count()))
+            .collect()
+    };
+    assert!(fields(&again) == fields(&odd));
+    assert!(odd.sync_markdown(&exported).unmatched.is_empty());
+    // A done item is no duplicate of an open task.
+    let mut unique = ToDoList::new().with_policy(to_do_list::ValidationPolicy {
+        allow_duplicates: false,
+        ..Default::default()
+    });
+    let report = unique.import_markdown("- [ ] Buy milk\n- [x] Buy milk\n- [ ] Buy milk\n");
+    assert!(report.added.len() == 2 && report.skipped.len() == 1);
+
+    // Ticking tasks in the list ticks them in the notes, and nothing else
+    // changes.
+    garden.mark_done("Buy milk").unwrap();
+    garden.complete_tree(mow).unwrap();
+    let sync = garden.sync_markdown(notes);
+    print!("{}", sync.text);
+    let changed = [
+        line_of("Buy milk"),
+        line_of("Mow"),
+        line_of("Fill"),
+        line_of("Prune"),
+    ];
+    assert!(sync.changed == changed);
+    assert!(sync.unmatched == [line_of("Rake"), line_of("Water")]);
+    for (number, (before, after)) in notes.lines().zip(sync.text.lines()).enumerate() {
+        let differ = before.chars().zip(after.chars()).filter(|(a, b)| a != b);
+        let expected = usize::from(changed.contains(&(number + 1)));
+        assert!(before.len() == after.len() && differ.count() == expected);
+    }
+    assert!(sync.text.contains("- [x] Buy milk (P2)\n"));
+    assert!(sync.text.contains("- [ ] Prune roses (P3)\n"));
+    assert!(sync.text.contains("- [ ] Not a task"));
+    assert!(garden.sync_markdown(&sync.text).changed.is_empty());
+
+    let file = std::env::temp_dir().join("exercises-day-5-notes.md");
+    std::fs::write(&file, notes.replace('\n', "\r\n")).unwrap();
+    let synced = garden.sync_markdown_file(&file).unwrap();
+    assert!(synced.changed == changed);
+    let written = std::fs::read_to_string(&file).unwrap();
+    assert!(written == sync.text.replace('\n', "\r\n"));
+    let _ = std::fs::remove_file(&file);
+}
+
+/// Steps the xorshift generator in `seed`, which must not be 0, and returns
+/// a number below `n`, for the property tests.
+fn xorshift(seed: &mut u64, n: usize) -> usize {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    (*seed % n as u64) as usize
 }
@@ -0,0 +1,438 @@
+//! A small HTTP/JSON API over a ToDoList file, for dashboards and scripts
+//! that cannot link the crate.
+//!
+//! | Request                | Body                              | Response             |
+//! |------------------------|-----------------------------------|----------------------|
+//! | `GET /tasks`           | none; `?filter=EXPR` optional     | 200, array of tasks  |
+//! | `POST /tasks`          | `{"description": .., "priority": ..}` | 201, the new task |
+//! | `PATCH /tasks/{id}`    | `{"done": true}` or `false`       | 200, the task        |
+//! | `DELETE /tasks/done`   | none                              | 200, `{"removed": n}` |
+//! | `POST /merge`          | a list document, as saved to disk | 200, `{"added": [..], "rejected": [..]}` |
+//!
+//! Errors come back as `{"error": "..."}` with status 400 for a malformed
+//! request, 404 for an unknown task or path, 405 for a wrong method, 413 for
+//! a body over 1 MiB, 415 for a body that is not sent as
+//! `application/json`, 421 for a `Host` other than the server itself, 422
+//! for a task the list refuses, 500 if the file cannot be written and 503
+//! when too many clients are connected.
+//!
+//! The server only listens on the loopback interface. So that web pages in
+//! a browser cannot use it, it only answers requests addressed to
+//! `127.0.0.1:<port>` or `localhost:<port>`, which rules out DNS rebinding,
+//! and only takes bodies a page cannot send without asking first.
+//!
+//! Every change is saved to the file before it is answered; if that fails,
+//! the change is undone, so that what clients see always matches what is on
+//! disk. Each connection carries one request, which must arrive within 5
+//! seconds; at most 32 connections are served at once.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::to_do_list::{Filter, SharedToDoList, StorageError, TaskId, ToDoError, ToDoList};
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY: usize = 1024 * 1024;
+/// Largest request line plus headers accepted, in bytes.
+const MAX_HEAD: u64 = 16 * 1024;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a client may take to accept the response.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most connections served at once; more are answered 503 straight away.
+const MAX_CONNECTIONS: usize = 32;
+/// How long a turned away client may take to finish sending its request.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// HTTP server for one list file.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    list: SharedToDoList,
+    file: Arc<Path>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    host: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewTask {
+    description: String,
+    priority: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskPatch {
+    done: bool,
+}
+
+impl Server {
+    /// Loads `file`, or starts from an empty list if it does not exist yet,
+    /// and listens on `127.0.0.1:port`. Port 0 picks a free port, which
+    /// `local_addr` then reports.
+    pub fn bind(port: u16, file: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let file: Arc<Path> = file.into().into();
+        let list = ToDoList::load_or_new(&file)?;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        Ok(Server {
+            listener,
+            list: SharedToDoList::new(list),
+            file,
+        })
+    }
+
+    /// Address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests until the process ends, each connection on its own
+    /// thread, at most `MAX_CONNECTIONS` at a time.
+    pub fn run(&self) -> io::Result<()> {
+        let port = self.local_addr()?.port();
+        let active = Arc::new(AtomicUsize::new(0));
+        for stream in self.listener.incoming() {
+            let stream = match stream.and_then(|stream| {
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                Ok(stream)
+            }) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("todo-server: accept failed: {}", err);
+                    continue;
+                }
+            };
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                turn_away(&stream);
+                continue;
+            }
+            let active = Arc::clone(&active);
+            let list = self.list.clone();
+            let file = Arc::clone(&self.file);
+            thread::spawn(move || {
+                if let Err(err) = serve(stream, port, &list, &file) {
+                    eprintln!("todo-server: {}", err);
+                }
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Answers 503 without a thread. The request is drained for a moment
+/// before closing, since closing with unread data resets the connection
+/// and the client may lose the answer.
+fn turn_away(stream: &TcpStream) {
+    let _ = write_response(stream, &error(503, "too many connections"));
+    let _ = stream.shutdown(Shutdown::Write);
+    let mut drain = Deadline {
+        stream,
+        end: Instant::now() + DRAIN_TIMEOUT,
+    };
+    let _ = io::copy(&mut drain.by_ref().take(MAX_HEAD), &mut io::sink());
+}
+
+/// Reads from a stream until a deadline, however slowly the data comes.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    end: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+fn serve(stream: TcpStream, port: u16, list: &SharedToDoList, file: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(Deadline {
+        stream: &stream,
+        end: Instant::now() + READ_TIMEOUT,
+    });
+    let response = match read_request(&mut reader)? {
+        Ok(request) => match check_origin(&request, port) {
+            Ok(()) => route(list, file, &request),
+            Err(response) => response,
+        },
+        Err(response) => response,
+    };
+    write_response(&stream, &response)
+}
+
+/// Reads one request. The outer error is an I/O failure; the inner one is
+/// the response to send back for a request that cannot be served.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let mut head = reader.by_ref().take(MAX_HEAD);
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(Err(error(400, "malformed request line")));
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None),
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    let (mut host, mut content_type) = (None, None);
+    loop {
+        line.clear();
+        if head.read_line(&mut line)? == 0 {
+            return Ok(Err(error(400, "incomplete request headers")));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(error(400, "malformed header")));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            match value.trim().parse() {
+                Ok(n) => length = n,
+                Err(_) => return Ok(Err(error(400, "invalid Content-Length"))),
+            }
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Ok(Err(error(400, "chunked bodies are not supported")));
+        }
+    }
+    if length > MAX_BODY {
+        return Ok(Err(error(413, "request body is too large")));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request {
+        method,
+        path,
+        query,
+        host,
+        content_type,
+        body,
+    }))
+}
+
+/// Refuses requests that may come from a web page: those addressed to
+/// another host, as after DNS rebinding, and those with a body in a type a
+/// page can send across origins without a preflight.
+fn check_origin(request: &Request, port: u16) -> Result<(), Response> {
+    let local = [format!("127.0.0.1:{}", port), format!("localhost:{}", port)];
+    let host = request.host.as_deref().unwrap_or_default();
+    if !local.iter().any(|local| local.eq_ignore_ascii_case(host)) {
+        return Err(error(421, "request is not addressed to this server"));
+    }
+    let json = request.content_type.as_deref().is_some_and(|value| {
+        let media_type = value.split(';').next().unwrap_or_default();
+        media_type.trim().eq_ignore_ascii_case("application/json")
+    });
+    if matches!(request.method.as_str(), "POST" | "PATCH") && !json {
+        return Err(error(415, "body must be sent as application/json"));
+    }
+    Ok(())
+}
+
+fn route(list: &SharedToDoList, file: &Path, request: &Request) -> Response {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["tasks"]) => get_tasks(list, request.query.as_deref()),
+        ("POST", ["tasks"]) => add_task(list, file, &request.body),
+        ("DELETE", ["tasks", "done"]) => remove_done(list, file),
+        ("PATCH", ["tasks", id]) => patch_task(list, file, id, &request.body),
+        ("POST", ["merge"]) => merge(list, file, &request.body),
+        (_, ["tasks"] | ["tasks", _] | ["merge"]) => Err(error(405, "method not allowed")),
+        _ => Err(error(404, "no such endpoint")),
+    };
+    result.unwrap_or_else(|response| response)
+}
+
+fn get_tasks(list: &SharedToDoList, query: Option<&str>) -> Result<Response, Response> {
+    let filter = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter_map(|pair| pair.strip_prefix("filter="))
+        .next_back()
+        .map(|expr| {
+            let expr = percent_decode(expr).ok_or_else(|| error(400, "invalid query encoding"))?;
+            Filter::parse(&expr).map_err(|err| error(400, &format!("invalid filter {}", err)))
+        })
+        .transpose()?;
+    let tasks = match filter {
+        Some(filter) => list.snapshot_where(&filter),
+        None => list.snapshot(),
+    };
+    Ok(ok(200, json!(tasks)))
+}
+
+fn add_task(list: &SharedToDoList, file: &Path, body: &[u8]) -> Result<Response, Response> {
+    let new: NewTask = parse_body(body)?;
+    let task = change(list, file, |list| {
+        let id = list.add_task(&new.description, new.priority)?;
+        Ok(list.get(id).cloned())
+    })?;
+    Ok(ok(201, json!(task)))
+}
+
+fn patch_task(
+    list: &SharedToDoList,
+    file: &Path,
+    id: &str,
+    body: &[u8],
+) -> Result<Response, Response> {
+    let id = id
+        .parse::<u64>()
+        .map(TaskId::from)
+        .map_err(|_| error(404, "no such task"))?;
+    let patch: TaskPatch = parse_body(body)?;
+    let task = change(list, file, |list| {
+        if patch.done {
+            list.mark_done_by_id(id)?;
+        } else {
+            list.reopen(id)?;
+        }
+        Ok(list.get(id).cloned())
+    })?;
+    Ok(ok(200, json!(task)))
+}
+
+fn remove_done(list: &SharedToDoList, file: &Path) -> Result<Response, Response> {
+    let removed = change(list, file, ToDoList::remove_done)?;
+    Ok(ok(200, json!({ "removed": removed })))
+}
+
+fn merge(list: &SharedToDoList, file: &Path, body: &[u8]) -> Result<Response, Response> {
+    let text = std::str::from_utf8(body).map_err(|_| error(400, "body is not UTF-8"))?;
+    let other = ToDoList::from_json(text).map_err(|err| error(400, &err.to_string()))?;
+    let report = change(list, file, |list| Ok(list.merge(&other)))?;
+    let rejected: Vec<Value> = report
+        .rejected
+        .iter()
+        .map(|(id, err)| json!({ "id": id, "error": err.to_string() }))
+        .collect();
+    Ok(ok(
+        200,
+        json!({ "added": report.added, "rejected": rejected }),
+    ))
+}
+
+/// Applies `op` and saves the list while holding the write lock, so that
+/// changes reach the file in the order they were made. If the list cannot
+/// be saved, `op` is undone.
+fn change<T>(
+    list: &SharedToDoList,
+    file: &Path,
+    op: impl FnOnce(&mut ToDoList) -> Result<T, ToDoError>,
+) -> Result<T, Response> {
+    let mut guard = list.write();
+    // Clients cannot undo, so the history only ever holds the change being
+    // made, ready to be reverted.
+    guard.clear_history();
+    let value = op(&mut guard).map_err(|err| {
+        let status = match err {
+            ToDoError::NotFound(_) | ToDoError::NoOpenTask(_) => 404,
+            ToDoError::Store(_) => 500,
+            _ => 422,
+        };
+        error(status, &err.to_string())
+    })?;
+    if let Err(err) = guard.save(file) {
+        guard.undo();
+        return Err(error(
+            500,
+            &format!("cannot save {}: {}", file.display(), err),
+        ));
+    }
+    Ok(value)
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, Response> {
+    serde_json::from_slice(body).map_err(|err| error(400, &format!("invalid body: {}", err)))
+}
+
+fn ok(status: u16, body: Value) -> Response {
+    Response { status, body }
+}
+
+fn error(status: u16, message: &str) -> Response {
+    Response {
+        status,
+        body: json!({ "error": message }),
+    }
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Decodes `%XX` escapes and `+` as a space, as used in query strings.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(b) = input.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -1,84 +1,780 @@
-#[derive(Debug)]
-struct Task {
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+mod clock;
+mod crdt;
+mod error;
+mod event_log;
+mod filter;
+mod history;
+mod ical;
+mod markdown;
+mod ordering;
+mod policy;
+mod recurrence;
+mod report;
+mod schedule;
+mod search;
+mod shared;
+mod sqlite;
+mod storage;
+mod store;
+mod three_way;
+mod todo_txt;
+mod tree;
+
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use crdt::{Dot, ListState, ReplicaId, ReplicatedToDoList, TaskRecord};
+pub use error::ToDoError;
+pub use event_log::{Event, EventKind, EventLogError, EventSourcedToDoList};
+pub use filter::{Comparison, Filter, FilterError};
+pub use ical::IcalError;
+pub use markdown::{MarkdownError, MarkdownSync};
+pub use ordering::{
+    ordering_named, Alphabetical, ByCreation, ByDueDate, ByPriority, KeyPart, TaskOrdering, Urgency,
+};
+pub use policy::{PolicyError, ValidationPolicy};
+pub use recurrence::{Frequency, Recurrence, RecurrenceError};
+pub use report::{DayRow, PriorityRow, Report};
+pub use search::SearchHit;
+pub use shared::SharedToDoList;
+pub use sqlite::SqliteStore;
+
+use history::{Edit, History};
+use ordering::OrderIndexes;
+use search::SearchIndex;
+pub use storage::{default_file, StorageError};
+pub use store::{FileStore, MemoryStore, StoreChange, StoreError, TaskStore};
+pub use three_way::{
+    Change, ChangeKind, Conflict, ConflictKind, Field, MergeStrategy, ThreeWayReport,
+};
+pub use todo_txt::TodoTxtError;
+
+/// Identifier of a task, unique within its list.
+///
+/// IDs are assigned in increasing order and never reused, so a task keeps
+/// its ID across `merge`, `remove_done` and every other operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TaskId(u64);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl From<u64> for TaskId {
+    fn from(id: u64) -> Self {
+        TaskId(id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Task {
+    id: TaskId,
     description: String,
     priority: u8,
     done: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<NaiveDate>,
+    created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<TaskId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<Recurrence>,
+}
+
+/// Where a task stands in list order: by priority, then by when it took its
+/// place among the tasks of that priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Slot {
+    priority: u8,
+    seq: u64,
 }
 
-pub struct ToDoList {
-    tasks: Vec<Task>,
+/// A list of tasks, kept in a `TaskStore`; by default only in memory.
+#[derive(Debug)]
+pub struct ToDoList<S = MemoryStore> {
+    /// The tasks in list order.
+    tasks: BTreeMap<Slot, Task>,
+    /// Where each task is in `tasks`.
+    slots: HashMap<TaskId, Slot>,
+    /// Sequence number of the next slot taken.
+    next_seq: u64,
+    /// Number of open tasks with each description, to find duplicates.
+    open: HashMap<String, usize>,
+    /// Where the subtasks of each task are in `tasks`, for `children`.
+    children: HashMap<TaskId, BTreeSet<Slot>>,
+    /// ID given to the next task added to this list.
+    next_id: u64,
+    clock: Arc<dyn Clock>,
+    history: History,
+    policy: ValidationPolicy,
+    /// Words of the tasks, for `search`.
+    index: SearchIndex,
+    /// Indexes of the active orderings, for `sorted_by`.
+    orderings: OrderIndexes,
+    store: S,
+    /// Tasks changed since the store was last written, with what the store
+    /// holds of them: `None` for a task it does not have.
+    unsaved: BTreeMap<TaskId, Option<Task>>,
+    /// `next_id` as the store holds it.
+    saved_next_id: u64,
+    /// Why the changes in `unsaved` could not be written, if they were tried.
+    store_error: Option<StoreError>,
+}
+
+impl Task {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    /// When the task was marked as done, if it is done.
+    pub fn completed(&self) -> Option<DateTime<Utc>> {
+        self.completed
+    }
+
+    /// Tags of the task, in alphabetical order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// ID of the task this one is a subtask of, if any.
+    pub fn parent(&self) -> Option<TaskId> {
+        self.parent
+    }
+
+    /// The rule by which the task comes back once done, if it recurs.
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+}
+
+impl Default for ToDoList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ToDoList {
     /// Creates a new, empty ToDoList.
     pub fn new() -> Self {
-        Self { tasks: Vec::new() }
+        Self::empty(MemoryStore::new())
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Creates an empty list on `store`, without reading it.
+    fn empty(store: S) -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            slots: HashMap::new(),
+            next_seq: 0,
+            open: HashMap::new(),
+            children: HashMap::new(),
+            next_id: 1,
+            clock: Arc::new(SystemClock),
+            history: History::default(),
+            policy: ValidationPolicy::default(),
+            index: SearchIndex::default(),
+            orderings: OrderIndexes::default(),
+            store,
+            unsaved: BTreeMap::new(),
+            saved_next_id: 1,
+            store_error: None,
+        }
+    }
+
+    /// Makes the list read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Makes the list check new tasks against `policy` instead of the
+    /// default one. Tasks already in the list are kept as they are.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &ValidationPolicy {
+        &self.policy
     }
 
     /// Adds a new task to the list.
     ///
-    /// Returns an error if the description is longer than 40 characters or if the priority
-    /// is not between 1 and 5 (inclusive). The new task is inserted so that the list remains
-    /// sorted by ascending priority (for equal priority, insertion order is preserved).
-    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<(), &'static str> {
-        if description.len() > 40 {
-            return Err("Description is too long");
-        }
-        if priority < 1 || priority > 5 {
-            return Err("Priority must be between 1 and 5");
-        }
-        let new_task = Task {
+    /// Returns an error if the task breaks the rules of the list's
+    /// `ValidationPolicy`; by default, if the description is longer than 40
+    /// characters or the priority is not between 1 and 5 (inclusive). The
+    /// new task is inserted so that the list remains sorted by ascending
+    /// priority (for equal priority, insertion order is preserved). On
+    /// success the ID assigned to the new task is returned.
+    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<TaskId, ToDoError> {
+        self.transaction("add task", |list| list.add(description, priority, None))
+    }
+
+    /// Validates and inserts a new open task, created now.
+    fn add(
+        &mut self,
+        description: &str,
+        priority: u8,
+        due: Option<NaiveDate>,
+    ) -> Result<TaskId, ToDoError> {
+        self.validate(description, priority, None)?;
+        Ok(self.insert_new(description, priority, due, false))
+    }
+
+    /// Validates and inserts a new task, created and completed now, as the
+    /// importers do for done items. A done task is no duplicate of anything,
+    /// as in `fill`.
+    fn add_done(
+        &mut self,
+        description: &str,
+        priority: u8,
+        due: Option<NaiveDate>,
+    ) -> Result<TaskId, ToDoError> {
+        self.policy.check(description, priority)?;
+        Ok(self.insert_new(description, priority, due, true))
+    }
+
+    fn insert_new(
+        &mut self,
+        description: &str,
+        priority: u8,
+        due: Option<NaiveDate>,
+        done: bool,
+    ) -> TaskId {
+        let id = self.allocate_id();
+        let created = self.clock.now();
+        self.insert_sorted(Task {
+            id,
             description: description.to_string(),
             priority,
-            done: false,
+            done,
+            due,
+            created,
+            completed: done.then_some(created),
+            tags: BTreeSet::new(),
+            parent: None,
+            recurrence: None,
+        });
+        id
+    }
+
+    /// Checks the policy of the list, wherever the task comes from. `task`
+    /// is the task being changed, which does not count as a duplicate of
+    /// itself.
+    fn validate(
+        &self,
+        description: &str,
+        priority: u8,
+        task: Option<TaskId>,
+    ) -> Result<(), ToDoError> {
+        self.policy.check(description, priority)?;
+        let duplicate = || {
+            let count = self.open.get(description).copied().unwrap_or(0);
+            let itself = task
+                .and_then(|id| self.get(id))
+                .is_some_and(|t| !t.done && t.description == description);
+            count > usize::from(itself)
         };
-        // Find the first task with a higher priority value (i.e., lower urgency)
-        // and insert before it. If none is found, insert at the end.
-        let pos = self
-            .tasks
-            .iter()
-            .position(|task| task.priority > priority)
-            .unwrap_or(self.tasks.len());
-        self.tasks.insert(pos, new_task);
+        if !self.policy.allow_duplicates && duplicate() {
+            return Err(ToDoError::DuplicateDescription(description.to_string()));
+        }
         Ok(())
     }
 
-    /// Prints all tasks in the list.
+    /// Reserves a fresh ID for a new task.
+    fn allocate_id(&mut self) -> TaskId {
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Inserts a task keeping the list sorted by ascending priority, after
+    /// the tasks of the same priority.
+    fn insert_sorted(&mut self, task: Task) {
+        let slot = Slot {
+            priority: task.priority,
+            seq: self.next_seq,
+        };
+        self.next_seq += 1;
+        self.insert_at(slot, task);
+    }
+
+    // Every change to `tasks` goes through `insert_at`, `remove_at` and
+    // `replace_at`, so that it can be recorded for undo, kept in the search
+    // and ordering indexes, and written to the store. Each costs O(log n).
+
+    fn insert_at(&mut self, slot: Slot, task: Task) {
+        self.unsaved.entry(task.id).or_insert(None);
+        self.history.log(|| Edit::Insert {
+            slot,
+            task: task.clone(),
+        });
+        self.index.insert(&task);
+        self.orderings.insert(&task);
+        count_open(&mut self.open, &task, true);
+        link_child(&mut self.children, slot, &task, true);
+        self.slots.insert(task.id, slot);
+        self.tasks.insert(slot, task);
+    }
+
+    fn remove_at(&mut self, slot: Slot) -> Task {
+        let task = self.tasks.remove(&slot).expect("slot is taken");
+        self.slots.remove(&task.id);
+        self.unsaved
+            .entry(task.id)
+            .or_insert_with(|| Some(task.clone()));
+        self.index.remove(&task);
+        self.orderings.remove(&task);
+        count_open(&mut self.open, &task, false);
+        link_child(&mut self.children, slot, &task, false);
+        self.history.log(|| Edit::Remove {
+            slot,
+            task: task.clone(),
+        });
+        task
+    }
+
+    fn replace_at(&mut self, slot: Slot, task: Task) -> Task {
+        let before = &self.tasks[&slot];
+        self.unsaved
+            .entry(before.id)
+            .or_insert_with(|| Some(before.clone()));
+        self.index.replace(before, &task);
+        self.orderings.replace(before, &task);
+        count_open(&mut self.open, before, false);
+        count_open(&mut self.open, &task, true);
+        link_child(&mut self.children, slot, before, false);
+        link_child(&mut self.children, slot, &task, true);
+        let place = self.tasks.get_mut(&slot).expect("slot is taken");
+        let before = std::mem::replace(place, task);
+        self.history.log(|| Edit::Replace {
+            slot,
+            before: before.clone(),
+            after: self.tasks[&slot].clone(),
+        });
+        before
+    }
+
+    /// Applies `change` to a copy of the task with the given ID and stores the
+    /// result in place. The priority must not be changed this way.
+    fn modify(&mut self, id: TaskId, change: impl FnOnce(&mut Task)) -> Result<(), ToDoError> {
+        let slot = self.position(id)?;
+        let mut task = self.tasks[&slot].clone();
+        change(&mut task);
+        debug_assert_eq!(task.priority, slot.priority);
+        if task != self.tasks[&slot] {
+            self.replace_at(slot, task);
+        }
+        Ok(())
+    }
+
+    /// Returns the slot of the task with the given ID.
+    fn position(&self, id: TaskId) -> Result<Slot, ToDoError> {
+        self.slots.get(&id).copied().ok_or(ToDoError::NotFound(id))
+    }
+
+    /// Returns the task with the given ID, if any.
+    pub fn get(&self, id: TaskId) -> Option<&Task> {
+        self.slots.get(&id).map(|slot| &self.tasks[slot])
+    }
+
+    /// Returns the tasks in list order (ascending priority).
+    pub fn iter(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.values()
+    }
+
+    /// Returns the tasks selected by `filter`, in list order.
+    pub fn iter_where<'a>(&'a self, filter: &'a Filter) -> impl Iterator<Item = &'a Task> {
+        self.tasks.values().filter(move |task| filter.matches(task))
+    }
+
+    /// Prints all tasks in the list, as a tree.
     ///
     /// Each task is printed on a new line in the format:
     /// "[ ] 1 - Task description" for tasks not done, and
     /// "[X] 1 - Task description" for tasks that are done.
+    /// Subtasks follow their parent, indented by two spaces per level.
+    /// `print_by` prints them in another order.
     pub fn print(&self) {
-        for task in &self.tasks {
-            let marker = if task.done { "[X]" } else { "[ ]" };
-            println!("{} {} - {}", marker, task.priority, task.description);
+        for (depth, task) in self.tree() {
+            print_task(depth, task);
+        }
+    }
+
+    /// Prints the tasks selected by `filter`, in the same format as `print`.
+    ///
+    /// A task is indented under its nearest ancestor that is also printed.
+    pub fn print_where(&self, filter: &Filter) {
+        for (depth, task) in self.tree_where(filter) {
+            print_task(depth, task);
         }
     }
 
     /// Merges another ToDoList into this one.
     ///
-    /// Tasks from the other list are added with their original description and priority,
-    /// but are marked as not done regardless of their state in the other list.
-    pub fn merge(&mut self, other: &ToDoList) {
-        for task in &other.tasks {
-            // Ignore any error here; we assume tasks in the other list are valid.
-            let _ = self.add_task(&task.description, task.priority);
+    /// Tasks from the other list are added with their original description, priority,
+    /// due date, tags and recurrence, but are marked as not done regardless of their state in the other list.
+    /// They get fresh IDs from this list; the IDs of tasks already here are left untouched.
+    ///
+    /// Tasks that `add_task` refuses are skipped and listed in the returned report.
+    /// Subtasks keep their place in the tree; a subtask whose parent was not added
+    /// goes under its nearest ancestor that was, or to the top level.
+    pub fn merge(&mut self, other: &ToDoList<impl TaskStore>) -> MergeReport {
+        self.record("merge", |list| {
+            list.merge_tasks(other, other.tasks.values())
+        })
+    }
+
+    /// Merges only the tasks of `other` selected by `filter`, as `merge` does.
+    pub fn merge_where(
+        &mut self,
+        other: &ToDoList<impl TaskStore>,
+        filter: &Filter,
+    ) -> MergeReport {
+        self.record("merge", |list| {
+            list.merge_tasks(other, other.iter_where(filter))
+        })
+    }
+
+    fn merge_tasks<'a>(
+        &mut self,
+        other: &ToDoList<impl TaskStore>,
+        tasks: impl Iterator<Item = &'a Task>,
+    ) -> MergeReport {
+        let mut report = MergeReport::default();
+        let mut new_ids = HashMap::new();
+        for task in tasks {
+            match self.add(&task.description, task.priority, task.due) {
+                Ok(id) => {
+                    self.modify(id, |t| {
+                        t.tags = task.tags.clone();
+                        t.recurrence = task.recurrence.clone();
+                    })
+                    .expect("task was just added");
+                    new_ids.insert(task.id, id);
+                    report.added.push(id);
+                }
+                Err(err) => report.rejected.push((task.id, err)),
+            }
         }
+        // Link the parents only once every task is in, since a subtask may
+        // come before its parent in list order.
+        for (&old_id, &id) in &new_ids {
+            let parent = other
+                .ancestors(old_id)
+                .find_map(|ancestor| new_ids.get(&ancestor).copied());
+            self.modify(id, |t| t.parent = parent)
+                .expect("task was just added");
+        }
+        report
     }
 
-    /// Marks the first not-done task with the given description as done.
-    pub fn mark_done(&mut self, description: &str) {
-        if let Some(task) = self
+    /// Marks the first not-done task with the given description as done and
+    /// returns its ID.
+    pub fn mark_done(&mut self, description: &str) -> Result<TaskId, ToDoError> {
+        let id = self
             .tasks
-            .iter_mut()
+            .values()
             .find(|t| t.description == description && !t.done)
-        {
-            task.done = true;
+            .map(|t| t.id)
+            .ok_or_else(|| ToDoError::NoOpenTask(description.to_string()))?;
+        self.mark_done_by_id(id)?;
+        Ok(id)
+    }
+
+    /// Marks the task with the given ID as done.
+    ///
+    /// Marking a task that is already done keeps its original completion time.
+    /// A task with open subtasks cannot be marked as done; use `complete_tree`
+    /// to complete it together with its subtasks. Completing a recurring task
+    /// adds its next occurrence, as described in `set_recurrence`, and
+    /// returns its ID.
+    pub fn mark_done_by_id(&mut self, id: TaskId) -> Result<Option<TaskId>, ToDoError> {
+        self.transaction("mark done", |list| {
+            Ok(list.complete(&[id])?.into_iter().next())
+        })
+    }
+
+    /// Marks the tasks with the given IDs as done, in order, then adds the
+    /// next occurrences of the recurring ones, so that a new occurrence does
+    /// not count as an open subtask of a parent completed after it. Returns
+    /// the IDs of the next occurrences.
+    fn complete(&mut self, ids: &[TaskId]) -> Result<Vec<TaskId>, ToDoError> {
+        let now = self.clock.now();
+        let mut completed = Vec::new();
+        for &id in ids {
+            if self.has_open_children(id) {
+                return Err(ToDoError::OpenSubtasks(id));
+            }
+            if self.tasks[&self.position(id)?].done {
+                continue;
+            }
+            self.modify(id, |task| {
+                task.done = true;
+                task.completed = Some(now);
+            })?;
+            completed.push(id);
+        }
+        let mut spawned = Vec::new();
+        for id in completed {
+            spawned.extend(self.spawn_next(id)?);
+        }
+        Ok(spawned)
+    }
+
+    /// Marks the task with the given ID as not done, along with its done
+    /// ancestors, since a done task cannot have open subtasks.
+    pub fn reopen(&mut self, id: TaskId) -> Result<(), ToDoError> {
+        self.transaction("reopen", |list| {
+            list.modify(id, |task| {
+                task.done = false;
+                task.completed = None;
+            })?;
+            list.reopen_ancestors(id)
+        })
+    }
+
+    /// Marks every open task selected by `filter` as done and returns their IDs,
+    /// in list order.
+    ///
+    /// Fails without changing anything if a selected task has open subtasks
+    /// that are not selected too.
+    pub fn mark_done_where(&mut self, filter: &Filter) -> Result<Vec<TaskId>, ToDoError> {
+        let ids: Vec<TaskId> = self
+            .iter_where(filter)
+            .filter(|task| !task.done)
+            .map(|task| task.id)
+            .collect();
+        // Subtasks before their parents, so that selecting a whole tree works.
+        let mut order: Vec<TaskId> = self.tree().into_iter().map(|(_, t)| t.id).collect();
+        order.retain(|id| ids.contains(id));
+        order.reverse();
+        self.transaction("mark done", |list| {
+            list.complete(&order)?;
+            Ok(ids)
+        })
+    }
+
+    /// Removes all tasks that are marked as done and returns how many were removed.
+    pub fn remove_done(&mut self) -> Result<usize, ToDoError> {
+        self.transaction("remove done", |list| {
+            Ok(list.remove_matching(|task| task.done))
+        })
+    }
+
+    /// Removes the done tasks selected by `filter` and returns how many were removed.
+    ///
+    /// The subtasks of a removed task are removed with it, even if the filter
+    /// does not select them; they are all done, as their parent is.
+    pub fn remove_done_where(&mut self, filter: &Filter) -> Result<usize, ToDoError> {
+        let mut ids = HashSet::new();
+        for task in self.iter_where(filter).filter(|task| task.done) {
+            ids.insert(task.id);
+            ids.extend(self.descendants(task.id)?);
+        }
+        self.transaction("remove done", |list| {
+            Ok(list.remove_matching(|task| ids.contains(&task.id)))
+        })
+    }
+
+    /// Removes every task for which `pred` holds and returns how many were removed.
+    fn remove_matching(&mut self, pred: impl Fn(&Task) -> bool) -> usize {
+        let slots: Vec<Slot> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| pred(task))
+            .map(|(slot, _)| *slot)
+            .collect();
+        for &slot in &slots {
+            self.remove_at(slot);
         }
+        slots.len()
+    }
+
+    /// Removes the task with the given ID and returns it.
+    ///
+    /// Its subtasks are kept and move up to its parent, or to the top level;
+    /// `remove_tree` removes them too.
+    pub fn remove(&mut self, id: TaskId) -> Result<Task, ToDoError> {
+        self.transaction("remove", |list| {
+            let pos = list.position(id)?;
+            list.lift_children(pos)?;
+            Ok(list.remove_at(pos))
+        })
+    }
+
+    /// Replaces the description of the task with the given ID.
+    ///
+    /// The new description must satisfy the same rules as in `add_task`.
+    pub fn edit_description(&mut self, id: TaskId, description: &str) -> Result<(), ToDoError> {
+        let pos = self.position(id)?;
+        self.validate(description, self.tasks[&pos].priority, Some(id))?;
+        self.transaction("edit description", |list| {
+            list.modify(id, |task| task.description = description.to_string())
+        })
     }
 
-    /// Removes all tasks that are marked as done.
-    pub fn remove_done(&mut self) {
-        self.tasks.retain(|task| !task.done);
+    /// Adds `tag` to the task with the given ID.
+    ///
+    /// Tags may contain letters, digits and `-`, `_`, `.` or `/`, so that they
+    /// can always be written in a filter as `tag:<name>`. They may also start
+    /// with `+` or `@`, like todo.txt projects and contexts.
+    pub fn add_tag(&mut self, id: TaskId, tag: &str) -> Result<(), ToDoError> {
+        self.position(id)?;
+        validate_tag(tag)?;
+        self.transaction("add tag", |list| {
+            list.modify(id, |task| {
+                task.tags.insert(tag.to_string());
+            })
+        })
+    }
+
+    /// Removes `tag` from the task with the given ID, returning whether it was there.
+    pub fn remove_tag(&mut self, id: TaskId, tag: &str) -> Result<bool, ToDoError> {
+        let mut removed = false;
+        self.transaction("remove tag", |list| {
+            list.modify(id, |task| removed = task.tags.remove(tag))
+        })?;
+        Ok(removed)
+    }
+
+    /// Changes the priority of the task with the given ID.
+    ///
+    /// The task is moved so that the list stays sorted; among tasks of its new
+    /// priority it goes last, as if it had just been added.
+    pub fn set_priority(&mut self, id: TaskId, priority: u8) -> Result<(), ToDoError> {
+        let pos = self.position(id)?;
+        self.validate(&self.tasks[&pos].description, priority, Some(id))?;
+        self.transaction("set priority", |list| {
+            let mut task = list.remove_at(pos);
+            task.priority = priority;
+            list.insert_sorted(task);
+            Ok(())
+        })
+    }
+}
+
+/// Outcome of `ToDoList::merge`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// IDs given to the tasks that were added to this list.
+    pub added: Vec<TaskId>,
+    /// Tasks of the other list that were not added, by their ID in the other
+    /// list, with the reason they were refused.
+    pub rejected: Vec<(TaskId, ToDoError)>,
+}
+
+/// Outcome of importing tasks from another format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportReport<E> {
+    /// IDs of the imported tasks, in file order.
+    pub added: Vec<TaskId>,
+    /// Entries that were skipped, by the 1-based line number where they
+    /// start, with the reason.
+    pub skipped: Vec<(usize, E)>,
+}
+
+impl<E> Default for ImportReport<E> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+fn print_task(depth: usize, task: &Task) {
+    let marker = if task.done { "[X]" } else { "[ ]" };
+    let indent = "  ".repeat(depth);
+    println!(
+        "{}{} {} - {}",
+        indent, marker, task.priority, task.description
+    );
+}
+
+/// Counts `task` in, or out of, the number of open tasks with its
+/// description, if it is open.
+/// Adds the task in `slot` to the subtasks of its parent, or takes it out.
+fn link_child(
+    children: &mut HashMap<TaskId, BTreeSet<Slot>>,
+    slot: Slot,
+    task: &Task,
+    added: bool,
+) {
+    let Some(parent) = task.parent else {
+        return;
+    };
+    if added {
+        children.entry(parent).or_default().insert(slot);
+    } else if let Some(slots) = children.get_mut(&parent) {
+        slots.remove(&slot);
+        if slots.is_empty() {
+            children.remove(&parent);
+        }
+    }
+}
+
+fn count_open(open: &mut HashMap<String, usize>, task: &Task, added: bool) {
+    if task.done {
+        return;
+    }
+    if added {
+        *open.entry(task.description.clone()).or_default() += 1;
+    } else if let Some(count) = open.get_mut(&task.description) {
+        *count -= 1;
+        if *count == 0 {
+            open.remove(&task.description);
+        }
+    }
+}
+
+/// Checks that a tag is non-empty and only uses the characters allowed in filters.
+fn validate_tag(tag: &str) -> Result<(), ToDoError> {
+    let name = tag.strip_prefix(['+', '@']).unwrap_or(tag);
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(ToDoError::InvalidTag(tag.to_string()))
     }
 }
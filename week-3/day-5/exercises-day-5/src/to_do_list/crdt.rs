@@ -0,0 +1,361 @@
+//! A replicated task list that converges without a central server.
+//!
+//! Every replica holds a full `ListState`, changes it locally and now and
+//! then sends it to the others, which `join` it into their own. `join` is
+//! commutative, associative and idempotent, so replicas that have seen the
+//! same changes hold the same state, whatever order the states arrived in
+//! and however often.
+//!
+//! The state is a set of tasks, each named by a `Dot` that only the replica
+//! that added it could have made, so two adds never clash. Description,
+//! priority and done are last-writer-wins registers, stamped with a Lamport
+//! clock so that a write seen by a replica always wins over the writes that
+//! replica had seen before it; concurrent writes are ordered by replica ID.
+//! Removed tasks leave a tombstone behind, and a removal wins over any
+//! concurrent change to the task.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ToDoError, ValidationPolicy};
+
+/// Identifier of a replica. Every replica of a list needs its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReplicaId(pub u32);
+
+/// A Lamport timestamp made unique by the replica that took it. Dots name
+/// tasks and stamp register writes; they are ordered by counter, then
+/// replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Dot {
+    counter: u64,
+    replica: ReplicaId,
+}
+
+/// Last-writer-wins register.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Lww<T> {
+    value: T,
+    stamp: Dot,
+}
+
+/// A task as held by a replica.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRecord {
+    description: Lww<String>,
+    priority: Lww<u8>,
+    done: Lww<bool>,
+}
+
+/// The replicated part of a list: what replicas exchange and join.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListState {
+    /// Tasks not removed, by the dot of the add that created them.
+    #[serde(with = "entries")]
+    tasks: BTreeMap<Dot, TaskRecord>,
+    /// Dots of removed tasks, kept so that a removal is not undone by a
+    /// replica that has not seen it yet.
+    removed: BTreeSet<Dot>,
+}
+
+/// One replica of a list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicatedToDoList {
+    replica: ReplicaId,
+    /// Highest counter seen in any dot, here or in a joined state.
+    clock: u64,
+    state: ListState,
+    /// Not replicated: each replica checks its own changes.
+    #[serde(skip)]
+    policy: ValidationPolicy,
+}
+
+impl fmt::Display for ReplicaId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "r{}", self.0)
+    }
+}
+
+impl fmt::Display for Dot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.replica, self.counter)
+    }
+}
+
+impl Dot {
+    pub fn replica(&self) -> ReplicaId {
+        self.replica
+    }
+}
+
+impl<T: Clone> Lww<T> {
+    fn join(&self, other: &Self) -> Self {
+        if other.stamp > self.stamp {
+            other.clone()
+        } else {
+            self.clone()
+        }
+    }
+}
+
+impl TaskRecord {
+    pub fn description(&self) -> &str {
+        &self.description.value
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority.value
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.value
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        TaskRecord {
+            description: self.description.join(&other.description),
+            priority: self.priority.join(&other.priority),
+            done: self.done.join(&other.done),
+        }
+    }
+
+    fn max_counter(&self) -> u64 {
+        let stamps = [self.description.stamp, self.priority.stamp, self.done.stamp];
+        stamps.iter().map(|s| s.counter).max().unwrap_or(0)
+    }
+}
+
+impl ListState {
+    /// Returns the least state that includes both `self` and `other`.
+    pub fn join(&self, other: &ListState) -> ListState {
+        let removed: BTreeSet<Dot> = self.removed.union(&other.removed).copied().collect();
+        let mut tasks = self.tasks.clone();
+        for (dot, record) in &other.tasks {
+            tasks
+                .entry(*dot)
+                .and_modify(|mine| *mine = mine.join(record))
+                .or_insert_with(|| record.clone());
+        }
+        tasks.retain(|dot, _| !removed.contains(dot));
+        ListState { tasks, removed }
+    }
+
+    /// Returns the live tasks sorted by priority, then by the order they
+    /// were added in, which is the same on every replica.
+    pub fn iter(&self) -> impl Iterator<Item = (Dot, &TaskRecord)> {
+        let mut tasks: Vec<(Dot, &TaskRecord)> = self.tasks.iter().map(|(d, r)| (*d, r)).collect();
+        tasks.sort_by_key(|(dot, record)| (record.priority(), *dot));
+        tasks.into_iter()
+    }
+
+    fn max_counter(&self) -> u64 {
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|(dot, record)| dot.counter.max(record.max_counter()));
+        let removed = self.removed.iter().map(|dot| dot.counter);
+        tasks.chain(removed).max().unwrap_or(0)
+    }
+}
+
+impl ReplicatedToDoList {
+    /// Creates an empty replica. `replica` must differ from the ID of every
+    /// other replica of the list.
+    pub fn new(replica: ReplicaId) -> Self {
+        Self {
+            replica,
+            clock: 0,
+            state: ListState::default(),
+            policy: ValidationPolicy::default(),
+        }
+    }
+
+    /// Makes the replica check its changes against `policy` instead of the
+    /// default one.
+    pub fn with_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn replica(&self) -> ReplicaId {
+        self.replica
+    }
+
+    /// The state to send to other replicas.
+    pub fn state(&self) -> &ListState {
+        &self.state
+    }
+
+    /// Takes in the changes of another replica.
+    pub fn join(&mut self, other: &ListState) {
+        self.state = self.state.join(other);
+        self.clock = self.clock.max(other.max_counter());
+    }
+
+    /// Adds a new task, with the same rules as `ToDoList::add_task`, and
+    /// returns its dot.
+    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<Dot, ToDoError> {
+        self.validate(description, priority, None)?;
+        let dot = self.tick();
+        let record = TaskRecord {
+            description: Lww {
+                value: description.to_string(),
+                stamp: dot,
+            },
+            priority: Lww {
+                value: priority,
+                stamp: dot,
+            },
+            done: Lww {
+                value: false,
+                stamp: dot,
+            },
+        };
+        self.state.tasks.insert(dot, record);
+        Ok(dot)
+    }
+
+    /// Replaces the description of a task, with the same rules as `add_task`.
+    pub fn edit_description(&mut self, dot: Dot, description: &str) -> Result<(), ToDoError> {
+        self.validate(description, self.record(dot)?.priority(), Some(dot))?;
+        let stamp = self.tick();
+        self.record_mut(dot)?.description = Lww {
+            value: description.to_string(),
+            stamp,
+        };
+        Ok(())
+    }
+
+    pub fn set_priority(&mut self, dot: Dot, priority: u8) -> Result<(), ToDoError> {
+        self.validate(self.record(dot)?.description(), priority, Some(dot))?;
+        let stamp = self.tick();
+        self.record_mut(dot)?.priority = Lww {
+            value: priority,
+            stamp,
+        };
+        Ok(())
+    }
+
+    pub fn mark_done(&mut self, dot: Dot) -> Result<(), ToDoError> {
+        self.set_done(dot, true)
+    }
+
+    pub fn reopen(&mut self, dot: Dot) -> Result<(), ToDoError> {
+        self.set_done(dot, false)
+    }
+
+    /// Removes a task for good, on every replica once they have joined.
+    pub fn remove(&mut self, dot: Dot) -> Result<(), ToDoError> {
+        self.record(dot)?;
+        self.state.tasks.remove(&dot);
+        self.state.removed.insert(dot);
+        Ok(())
+    }
+
+    /// Removes all tasks done and returns how many were removed.
+    pub fn remove_done(&mut self) -> usize {
+        let done: Vec<Dot> = self
+            .state
+            .tasks
+            .iter()
+            .filter(|(_, record)| record.is_done())
+            .map(|(dot, _)| *dot)
+            .collect();
+        for dot in &done {
+            self.state.tasks.remove(dot);
+            self.state.removed.insert(*dot);
+        }
+        done.len()
+    }
+
+    pub fn get(&self, dot: Dot) -> Option<&TaskRecord> {
+        self.state.tasks.get(&dot)
+    }
+
+    /// Returns the tasks in the same order on every replica; see `ListState::iter`.
+    pub fn iter(&self) -> impl Iterator<Item = (Dot, &TaskRecord)> {
+        self.state.iter()
+    }
+
+    /// Prints the tasks in the same format as `ToDoList::print`.
+    pub fn print(&self) {
+        for (_, record) in self.iter() {
+            let marker = if record.is_done() { "[X]" } else { "[ ]" };
+            println!(
+                "{} {} - {}",
+                marker,
+                record.priority(),
+                record.description()
+            );
+        }
+    }
+
+    fn set_done(&mut self, dot: Dot, done: bool) -> Result<(), ToDoError> {
+        self.record(dot)?;
+        let stamp = self.tick();
+        self.record_mut(dot)?.done = Lww { value: done, stamp };
+        Ok(())
+    }
+
+    /// Checks the policy as `ToDoList` does. Tasks added by other replicas
+    /// at the same time are not seen, so they may still end up duplicates.
+    fn validate(&self, description: &str, priority: u8, dot: Option<Dot>) -> Result<(), ToDoError> {
+        self.policy.check(description, priority)?;
+        let duplicate = || {
+            self.iter().any(|(other, record)| {
+                !record.is_done() && record.description() == description && Some(other) != dot
+            })
+        };
+        if !self.policy.allow_duplicates && duplicate() {
+            return Err(ToDoError::DuplicateDescription(description.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Returns a fresh dot, later than every dot seen so far.
+    fn tick(&mut self) -> Dot {
+        self.clock += 1;
+        Dot {
+            counter: self.clock,
+            replica: self.replica,
+        }
+    }
+
+    fn record(&self, dot: Dot) -> Result<&TaskRecord, ToDoError> {
+        self.state.tasks.get(&dot).ok_or(ToDoError::UnknownDot(dot))
+    }
+
+    fn record_mut(&mut self, dot: Dot) -> Result<&mut TaskRecord, ToDoError> {
+        self.state
+            .tasks
+            .get_mut(&dot)
+            .ok_or(ToDoError::UnknownDot(dot))
+    }
+}
+
+/// JSON objects need string keys, so the task map is written as a list of
+/// `[dot, task]` pairs.
+mod entries {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Dot, TaskRecord};
+
+    pub fn serialize<S: Serializer>(
+        tasks: &BTreeMap<Dot, TaskRecord>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(tasks.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Dot, TaskRecord>, D::Error> {
+        let pairs: Vec<(Dot, TaskRecord)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
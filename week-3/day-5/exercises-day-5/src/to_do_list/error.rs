@@ -0,0 +1,82 @@
+use std::fmt;
+
+use super::{Dot, TaskId};
+
+/// Errors returned by operations on a ToDoList.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToDoError {
+    /// The description is longer than the `max` characters allowed.
+    DescriptionTooLong { len: usize, max: usize },
+    /// The description is empty or only white space, which the policy forbids.
+    BlankDescription,
+    /// Another open task already has this description, which the policy forbids.
+    DuplicateDescription(String),
+    /// The priority is outside the `min`–`max` range.
+    PriorityOutOfRange { priority: u8, min: u8, max: u8 },
+    /// No task in the list has the given ID.
+    NotFound(TaskId),
+    /// No open task has the given description.
+    NoOpenTask(String),
+    /// No open task matches the search query.
+    NoMatch(String),
+    /// Several open tasks match the search query equally well.
+    AmbiguousMatch {
+        query: String,
+        candidates: Vec<TaskId>,
+    },
+    /// Another task already uses the given ID.
+    Duplicate(TaskId),
+    /// The tag is empty or contains characters that filters cannot express.
+    InvalidTag(String),
+    /// The task cannot be marked as done while some of its subtasks are open.
+    OpenSubtasks(TaskId),
+    /// Making `parent` the parent of `task` would put `task` under itself.
+    Cycle { task: TaskId, parent: TaskId },
+    /// No live task in a replicated list has the given dot.
+    UnknownDot(Dot),
+    /// The store could not write the change, which was undone; holds the
+    /// message of the `StoreError`.
+    Store(String),
+}
+
+impl fmt::Display for ToDoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToDoError::DescriptionTooLong { len, max } => write!(
+                f,
+                "description is too long ({} characters, at most {} allowed)",
+                len, max
+            ),
+            ToDoError::BlankDescription => write!(f, "description is blank"),
+            ToDoError::DuplicateDescription(description) => {
+                write!(f, "an open task named '{}' already exists", description)
+            }
+            ToDoError::PriorityOutOfRange { priority, min, max } => write!(
+                f,
+                "priority {} is not between {} and {}",
+                priority, min, max
+            ),
+            ToDoError::NotFound(id) => write!(f, "no task with id {}", id),
+            ToDoError::NoOpenTask(description) => {
+                write!(f, "no open task named '{}'", description)
+            }
+            ToDoError::NoMatch(query) => write!(f, "no open task matches '{}'", query),
+            ToDoError::AmbiguousMatch { query, candidates } => {
+                let ids: Vec<String> = candidates.iter().map(TaskId::to_string).collect();
+                write!(f, "'{}' matches several tasks: {}", query, ids.join(", "))
+            }
+            ToDoError::Duplicate(id) => write!(f, "id {} is already in use", id),
+            ToDoError::InvalidTag(tag) => write!(f, "invalid tag '{}'", tag),
+            ToDoError::OpenSubtasks(id) => write!(f, "task {} has open subtasks", id),
+            ToDoError::Cycle { task, parent } => write!(
+                f,
+                "task {} cannot be moved under {}, which is part of its subtree",
+                task, parent
+            ),
+            ToDoError::UnknownDot(dot) => write!(f, "no task with dot {}", dot),
+            ToDoError::Store(err) => write!(f, "cannot write to the store: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ToDoError {}
@@ -0,0 +1,492 @@
+//! A ToDoList kept as the log of everything that happened to it.
+//!
+//! Each change is appended to the log file as one JSON event per line,
+//! saying who made it and when, and the list is rebuilt on `open` by
+//! replaying the events in order. Replaying runs the same operation again
+//! with the clock stopped at the time of the event, and checks that it has
+//! the same outcome, so a log that does not match the code is reported
+//! rather than silently giving a different list.
+//!
+//! Every `snapshot_every` events the whole list is written to a snapshot
+//! file next to the log, and `open` only replays the events after it.
+//! `compact` drops the events of tasks that are no longer in the list; the
+//! others are kept for `history`.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::storage::replace_file;
+use super::{
+    Clock, FixedClock, MergeReport, StorageError, SystemClock, TaskId, ToDoError, ToDoList,
+    ValidationPolicy,
+};
+
+/// Number of events between two snapshots unless set with `with_snapshot_every`.
+const DEFAULT_SNAPSHOT_EVERY: usize = 100;
+/// Actor recorded in events unless set with `with_actor`.
+const DEFAULT_ACTOR: &str = "unknown";
+
+/// One change to the list, as recorded in the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// Position of the event in the log, starting at 1.
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// Who made the change.
+    pub actor: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What an event did, with what it was given and what came out of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// `add_task` added `task`.
+    Added {
+        task: TaskId,
+        description: String,
+        priority: u8,
+    },
+    /// `task` was marked as done; `next` is the next occurrence it spawned,
+    /// if it is a recurring task.
+    Done {
+        task: TaskId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next: Option<TaskId>,
+    },
+    /// `remove_done` removed these tasks.
+    RemovedDone { tasks: Vec<TaskId> },
+    /// The list in `from`, in the format written by `ToDoList::save`, was
+    /// merged in and its tasks were added as `added`.
+    Merged {
+        added: Vec<TaskId>,
+        from: serde_json::Value,
+    },
+}
+
+/// Errors of an event-sourced list.
+#[derive(Debug)]
+pub enum EventLogError {
+    /// The log or snapshot could not be read or written.
+    Io(io::Error),
+    /// The line of the log is not a valid event.
+    Parse {
+        line: usize,
+        error: serde_json::Error,
+    },
+    /// The event on the line of the log does not follow the one before it.
+    OutOfOrder { line: usize, seq: u64 },
+    /// Replaying the event did not give the outcome it recorded.
+    Diverged { seq: u64 },
+    /// The snapshot, or a list embedded in an event, is not valid.
+    Storage(StorageError),
+    /// The list refused the change, which was not logged.
+    Task(ToDoError),
+}
+
+impl fmt::Display for EventLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventLogError::Io(err) => write!(f, "I/O error: {}", err),
+            EventLogError::Parse { line, error } => {
+                write!(f, "invalid event on line {}: {}", line, error)
+            }
+            EventLogError::OutOfOrder { line, seq } => {
+                write!(f, "event {} on line {} is out of sequence", seq, line)
+            }
+            EventLogError::Diverged { seq } => {
+                write!(
+                    f,
+                    "replaying event {} did not give the recorded outcome",
+                    seq
+                )
+            }
+            EventLogError::Storage(err) => write!(f, "{}", err),
+            EventLogError::Task(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EventLogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EventLogError::Io(err) => Some(err),
+            EventLogError::Parse { error, .. } => Some(error),
+            EventLogError::Storage(err) => Some(err),
+            EventLogError::Task(err) => Some(err),
+            EventLogError::OutOfOrder { .. } | EventLogError::Diverged { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for EventLogError {
+    fn from(err: io::Error) -> Self {
+        EventLogError::Io(err)
+    }
+}
+
+impl From<StorageError> for EventLogError {
+    fn from(err: StorageError) -> Self {
+        EventLogError::Storage(err)
+    }
+}
+
+impl From<ToDoError> for EventLogError {
+    fn from(err: ToDoError) -> Self {
+        EventLogError::Task(err)
+    }
+}
+
+/// The snapshot file: the list as it was after event `seq`.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    seq: u64,
+    list: serde_json::Value,
+}
+
+/// A ToDoList whose changes are all written to an append-only log.
+#[derive(Debug)]
+pub struct EventSourcedToDoList {
+    list: ToDoList,
+    path: PathBuf,
+    clock: Arc<dyn Clock>,
+    actor: String,
+    /// Sequence number of the last event applied.
+    seq: u64,
+    since_snapshot: usize,
+    snapshot_every: usize,
+}
+
+impl Event {
+    /// Returns the tasks the event is about.
+    pub fn tasks(&self) -> Vec<TaskId> {
+        match &self.kind {
+            EventKind::Added { task, .. } => vec![*task],
+            EventKind::Done { task, next } => std::iter::once(*task).chain(*next).collect(),
+            EventKind::RemovedDone { tasks } => tasks.clone(),
+            EventKind::Merged { added, .. } => added.clone(),
+        }
+    }
+}
+
+impl EventSourcedToDoList {
+    /// Opens the list logged at `path`, starting from its snapshot if there
+    /// is one. A log that does not exist yet gives an empty list.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EventLogError> {
+        Self::open_with_policy(path, ValidationPolicy::default())
+    }
+
+    /// Opens the list as `open` does, checking changes against `policy`.
+    ///
+    /// The log has to be opened with the policy it was written with: a
+    /// change the policy refuses cannot be replayed.
+    pub fn open_with_policy<P: AsRef<Path>>(
+        path: P,
+        policy: ValidationPolicy,
+    ) -> Result<Self, EventLogError> {
+        let path = path.as_ref().to_path_buf();
+        let (mut list, snapshot_seq) = match fs::read_to_string(snapshot_path(&path)) {
+            Ok(text) => {
+                let snapshot: Snapshot = serde_json::from_str(&text).map_err(StorageError::from)?;
+                (ToDoList::from_value(snapshot.list, policy)?, snapshot.seq)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (ToDoList::new().with_policy(policy), 0)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut last = 0;
+        let mut seq = snapshot_seq;
+        for (line, event) in read_events(&path)? {
+            // Events up to the snapshot are already in it and only kept for
+            // `history`; after it, none may be missing.
+            let in_order = if event.seq <= snapshot_seq {
+                event.seq > last
+            } else {
+                event.seq == seq + 1
+            };
+            if !in_order {
+                return Err(EventLogError::OutOfOrder {
+                    line,
+                    seq: event.seq,
+                });
+            }
+            last = event.seq;
+            if event.seq > snapshot_seq {
+                replay(&mut list, &event)?;
+                seq = event.seq;
+            }
+        }
+        list.clear_history();
+
+        Ok(Self {
+            list,
+            path,
+            clock: Arc::new(SystemClock),
+            actor: DEFAULT_ACTOR.to_string(),
+            seq,
+            since_snapshot: (seq - snapshot_seq) as usize,
+            snapshot_every: DEFAULT_SNAPSHOT_EVERY,
+        })
+    }
+
+    /// Makes the list read the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.list.clock = Arc::clone(&clock);
+        self.clock = clock;
+        self
+    }
+
+    /// Records `actor` as the author of the changes made from now on.
+    pub fn with_actor(mut self, actor: &str) -> Self {
+        self.actor = actor.to_string();
+        self
+    }
+
+    /// Writes a snapshot after every `events` events; 0 turns periodic
+    /// snapshots off.
+    pub fn with_snapshot_every(mut self, events: usize) -> Self {
+        self.snapshot_every = events;
+        self
+    }
+
+    pub fn list(&self) -> &ToDoList {
+        &self.list
+    }
+
+    /// Adds a new task, as `ToDoList::add_task` does.
+    pub fn add_task(&mut self, description: &str, priority: u8) -> Result<TaskId, EventLogError> {
+        self.run(|list| add(list, description, priority))
+    }
+
+    /// Marks the first open task with this description as done, as
+    /// `ToDoList::mark_done` does, and returns its ID.
+    pub fn mark_done(&mut self, description: &str) -> Result<TaskId, EventLogError> {
+        let id = self
+            .list
+            .iter()
+            .find(|t| t.description() == description && !t.is_done())
+            .map(|t| t.id())
+            .ok_or_else(|| ToDoError::NoOpenTask(description.to_string()))?;
+        self.mark_done_by_id(id)?;
+        Ok(id)
+    }
+
+    /// Marks the task with the given ID as done, as `ToDoList::mark_done_by_id`
+    /// does. Nothing is logged if it already was.
+    pub fn mark_done_by_id(&mut self, id: TaskId) -> Result<Option<TaskId>, EventLogError> {
+        self.run(|list| done(list, id))
+    }
+
+    /// Removes all done tasks and returns how many were removed.
+    pub fn remove_done(&mut self) -> Result<usize, EventLogError> {
+        self.run(remove_done)
+    }
+
+    /// Merges another list in, as `ToDoList::merge` does. The other list is
+    /// stored in the event, so that the merge can be replayed.
+    pub fn merge(&mut self, other: &ToDoList) -> Result<MergeReport, EventLogError> {
+        self.run(|list| merge(list, other))
+    }
+
+    /// Returns every logged event about the task, oldest first.
+    pub fn history(&self, task: TaskId) -> Result<Vec<Event>, EventLogError> {
+        Ok(read_events(&self.path)?
+            .into_iter()
+            .map(|(_, event)| event)
+            .filter(|event| event.tasks().contains(&task))
+            .collect())
+    }
+
+    /// Writes the list as it is now to the snapshot file, so that `open`
+    /// does not have to replay the events so far.
+    pub fn snapshot(&mut self) -> Result<(), EventLogError> {
+        let snapshot = Snapshot {
+            seq: self.seq,
+            list: self.list.to_value()?,
+        };
+        let json = serde_json::to_vec(&snapshot).map_err(StorageError::from)?;
+        replace_file(&snapshot_path(&self.path), &json)?;
+        self.since_snapshot = 0;
+        Ok(())
+    }
+
+    /// Writes a snapshot and drops from the log the events about tasks that
+    /// are no longer in the list. Returns how many events were dropped.
+    ///
+    /// Their history is lost, but they cannot come back: every event still
+    /// to replay comes after the snapshot.
+    pub fn compact(&mut self) -> Result<usize, EventLogError> {
+        self.snapshot()?;
+        let mut kept = Vec::new();
+        let mut dropped = 0;
+        for (_, event) in read_events(&self.path)? {
+            if event.tasks().iter().any(|&id| self.list.get(id).is_some()) {
+                kept.extend(to_line(&event)?);
+            } else {
+                dropped += 1;
+            }
+        }
+        replace_file(&self.path, &kept)?;
+        Ok(dropped)
+    }
+
+    /// Makes a change at the current time and logs it. If the event cannot
+    /// be written, the change is undone, so that the list never holds a
+    /// change the log does not.
+    fn run<T>(
+        &mut self,
+        op: impl FnOnce(&mut ToDoList) -> Result<(T, Option<EventKind>), EventLogError>,
+    ) -> Result<T, EventLogError> {
+        let at = self.clock.now();
+        let (value, kind) = at_time(&mut self.list, at, op)?;
+        let Some(kind) = kind else {
+            return Ok(value);
+        };
+        let event = Event {
+            seq: self.seq + 1,
+            at,
+            actor: self.actor.clone(),
+            kind,
+        };
+        if let Err(err) = append(&self.path, &event) {
+            self.list.undo();
+            return Err(err);
+        }
+        self.seq = event.seq;
+        self.since_snapshot += 1;
+        if self.snapshot_every > 0 && self.since_snapshot >= self.snapshot_every {
+            // The log alone is enough to rebuild the list, so a failed
+            // snapshot is only tried again after the next event.
+            let _ = self.snapshot();
+        }
+        Ok(value)
+    }
+}
+
+// Each operation returns its result and the event to log, if it changed
+// anything. Replaying calls them again with what the event recorded.
+
+fn add(
+    list: &mut ToDoList,
+    description: &str,
+    priority: u8,
+) -> Result<(TaskId, Option<EventKind>), EventLogError> {
+    let task = list.add_task(description, priority)?;
+    let event = EventKind::Added {
+        task,
+        description: description.to_string(),
+        priority,
+    };
+    Ok((task, Some(event)))
+}
+
+fn done(
+    list: &mut ToDoList,
+    task: TaskId,
+) -> Result<(Option<TaskId>, Option<EventKind>), EventLogError> {
+    let was_done = list.get(task).ok_or(ToDoError::NotFound(task))?.done;
+    let next = list.mark_done_by_id(task)?;
+    Ok((next, (!was_done).then_some(EventKind::Done { task, next })))
+}
+
+fn remove_done(list: &mut ToDoList) -> Result<(usize, Option<EventKind>), EventLogError> {
+    let tasks: Vec<TaskId> = list.iter().filter(|t| t.done).map(|t| t.id).collect();
+    let removed = list.remove_done()?;
+    let event = (!tasks.is_empty()).then_some(EventKind::RemovedDone { tasks });
+    Ok((removed, event))
+}
+
+fn merge(
+    list: &mut ToDoList,
+    other: &ToDoList,
+) -> Result<(MergeReport, Option<EventKind>), EventLogError> {
+    let from = other.to_value()?;
+    let report = list.merge(other);
+    let event = (!report.added.is_empty()).then(|| EventKind::Merged {
+        added: report.added.clone(),
+        from,
+    });
+    Ok((report, event))
+}
+
+/// Applies a logged event to `list`.
+fn replay(list: &mut ToDoList, event: &Event) -> Result<(), EventLogError> {
+    let outcome = at_time(list, event.at, |list| match &event.kind {
+        EventKind::Added {
+            description,
+            priority,
+            ..
+        } => add(list, description, *priority).map(|(_, kind)| kind),
+        EventKind::Done { task, .. } => done(list, *task).map(|(_, kind)| kind),
+        EventKind::RemovedDone { .. } => remove_done(list).map(|(_, kind)| kind),
+        EventKind::Merged { from, .. } => {
+            // The merged list was never checked against this list's policy,
+            // only its tasks were, by `merge`.
+            let other = ToDoList::from_value(from.clone(), ValidationPolicy::unrestricted())?;
+            merge(list, &other).map(|(_, kind)| kind)
+        }
+    });
+    match outcome {
+        Ok(Some(kind)) if kind == event.kind => Ok(()),
+        Err(EventLogError::Storage(err)) => Err(EventLogError::Storage(err)),
+        _ => Err(EventLogError::Diverged { seq: event.seq }),
+    }
+}
+
+/// Runs `op` with the list's clock stopped at `at`, so that replaying an
+/// event gives the same timestamps as when it happened.
+fn at_time<T>(list: &mut ToDoList, at: DateTime<Utc>, op: impl FnOnce(&mut ToDoList) -> T) -> T {
+    let clock = std::mem::replace(&mut list.clock, Arc::new(FixedClock::new(at)));
+    let result = op(list);
+    list.clock = clock;
+    result
+}
+
+/// Reads the events of the log with their line numbers. A log that does
+/// not exist yet has none.
+fn read_events(path: &Path) -> Result<Vec<(usize, Event)>, EventLogError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    text.lines()
+        .zip(1..)
+        .filter(|(text, _)| !text.trim().is_empty())
+        .map(|(text, line)| {
+            serde_json::from_str(text)
+                .map(|event| (line, event))
+                .map_err(|error| EventLogError::Parse { line, error })
+        })
+        .collect()
+}
+
+/// Appends `event` to the log and flushes it to disk before returning.
+fn append(path: &Path, event: &Event) -> Result<(), EventLogError> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&to_line(event)?)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+fn to_line(event: &Event) -> Result<Vec<u8>, EventLogError> {
+    let mut line = serde_json::to_vec(event).map_err(StorageError::from)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// The snapshot of `todo.log` is `todo.log.snapshot`.
+fn snapshot_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".snapshot");
+    path.with_file_name(name)
+}
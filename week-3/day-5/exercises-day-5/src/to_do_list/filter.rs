@@ -0,0 +1,366 @@
+//! Filter expressions used to select tasks, such as
+//! `tag:backend and priority<=2 and not done`.
+//!
+//! Grammar, from lowest to highest precedence:
+//!
+//! ```text
+//! or      := and ("or" and)*
+//! and     := unary ("and" unary)*
+//! unary   := "not" unary | primary
+//! primary := "(" or ")" | "done" | "tag:" NAME | "text:" WORD
+//!          | "priority" OP NUMBER | "due" OP YYYY-MM-DD
+//! OP      := "<" | "<=" | "=" | "!=" | ">=" | ">"
+//! ```
+//!
+//! Keywords are case-insensitive; tag names and text are compared as written,
+//! except `text:` which ignores case.
+//!
+//! Parentheses and `not` may be nested, and `and`/`or` chained, at most
+//! `Filter::MAX_DEPTH` deep, so that no filter can overflow the stack when
+//! it is parsed, matched or dropped.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use super::Task;
+
+/// Parsed filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// The task is done.
+    Done,
+    /// The task has this tag.
+    Tag(String),
+    /// The description contains this text, ignoring case.
+    Text(String),
+    /// The priority compares to the number as given.
+    Priority(Comparison, u8),
+    /// The task has a due date that compares to the date as given.
+    Due(Comparison, NaiveDate),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+/// Comparison operator used by `priority` and `due`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    Ge,
+    Gt,
+}
+
+/// Error found while parsing a filter, with the byte offset where it occurs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl Filter {
+    /// How deeply a filter may nest.
+    pub const MAX_DEPTH: usize = 256;
+
+    /// Parses a filter expression.
+    pub fn parse(input: &str) -> Result<Self, FilterError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: input.len(),
+            depth: 0,
+        };
+        let (filter, _) = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(token.error("expected 'and', 'or' or end of filter")),
+        }
+    }
+
+    /// Returns whether `task` is selected by this filter.
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Filter::Done => task.done,
+            Filter::Tag(tag) => task.tags.contains(tag),
+            Filter::Text(text) => task
+                .description
+                .to_lowercase()
+                .contains(&text.to_lowercase()),
+            Filter::Priority(cmp, priority) => cmp.test(task.priority, *priority),
+            Filter::Due(cmp, date) => task.due.is_some_and(|due| cmp.test(due, *date)),
+            Filter::Not(inner) => !inner.matches(task),
+            Filter::And(left, right) => left.matches(task) && right.matches(task),
+            Filter::Or(left, right) => left.matches(task) || right.matches(task),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Filter::parse(s)
+    }
+}
+
+impl Comparison {
+    fn test<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Ge => left >= right,
+            Comparison::Gt => left > right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Op(Comparison),
+    Word(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    offset: usize,
+}
+
+impl Token {
+    fn error(&self, message: &str) -> FilterError {
+        FilterError {
+            offset: self.offset,
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns whether this token is the word `keyword`, ignoring case.
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(offset, c)) = chars.peek() {
+        let kind = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' => {
+                chars.next();
+                TokenKind::LParen
+            }
+            ')' => {
+                chars.next();
+                TokenKind::RParen
+            }
+            '<' | '>' | '=' | '!' => {
+                chars.next();
+                let eq = chars.next_if(|&(_, c)| c == '=').is_some();
+                let op = match (c, eq) {
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    ('>', true) => Comparison::Ge,
+                    ('=', _) => Comparison::Eq,
+                    ('!', true) => Comparison::Ne,
+                    _ => {
+                        return Err(FilterError {
+                            offset,
+                            message: "expected '!='".to_string(),
+                        })
+                    }
+                };
+                TokenKind::Op(op)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some((_, c)) =
+                    chars.next_if(|&(_, c)| !c.is_whitespace() && !"()<>=!".contains(c))
+                {
+                    word.push(c);
+                }
+                TokenKind::Word(word)
+            }
+        };
+        tokens.push(Token { kind, offset });
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over the token list.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the input, reported when the filter ends too early.
+    end: usize,
+    /// How many parentheses and `not` the parser is inside.
+    depth: usize,
+}
+
+/// A parsed part of the filter, with the height of its tree.
+type Parsed = (Filter, usize);
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, FilterError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| FilterError {
+                offset: self.end,
+                message: format!("expected {}, found end of filter", expected),
+            })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consumes the keyword if it comes next, and returns its token.
+    fn eat_keyword(&mut self, keyword: &str) -> Option<Token> {
+        let token = self.peek().filter(|t| t.is_keyword(keyword)).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Runs `parse` one level deeper, or fails at `token`, which opened the
+    /// level, if that goes past `Filter::MAX_DEPTH`.
+    fn nested(
+        &mut self,
+        token: &Token,
+        parse: impl FnOnce(&mut Self) -> Result<Parsed, FilterError>,
+    ) -> Result<Parsed, FilterError> {
+        if self.depth == Filter::MAX_DEPTH {
+            return Err(token.error("filter is nested too deeply"));
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    /// Returns the height of a node over children at most `height` high, or
+    /// fails at `token`, which built the node, if that goes past
+    /// `Filter::MAX_DEPTH`.
+    fn taller(token: &Token, height: usize) -> Result<usize, FilterError> {
+        if height == Filter::MAX_DEPTH {
+            return Err(token.error("filter is nested too deeply"));
+        }
+        Ok(height + 1)
+    }
+
+    fn or(&mut self) -> Result<Parsed, FilterError> {
+        let (mut left, mut height) = self.and()?;
+        while let Some(token) = self.eat_keyword("or") {
+            let (right, right_height) = self.and()?;
+            height = Self::taller(&token, height.max(right_height))?;
+            left = Filter::Or(Box::new(left), Box::new(right));
+        }
+        Ok((left, height))
+    }
+
+    fn and(&mut self) -> Result<Parsed, FilterError> {
+        let (mut left, mut height) = self.unary()?;
+        while let Some(token) = self.eat_keyword("and") {
+            let (right, right_height) = self.unary()?;
+            height = Self::taller(&token, height.max(right_height))?;
+            left = Filter::And(Box::new(left), Box::new(right));
+        }
+        Ok((left, height))
+    }
+
+    fn unary(&mut self) -> Result<Parsed, FilterError> {
+        if let Some(token) = self.eat_keyword("not") {
+            let (inner, height) = self.nested(&token, Self::unary)?;
+            return Ok((Filter::Not(Box::new(inner)), Self::taller(&token, height)?));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Parsed, FilterError> {
+        let token = self.next("a condition")?;
+        let word = match &token.kind {
+            TokenKind::LParen => {
+                let inner = self.nested(&token, Self::or)?;
+                let close = self.next("')'")?;
+                if close.kind != TokenKind::RParen {
+                    return Err(close.error("expected ')'"));
+                }
+                return Ok(inner);
+            }
+            TokenKind::Word(word) => word.clone(),
+            _ => return Err(token.error("expected a condition")),
+        };
+        Ok((self.condition(&token, &word)?, 0))
+    }
+
+    /// Parses the condition that starts with `word`, read from `token`.
+    fn condition(&mut self, token: &Token, word: &str) -> Result<Filter, FilterError> {
+        let lower = word.to_ascii_lowercase();
+        if lower == "done" {
+            Ok(Filter::Done)
+        } else if lower.starts_with("tag:") {
+            let tag = &word["tag:".len()..];
+            if tag.is_empty() {
+                return Err(token.error("expected a tag name after 'tag:'"));
+            }
+            Ok(Filter::Tag(tag.to_string()))
+        } else if lower.starts_with("text:") {
+            Ok(Filter::Text(word["text:".len()..].to_string()))
+        } else if lower == "priority" {
+            let cmp = self.comparison()?;
+            let value = self.next("a priority")?;
+            match &value.kind {
+                TokenKind::Word(w) => w
+                    .parse()
+                    .map(|p| Filter::Priority(cmp, p))
+                    .map_err(|_| value.error("expected a priority between 0 and 255")),
+                _ => Err(value.error("expected a priority")),
+            }
+        } else if lower == "due" {
+            let cmp = self.comparison()?;
+            let value = self.next("a date")?;
+            match &value.kind {
+                TokenKind::Word(w) => NaiveDate::parse_from_str(w, "%Y-%m-%d")
+                    .map(|d| Filter::Due(cmp, d))
+                    .map_err(|_| value.error("expected a date as YYYY-MM-DD")),
+                _ => Err(value.error("expected a date")),
+            }
+        } else {
+            Err(token.error(&format!("unknown condition '{}'", word)))
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Comparison, FilterError> {
+        let token = self.next("a comparison")?;
+        match token.kind {
+            TokenKind::Op(cmp) => Ok(cmp),
+            _ => Err(token.error("expected one of <, <=, =, !=, >=, >")),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Undo/redo support.
+//!
+//! Every public mutation of a ToDoList runs inside a transaction that records
+//! the low-level edits it makes to the tasks. The recorded edits form a
+//! `Command`, whose inverse undoes exactly those edits in reverse order.
+//! When the transaction ends, the changes are written to the list's store.
+
+use std::collections::VecDeque;
+
+use super::{Slot, Task, TaskStore, ToDoError, ToDoList};
+
+/// Number of commands kept for undo unless set with `with_history_limit`.
+const DEFAULT_LIMIT: usize = 100;
+
+/// A single change to the tasks.
+#[derive(Debug, Clone)]
+pub(super) enum Edit {
+    Insert {
+        slot: Slot,
+        task: Task,
+    },
+    Remove {
+        slot: Slot,
+        task: Task,
+    },
+    Replace {
+        slot: Slot,
+        before: Task,
+        after: Task,
+    },
+}
+
+impl Edit {
+    fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { slot, task } => Edit::Remove {
+                slot: *slot,
+                task: task.clone(),
+            },
+            Edit::Remove { slot, task } => Edit::Insert {
+                slot: *slot,
+                task: task.clone(),
+            },
+            Edit::Replace {
+                slot,
+                before,
+                after,
+            } => Edit::Replace {
+                slot: *slot,
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+}
+
+/// A recorded mutation, such as `add task` or `merge`.
+#[derive(Debug, Clone)]
+struct Command {
+    name: &'static str,
+    edits: Vec<Edit>,
+}
+
+impl Command {
+    fn inverse(&self) -> Command {
+        Command {
+            name: self.name,
+            edits: self.edits.iter().rev().map(Edit::inverse).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct History {
+    undo: VecDeque<Command>,
+    redo: Vec<Command>,
+    limit: usize,
+    /// Edits of the transaction in progress, if any.
+    journal: Option<Vec<Edit>>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit: DEFAULT_LIMIT,
+            journal: None,
+        }
+    }
+}
+
+impl History {
+    /// Records an edit if a transaction is in progress.
+    pub(super) fn log(&mut self, edit: impl FnOnce() -> Edit) {
+        if let Some(journal) = &mut self.journal {
+            journal.push(edit());
+        }
+    }
+
+    fn push_undo(&mut self, command: Command) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(command);
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Keeps at most `limit` operations for `undo`; older ones are forgotten.
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.history.limit = limit;
+        while self.history.undo.len() > limit {
+            self.history.undo.pop_front();
+        }
+        self
+    }
+
+    /// Reverts the most recent operation that has not been undone yet and
+    /// returns its name, or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<&'static str> {
+        let command = self.history.undo.pop_back()?;
+        self.apply(&command.inverse());
+        self.write_back();
+        let name = command.name;
+        self.history.redo.push(command);
+        Some(name)
+    }
+
+    /// Applies again the most recently undone operation and returns its name,
+    /// or `None` if there is nothing to redo.
+    ///
+    /// Any new operation clears the operations that can be redone.
+    pub fn redo(&mut self) -> Option<&'static str> {
+        let command = self.history.redo.pop()?;
+        self.apply(&command);
+        self.write_back();
+        let name = command.name;
+        self.history.push_undo(command);
+        Some(name)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Forgets all operations that could be undone or redone.
+    pub fn clear_history(&mut self) {
+        self.history.undo.clear();
+        self.history.redo.clear();
+    }
+
+    /// Runs `op` as one undoable operation called `name`.
+    ///
+    /// The changes are then written to the store. If `op` or the store
+    /// fails, the edits it made are reverted, so an operation either applies
+    /// completely or not at all. Transactions started while another one is
+    /// running become part of the outer one.
+    pub(super) fn transaction<T>(
+        &mut self,
+        name: &'static str,
+        op: impl FnOnce(&mut Self) -> Result<T, ToDoError>,
+    ) -> Result<T, ToDoError> {
+        if self.history.journal.is_some() {
+            return op(self);
+        }
+        let next_id = self.next_id;
+        self.history.journal = Some(Vec::new());
+        let mut result = op(self);
+        let edits = self.history.journal.take().unwrap_or_default();
+        let command = Command { name, edits };
+        if result.is_ok() {
+            if let Err(err) = self.sync() {
+                // The IDs of the reverted tasks were never stored.
+                self.next_id = next_id;
+                result = Err(ToDoError::Store(err.to_string()));
+            }
+        }
+        match result {
+            Ok(_) if !command.edits.is_empty() => {
+                self.history.redo.clear();
+                self.history.push_undo(command);
+            }
+            Ok(_) => {}
+            Err(_) => {
+                self.apply(&command.inverse());
+                self.forget_reverted();
+            }
+        }
+        result
+    }
+
+    /// Runs an operation that cannot fail as one undoable operation, and
+    /// writes its changes back to the store.
+    pub(super) fn record<T>(&mut self, name: &'static str, op: impl FnOnce(&mut Self) -> T) -> T {
+        if self.history.journal.is_some() {
+            return op(self);
+        }
+        self.history.journal = Some(Vec::new());
+        let value = op(self);
+        let edits = self.history.journal.take().unwrap_or_default();
+        if !edits.is_empty() {
+            self.history.redo.clear();
+            self.history.push_undo(Command { name, edits });
+        }
+        self.write_back();
+        value
+    }
+
+    /// Writes the changes of an operation that cannot report an error, such
+    /// as an import or an undo, to the store. If that fails they are kept, to
+    /// be written by the next operation or by `sync`, and the error is kept
+    /// for `store_error`.
+    fn write_back(&mut self) {
+        if let Err(err) = self.sync() {
+            self.store_error = Some(err);
+        }
+    }
+
+    /// Replays the edits of `command` without recording them.
+    fn apply(&mut self, command: &Command) {
+        for edit in &command.edits {
+            match edit {
+                Edit::Insert { slot, task } => self.insert_at(*slot, task.clone()),
+                Edit::Remove { slot, .. } => {
+                    self.remove_at(*slot);
+                }
+                Edit::Replace { slot, after, .. } => {
+                    self.replace_at(*slot, after.clone());
+                }
+            }
+        }
+    }
+}
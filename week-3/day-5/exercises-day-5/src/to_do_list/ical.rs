@@ -0,0 +1,476 @@
+//! Import and export as iCalendar VTODO components (RFC 5545), the same
+//! ecosystem as the `rust.ics` schedule at the root of the repository.
+//!
+//! | VTODO property              | ToDoList                           |
+//! |-----------------------------|------------------------------------|
+//! | `SUMMARY`                   | description                        |
+//! | `PRIORITY` 1, 3, 5, 7, 9    | priority 1..5 (2, 4, 6, 8 round up, 0 is 5) |
+//! | `STATUS:COMPLETED`          | done                               |
+//! | `STATUS:NEEDS-ACTION`, `IN-PROCESS` | not done                   |
+//! | `CREATED`, `COMPLETED`      | `created`, `completed`             |
+//! | `DUE`                       | due date                           |
+//! | `CATEGORIES`                | tags                               |
+//! | `RRULE`                     | recurrence, in the subset described in `Recurrence` |
+//! | `RELATED-TO;RELTYPE=PARENT` | parent, by its `UID`               |
+//!
+//! `UID` is only used on import to link `RELATED-TO`, since tasks get fresh
+//! IDs, and `DTSTAMP` is written but ignored. A `RELATED-TO` with another
+//! `RELTYPE`, or naming a `UID` not in the document, is ignored. `X-` properties are ignored as RFC 5545 asks; any other
+//! property is reported as unsupported and its VTODO is skipped.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use super::{validate_tag, ImportReport, Recurrence, Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+/// Longest line allowed by RFC 5545, in octets, without the line break.
+const MAX_LINE_LEN: usize = 75;
+
+const PRODID: &str = "-//Rust Roadmap//ToDoList//EN";
+
+/// Problems found while reading an iCalendar document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IcalError {
+    /// The document is not well formed; nothing was imported.
+    Syntax { line: usize, message: String },
+    /// A VTODO uses a property the list cannot represent.
+    UnsupportedProperty(String),
+    /// A property has a value the list cannot represent.
+    InvalidValue { property: String, value: String },
+    /// The task breaks one of the rules of the list.
+    Task(ToDoError),
+}
+
+impl fmt::Display for IcalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IcalError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            IcalError::UnsupportedProperty(name) => {
+                write!(f, "property {} is not supported", name)
+            }
+            IcalError::InvalidValue { property, value } => {
+                write!(f, "invalid value '{}' for {}", value, property)
+            }
+            IcalError::Task(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for IcalError {}
+
+impl From<ToDoError> for IcalError {
+    fn from(err: ToDoError) -> Self {
+        IcalError::Task(err)
+    }
+}
+
+/// A content line, already unfolded: `NAME;PARAM=VALUE:value`.
+struct Property {
+    /// 1-based number of the physical line the property starts on.
+    line: usize,
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn invalid(&self) -> IcalError {
+        IcalError::InvalidValue {
+            property: self.name.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+/// The fields of a VTODO the list understands.
+#[derive(Default)]
+struct Todo {
+    uid: Option<String>,
+    parent: Option<String>,
+    summary: String,
+    priority: Option<u8>,
+    done: bool,
+    created: Option<DateTime<Utc>>,
+    completed: Option<DateTime<Utc>>,
+    due: Option<NaiveDate>,
+    tags: Vec<String>,
+    recurrence: Option<Recurrence>,
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Writes the list as a VCALENDAR with one VTODO per task, in list order.
+    pub fn to_ical(&self) -> String {
+        let mut out = String::new();
+        let stamp = format_date_time(self.clock.now());
+        for line in ["BEGIN:VCALENDAR", "VERSION:2.0", "CALSCALE:GREGORIAN"] {
+            push_line(&mut out, line);
+        }
+        push_line(&mut out, &format!("PRODID:{}", PRODID));
+        for task in self.tasks.values() {
+            write_todo(&mut out, task, &stamp);
+        }
+        push_line(&mut out, "END:VCALENDAR");
+        out
+    }
+
+    /// Adds the tasks of every VTODO in an iCalendar document to the list, as
+    /// one undoable operation.
+    ///
+    /// Other components, such as the VEVENTs of `rust.ics`, are ignored. A
+    /// VTODO that cannot be imported is skipped and reported with the line
+    /// its `BEGIN:VTODO` is on. If the document itself is malformed, nothing
+    /// is imported.
+    pub fn import_ical(&mut self, text: &str) -> Result<ImportReport<IcalError>, IcalError> {
+        let todos = parse_document(text)?;
+        Ok(self.record("import", |list| {
+            let mut report = ImportReport::default();
+            let mut uids = HashMap::new();
+            let mut links = Vec::new();
+            for (line, properties) in todos {
+                match list.import_todo(&properties, &mut uids, &mut links) {
+                    Ok(id) => report.added.push(id),
+                    Err(err) => report.skipped.push((line, err)),
+                }
+            }
+            list.link_imported(links, &uids)
+                .expect("imported tasks are in the list");
+            report
+        }))
+    }
+
+    /// Imports one VTODO, noting its `UID` in `uids` and its parent's in
+    /// `links`.
+    fn import_todo(
+        &mut self,
+        properties: &[Property],
+        uids: &mut HashMap<String, TaskId>,
+        links: &mut Vec<(TaskId, String)>,
+    ) -> Result<TaskId, IcalError> {
+        let todo = read_todo(properties)?;
+        for tag in &todo.tags {
+            validate_tag(tag)?;
+        }
+        let priority = todo.priority.unwrap_or(5);
+        let id = if todo.done {
+            self.add_done(&todo.summary, priority, todo.due)?
+        } else {
+            self.add(&todo.summary, priority, todo.due)?
+        };
+        self.modify(id, |task| {
+            task.tags = todo.tags.into_iter().collect();
+            task.recurrence = todo.recurrence;
+            if let Some(created) = todo.created {
+                task.created = created;
+            }
+            if todo.done {
+                task.completed = todo.completed.or(Some(task.created));
+            }
+        })?;
+        uids.extend(todo.uid.map(|uid| (uid, id)));
+        links.extend(todo.parent.map(|parent| (id, parent)));
+        Ok(id)
+    }
+}
+
+fn write_todo(out: &mut String, task: &Task, stamp: &str) {
+    push_line(out, "BEGIN:VTODO");
+    push_line(out, &format!("UID:{}", uid(task.id)));
+    push_line(out, &format!("DTSTAMP:{}", stamp));
+    push_line(out, &format!("CREATED:{}", format_date_time(task.created)));
+    push_line(out, &format!("SUMMARY:{}", escape(&task.description)));
+    // RFC 5545 uses 1 (highest) to 9 (lowest); spread our 1..5 over it,
+    // and send priorities outside 1..5 that a policy allows to the nearest end.
+    push_line(
+        out,
+        &format!("PRIORITY:{}", task.priority.clamp(1, 5) * 2 - 1),
+    );
+    if task.done {
+        push_line(out, "STATUS:COMPLETED");
+        if let Some(completed) = task.completed {
+            push_line(out, &format!("COMPLETED:{}", format_date_time(completed)));
+        }
+    } else {
+        push_line(out, "STATUS:NEEDS-ACTION");
+    }
+    if let Some(due) = task.due {
+        push_line(out, &format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|t| escape(t)).collect();
+        push_line(out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(rule) = &task.recurrence {
+        push_line(out, &format!("RRULE:{}", rule));
+    }
+    if let Some(parent) = task.parent {
+        push_line(out, &format!("RELATED-TO;RELTYPE=PARENT:{}", uid(parent)));
+    }
+    push_line(out, "END:VTODO");
+}
+
+fn uid(id: TaskId) -> String {
+    format!("rust-roadmap-task-{}", id.0)
+}
+
+/// Appends a content line, folded so that no line exceeds 75 octets.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            // The leading space of a continuation line counts towards its length.
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Splits a TEXT value on unescaped commas and unescapes each part.
+fn unescape_list(value: &str) -> Result<Vec<String>, ()> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().ok_or(())?;
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => part.push('\n'),
+                Some(c @ ('\\' | ';' | ',')) => part.push(c),
+                _ => return Err(()),
+            },
+            ',' => parts.push(String::new()),
+            _ => part.push(c),
+        }
+    }
+    Ok(parts)
+}
+
+fn unescape(value: &str) -> Result<String, ()> {
+    // Commas in a single TEXT value must be escaped, so a plain one is an error.
+    match unescape_list(value)?.as_slice() {
+        [single] => Ok(single.clone()),
+        _ => Err(()),
+    }
+}
+
+/// Unfolds the document and returns the properties of every VTODO, each with
+/// the line of its `BEGIN:VTODO`.
+fn parse_document(text: &str) -> Result<Vec<(usize, Vec<Property>)>, IcalError> {
+    let mut todos = Vec::new();
+    // Components currently open, innermost last.
+    let mut open: Vec<String> = Vec::new();
+    let mut current: Option<(usize, Vec<Property>)> = None;
+
+    for property in unfold(text)? {
+        let syntax = |message: String| IcalError::Syntax {
+            line: property.line,
+            message,
+        };
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.to_ascii_uppercase();
+                if open.is_empty() && component != "VCALENDAR" {
+                    return Err(syntax("expected BEGIN:VCALENDAR".to_string()));
+                }
+                if component == "VTODO" && open.len() == 1 {
+                    current = Some((property.line, Vec::new()));
+                }
+                open.push(component);
+            }
+            "END" => {
+                let component = property.value.to_ascii_uppercase();
+                match open.pop() {
+                    Some(expected) if expected == component => {}
+                    Some(expected) => {
+                        return Err(syntax(format!("expected END:{}", expected)));
+                    }
+                    None => return Err(syntax(format!("END:{} without BEGIN", component))),
+                }
+                if component == "VTODO" && open.len() == 1 {
+                    todos.extend(current.take());
+                }
+            }
+            _ if open.is_empty() => {
+                return Err(syntax("expected BEGIN:VCALENDAR".to_string()));
+            }
+            _ => {
+                // Only direct properties of a VTODO matter; nested components
+                // such as VALARM are skipped.
+                if let Some((_, properties)) = &mut current {
+                    if open.len() == 2 {
+                        properties.push(property);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(component) = open.pop() {
+        return Err(IcalError::Syntax {
+            line: text.lines().count(),
+            message: format!("missing END:{}", component),
+        });
+    }
+    Ok(todos)
+}
+
+/// Joins folded lines and splits each content line into a `Property`.
+fn unfold(text: &str) -> Result<Vec<Property>, IcalError> {
+    let mut logical: Vec<(usize, String)> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        if let Some(rest) = raw.strip_prefix([' ', '\t']) {
+            match logical.last_mut() {
+                Some((_, line)) => line.push_str(rest),
+                None => {
+                    return Err(IcalError::Syntax {
+                        line: index + 1,
+                        message: "continuation line without a property".to_string(),
+                    })
+                }
+            }
+        } else if !raw.is_empty() {
+            logical.push((index + 1, raw.to_string()));
+        }
+    }
+    logical
+        .into_iter()
+        .map(|(line, text)| parse_property(line, &text))
+        .collect()
+}
+
+fn parse_property(line: usize, text: &str) -> Result<Property, IcalError> {
+    let syntax = |message: &str| IcalError::Syntax {
+        line,
+        message: message.to_string(),
+    };
+    // The value starts at the first colon that is not inside a quoted parameter.
+    let mut quoted = false;
+    let colon = text
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ':' && !quoted
+        })
+        .map(|(i, _)| i)
+        .ok_or_else(|| syntax("expected ':' in property"))?;
+    let (head, value) = (&text[..colon], &text[colon + 1..]);
+
+    let mut parts = head.split(';');
+    let name = parts.next().unwrap_or_default().to_ascii_uppercase();
+    if name.is_empty() {
+        return Err(syntax("missing property name"));
+    }
+    let params = parts
+        .map(|param| {
+            param
+                .split_once('=')
+                .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+                .ok_or_else(|| syntax("expected '=' in parameter"))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Property {
+        line,
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn read_todo(properties: &[Property]) -> Result<Todo, IcalError> {
+    let mut todo = Todo::default();
+    for property in properties {
+        match property.name.as_str() {
+            "DTSTAMP" => {}
+            "UID" => todo.uid = Some(property.value.clone()),
+            "RELATED-TO" => {
+                // RELTYPE defaults to PARENT; siblings and children are
+                // implied by the parent links of the other VTODOs.
+                let is_parent = property
+                    .params
+                    .iter()
+                    .filter(|(key, _)| key == "RELTYPE")
+                    .all(|(_, value)| value.eq_ignore_ascii_case("PARENT"));
+                if is_parent {
+                    todo.parent = Some(property.value.clone());
+                }
+            }
+            name if name.starts_with("X-") => {}
+            "SUMMARY" => {
+                todo.summary = unescape(&property.value).map_err(|_| property.invalid())?;
+            }
+            "PRIORITY" => {
+                let value: u8 = property.value.parse().map_err(|_| property.invalid())?;
+                todo.priority = match value {
+                    0 => None,
+                    1..=9 => Some(value.div_ceil(2)),
+                    _ => return Err(property.invalid()),
+                };
+            }
+            "STATUS" => {
+                todo.done = match property.value.to_ascii_uppercase().as_str() {
+                    "COMPLETED" => true,
+                    "NEEDS-ACTION" | "IN-PROCESS" => false,
+                    _ => return Err(property.invalid()),
+                };
+            }
+            "CREATED" => todo.created = Some(parse_date_time(property)?),
+            "COMPLETED" => todo.completed = Some(parse_date_time(property)?),
+            "DUE" => todo.due = Some(parse_due(property)?),
+            "CATEGORIES" => {
+                let tags = unescape_list(&property.value).map_err(|_| property.invalid())?;
+                todo.tags.extend(tags);
+            }
+            "RRULE" => {
+                let rule = Recurrence::parse(&property.value).map_err(|_| property.invalid())?;
+                todo.recurrence = Some(rule);
+            }
+            name => return Err(IcalError::UnsupportedProperty(name.to_string())),
+        }
+    }
+    Ok(todo)
+}
+
+fn format_date_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Reads a UTC date-time such as `20250310T090000Z`.
+///
+/// Floating and zoned times would need a time zone database to convert, so
+/// they are refused rather than guessed.
+fn parse_date_time(property: &Property) -> Result<DateTime<Utc>, IcalError> {
+    NaiveDateTime::parse_from_str(&property.value, "%Y%m%dT%H%M%SZ")
+        .map(|time| time.and_utc())
+        .map_err(|_| property.invalid())
+}
+
+/// Reads a due date given either as a DATE or as a DATE-TIME.
+fn parse_due(property: &Property) -> Result<NaiveDate, IcalError> {
+    let is_date = property
+        .params
+        .iter()
+        .any(|(k, v)| k == "VALUE" && v.eq_ignore_ascii_case("DATE"));
+    if is_date {
+        NaiveDate::parse_from_str(&property.value, "%Y%m%d").map_err(|_| property.invalid())
+    } else {
+        parse_date_time(property).map(|time| time.date_naive())
+    }
+}
@@ -0,0 +1,482 @@
+//! Import and export of GitHub-style task lists in Markdown
+//! (<https://github.github.com/gfm/#task-list-items-extension->).
+//!
+//! | Markdown                          | ToDoList                        |
+//! |-----------------------------------|---------------------------------|
+//! | `- [ ] text`, `- [x] text`        | open, done task                 |
+//! | `*`, `+`, `1.` or `1)` bullets    | the same as `-`                 |
+//! | `(P2)`                            | priority 2                      |
+//! | no priority                       | priority 5                      |
+//! | `#word`                           | tag `word`                      |
+//! | `## Heading` above the item       | tag `heading`                   |
+//! | item nested in another item       | subtask                         |
+//!
+//! In descriptions, `\\`, `\#` and `\(` stand for `\`, `#` and `(`, and
+//! `\n` and `\r` for line breaks, so that any description can be written on
+//! the line of its item and read back as it was. The export escapes every
+//! `\`, `#`, `(` and line break of a description; a backslash before any
+//! other character is kept as written.
+//!
+//! A heading gives its section a tag made of its words in lower case,
+//! joined by `-`, so `## Week 3: Errands` tags the tasks under it
+//! `week-3-errands`, until the next heading of any level. Lines that are
+//! not task list items, and everything in fenced code blocks, are ignored.
+//!
+//! The export writes the tasks without tags first, then a `##` section per
+//! tag, with the tasks whose first tag it is; their other tags follow the
+//! description. Subtasks are written under their parent, so a subtask
+//! tagged differently from its parent gets the parent's section tag when
+//! imported back.
+//!
+//! `sync_markdown` goes the other way for notes that already exist: it
+//! ticks and unticks their checkboxes to match the list, and leaves every
+//! other character as it was.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use super::storage::replace_file;
+use super::{validate_tag, ImportReport, Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+/// Priority given to items without one: the least urgent.
+const DEFAULT_PRIORITY: u8 = 5;
+
+/// Why a task list item could not be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkdownError {
+    /// A `(P...)` annotation is not a priority from 0 to 255.
+    InvalidPriority(String),
+    /// The task breaks one of the rules of the list.
+    Task(ToDoError),
+}
+
+impl fmt::Display for MarkdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkdownError::InvalidPriority(word) => write!(f, "invalid priority '{}'", word),
+            MarkdownError::Task(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MarkdownError {}
+
+impl From<ToDoError> for MarkdownError {
+    fn from(err: ToDoError) -> Self {
+        MarkdownError::Task(err)
+    }
+}
+
+/// Outcome of `ToDoList::sync_markdown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownSync {
+    /// The document with its checkboxes matching the list.
+    pub text: String,
+    /// 1-based numbers of the lines whose checkbox changed.
+    pub changed: Vec<usize>,
+    /// 1-based numbers of the task list items that match no task.
+    pub unmatched: Vec<usize>,
+}
+
+/// A section of an export: its tag, and its tasks in tree order with their
+/// depth.
+type Section<'a> = (Option<&'a str>, Vec<(usize, &'a Task)>);
+
+/// A line of a document, as far as task lists are concerned.
+enum Line<'a> {
+    Heading(String),
+    Item(Item<'a>),
+    Other,
+}
+
+/// A task list item.
+struct Item<'a> {
+    /// Width of the white space before the bullet.
+    indent: usize,
+    done: bool,
+    /// Byte offset of the character between the brackets.
+    checkbox: usize,
+    text: &'a str,
+}
+
+/// The fields of a task written in the text of an item.
+struct Fields {
+    description: String,
+    priority: u8,
+    tags: Vec<String>,
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Adds every task list item of a Markdown document to the list, as one
+    /// undoable operation. Items that cannot be imported are skipped and
+    /// reported with their line number; their nested items go under the
+    /// nearest item above them that was imported.
+    ///
+    /// Done items with open nested items are reopened, since a done task
+    /// cannot have open subtasks.
+    pub fn import_markdown(&mut self, text: &str) -> ImportReport<MarkdownError> {
+        self.record("import", |list| {
+            let mut report = ImportReport::default();
+            let mut section = None;
+            // Items that contain the current line, with their task if it was
+            // imported.
+            let mut open: Vec<(usize, Option<TaskId>)> = Vec::new();
+            for (number, line) in lines(text) {
+                match line {
+                    Line::Heading(tag) => {
+                        section = Some(tag).filter(|tag| !tag.is_empty());
+                        open.clear();
+                    }
+                    Line::Item(item) => {
+                        while open
+                            .last()
+                            .is_some_and(|(indent, _)| *indent >= item.indent)
+                        {
+                            open.pop();
+                        }
+                        let parent = open.iter().rev().find_map(|(_, id)| *id);
+                        let imported = list.import_item(&item, section.as_deref(), parent);
+                        match &imported {
+                            Ok(id) => report.added.push(*id),
+                            Err(err) => report.skipped.push((number, err.clone())),
+                        }
+                        open.push((item.indent, imported.ok()));
+                    }
+                    Line::Other => {}
+                }
+            }
+            for &id in &report.added {
+                if list.get(id).is_some_and(|task| !task.done) {
+                    list.reopen_ancestors(id)
+                        .expect("imported tasks are in the list");
+                }
+            }
+            report
+        })
+    }
+
+    /// Writes the list as a Markdown task list, in list order within each
+    /// section.
+    pub fn to_markdown(&self) -> String {
+        // The untagged tasks, then a section per tag, each with its tasks
+        // and their subtasks in tree order.
+        let mut sections: Vec<Section> = vec![(None, Vec::new())];
+        let mut current = 0;
+        for (depth, task) in self.tree() {
+            if depth == 0 {
+                let tag = task.tags.first().map(String::as_str);
+                current = match sections.iter().position(|(section, _)| *section == tag) {
+                    Some(found) => found,
+                    None => {
+                        sections.push((tag, Vec::new()));
+                        sections.len() - 1
+                    }
+                };
+            }
+            sections[current].1.push((depth, task));
+        }
+        sections[1..].sort_by_key(|(tag, _)| *tag);
+
+        let mut out = String::new();
+        for (tag, tasks) in sections {
+            if tasks.is_empty() {
+                continue;
+            }
+            if let Some(tag) = tag {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("## {}\n\n", tag));
+            }
+            for (depth, task) in tasks {
+                out.push_str(&format_item(depth, task, tag));
+            }
+        }
+        out
+    }
+
+    /// Ticks and unticks the task list items of `text` to match the tasks
+    /// they stand for, leaving everything else in it untouched.
+    ///
+    /// An item stands for the first task, in list order, with its
+    /// description and the tag of its section, if it is in one, that no
+    /// earlier item stands for.
+    pub fn sync_markdown(&self, text: &str) -> MarkdownSync {
+        let mut out = String::with_capacity(text.len());
+        let mut sync = MarkdownSync {
+            text: String::new(),
+            changed: Vec::new(),
+            unmatched: Vec::new(),
+        };
+        let mut used = HashSet::new();
+        let mut section: Option<String> = None;
+        for (number, (raw, line)) in raw_lines(text).enumerate() {
+            let number = number + 1;
+            match line {
+                Line::Heading(tag) => section = Some(tag).filter(|tag| !tag.is_empty()),
+                Line::Item(item) => {
+                    let description = parse_fields(item.text)
+                        .map(|fields| fields.description)
+                        .unwrap_or_default();
+                    let found = self.tasks.values().find(|task| {
+                        task.description == description
+                            && section.as_ref().is_none_or(|tag| task.tags.contains(tag))
+                            && !used.contains(&task.id)
+                    });
+                    match found {
+                        Some(task) => {
+                            used.insert(task.id);
+                            if task.done != item.done {
+                                let mark = if task.done { "x" } else { " " };
+                                out.push_str(&raw[..item.checkbox]);
+                                out.push_str(mark);
+                                out.push_str(&raw[item.checkbox + 1..]);
+                                sync.changed.push(number);
+                                continue;
+                            }
+                        }
+                        None => sync.unmatched.push(number),
+                    }
+                }
+                Line::Other => {}
+            }
+            out.push_str(raw);
+        }
+        sync.text = out;
+        sync
+    }
+
+    /// Syncs the checkboxes of the Markdown file at `path` with
+    /// `sync_markdown`. The file is only written if a checkbox changed, and
+    /// then replaced in one step, so it is never left half written.
+    pub fn sync_markdown_file<P: AsRef<Path>>(&self, path: P) -> io::Result<MarkdownSync> {
+        let path = path.as_ref();
+        let sync = self.sync_markdown(&std::fs::read_to_string(path)?);
+        if !sync.changed.is_empty() {
+            replace_file(path, sync.text.as_bytes())?;
+        }
+        Ok(sync)
+    }
+
+    fn import_item(
+        &mut self,
+        item: &Item,
+        section: Option<&str>,
+        parent: Option<TaskId>,
+    ) -> Result<TaskId, MarkdownError> {
+        let mut fields = parse_fields(item.text)?;
+        fields.tags.extend(section.map(str::to_string));
+        for tag in &fields.tags {
+            validate_tag(tag)?;
+        }
+        let id = if item.done {
+            self.add_done(&fields.description, fields.priority, None)?
+        } else {
+            self.add(&fields.description, fields.priority, None)?
+        };
+        self.modify(id, |task| {
+            task.tags = fields.tags.into_iter().collect();
+            task.parent = parent;
+        })?;
+        Ok(id)
+    }
+}
+
+fn format_item(depth: usize, task: &Task, section: Option<&str>) -> String {
+    let mut words = vec![escape(&task.description), format!("(P{})", task.priority)];
+    for tag in &task.tags {
+        if Some(tag.as_str()) != section {
+            words.push(format!("#{}", tag));
+        }
+    }
+    let mark = if task.done { 'x' } else { ' ' };
+    let text = words
+        .into_iter()
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{}- [{}] {}\n", "  ".repeat(depth), mark, text)
+}
+
+/// Escapes what `parse_fields` would otherwise read as a priority, a tag or
+/// the end of the item.
+fn escape(description: &str) -> String {
+    let mut out = String::with_capacity(description.len());
+    for c in description.chars() {
+        match c {
+            '\\' | '#' | '(' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Undoes `escape`.
+fn unescape(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(c @ ('\\' | '#' | '(')) => out.push(c),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// The lines of `text` with their 1-based number, skipping fenced code blocks.
+fn lines(text: &str) -> impl Iterator<Item = (usize, Line<'_>)> {
+    raw_lines(text)
+        .enumerate()
+        .map(|(i, (_, line))| (i + 1, line))
+}
+
+/// The lines of `text`, with their line break, each with what it is.
+fn raw_lines(text: &str) -> impl Iterator<Item = (&str, Line<'_>)> {
+    let mut fence: Option<&str> = None;
+    text.split_inclusive('\n').map(move |raw| {
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (fence, marker) {
+            (Some(open), Some(close)) if open == close => {
+                fence = None;
+                return (raw, Line::Other);
+            }
+            (Some(_), _) => return (raw, Line::Other),
+            (None, Some(open)) => {
+                fence = Some(open);
+                return (raw, Line::Other);
+            }
+            (None, None) => {}
+        }
+        (raw, parse_line(line))
+    })
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    if let Some(title) = heading(trimmed).filter(|_| indent < 4) {
+        return Line::Heading(slug(title));
+    }
+    let Some(after_bullet) = bullet(trimmed) else {
+        return Line::Other;
+    };
+    let mut chars = after_bullet.chars();
+    let (Some('['), Some(mark), Some(']')) = (chars.next(), chars.next(), chars.next()) else {
+        return Line::Other;
+    };
+    let done = match mark {
+        ' ' => false,
+        'x' | 'X' => true,
+        _ => return Line::Other,
+    };
+    let rest = chars.as_str();
+    if !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return Line::Other;
+    }
+    Line::Item(Item {
+        indent: line[..indent]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum(),
+        done,
+        checkbox: line.len() - after_bullet.len() + 1,
+        text: rest.trim(),
+    })
+}
+
+/// Returns the title of an ATX heading (`# Title`).
+fn heading(line: &str) -> Option<&str> {
+    let level = line.bytes().take_while(|&b| b == b'#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    // A closing run of `#` is not part of the title.
+    let title = rest.trim();
+    let closed = title.trim_end_matches('#');
+    Some(if closed.is_empty() || closed.ends_with([' ', '\t']) {
+        closed.trim_end()
+    } else {
+        title
+    })
+}
+
+/// Returns what follows the bullet of a list item (`- `, `* `, `+ `, `1. `
+/// or `1) `), or `None` if the line is not a list item.
+fn bullet(line: &str) -> Option<&str> {
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    let rest = match digits {
+        0 => line.strip_prefix(['-', '*', '+'])?,
+        1..=9 => line[digits..].strip_prefix(['.', ')'])?,
+        _ => return None,
+    };
+    let text = rest.strip_prefix([' ', '\t'])?;
+    Some(text.trim_start_matches([' ', '\t']))
+}
+
+/// Makes a tag out of the words of a heading.
+fn slug(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn parse_fields(text: &str) -> Result<Fields, MarkdownError> {
+    let mut fields = Fields {
+        description: String::new(),
+        priority: DEFAULT_PRIORITY,
+        tags: Vec::new(),
+    };
+    // End of the last word, if it belongs to the description, so that the
+    // white space between description words is kept as written.
+    let mut last_end = None;
+    for word in text.split_whitespace() {
+        let start = word.as_ptr() as usize - text.as_ptr() as usize;
+        if let Some(number) = word
+            .strip_prefix("(P")
+            .or_else(|| word.strip_prefix("(p"))
+            .and_then(|w| w.strip_suffix(')'))
+        {
+            fields.priority = number
+                .parse()
+                .map_err(|_| MarkdownError::InvalidPriority(word.to_string()))?;
+            last_end = None;
+        } else if let Some(tag) = word.strip_prefix('#').filter(|t| validate_tag(t).is_ok()) {
+            fields.tags.push(tag.to_string());
+            last_end = None;
+        } else {
+            if !fields.description.is_empty() {
+                match last_end {
+                    Some(end) => fields.description.push_str(&text[end..start]),
+                    None => fields.description.push(' '),
+                }
+            }
+            fields.description.push_str(&unescape(word));
+            last_end = Some(start + word.len());
+        }
+    }
+    Ok(fields)
+}
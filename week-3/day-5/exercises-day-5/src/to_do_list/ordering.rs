@@ -0,0 +1,316 @@
+//! Other ways to look at a list than by priority.
+//!
+//! The list itself keeps its tasks by priority. An ordering only maps every
+//! task to a sort key; `sorted_by` and `print_by` sort by it on the spot, or
+//! read the index the list keeps for it if the ordering was made active with
+//! `keep_ordered`. Indexes are updated by `insert_at`, `remove_at` and
+//! `replace_at`, like the search index, so keeping one adds O(log n) to each
+//! change instead of a sort to each view.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use super::{print_task, Filter, Task, TaskId, TaskStore, ToDoList};
+
+/// One part of a sort key. Keys are compared part by part.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KeyPart {
+    Number(i64),
+    Text(String),
+}
+
+/// A way to sort the tasks of a list.
+///
+/// The key of a task must depend on the task alone, not on the other tasks
+/// or the time, so that an index of the keys stays valid as the list
+/// changes. Tasks with equal keys are sorted by ID, that is by the order
+/// they were added.
+pub trait TaskOrdering: fmt::Debug + Send + Sync {
+    /// Identifies the ordering among the active ones of a list. Orderings
+    /// that may sort differently must have different names.
+    fn name(&self) -> String;
+
+    /// Returns the key of `task`; tasks with smaller keys come first.
+    fn key(&self, task: &Task) -> Vec<KeyPart>;
+}
+
+/// Most urgent priority first.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ByPriority;
+
+/// Earliest due date first, then by priority. Tasks without a due date
+/// come last.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ByDueDate;
+
+/// Oldest task first.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ByCreation;
+
+/// By description, ignoring case.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Alphabetical;
+
+/// Highest urgency score first, with done tasks last.
+///
+/// The score adds `due_weight` points per day the task is overdue (taking
+/// them away while it is not due yet) and `age_weight` per day since it was
+/// created, and takes away `priority_weight` per priority level, so that
+/// priority 1 scores highest. A task without a due date counts as due
+/// `undated_after` days after it was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Urgency {
+    pub priority_weight: u32,
+    pub due_weight: u32,
+    pub age_weight: u32,
+    pub undated_after: u32,
+}
+
+impl Default for Urgency {
+    fn default() -> Self {
+        Self {
+            priority_weight: 4,
+            due_weight: 2,
+            age_weight: 1,
+            undated_after: 14,
+        }
+    }
+}
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+impl Urgency {
+    /// Returns the score of `task` at `now`. Done tasks score like open ones;
+    /// only the ordering puts them last.
+    pub fn score(&self, task: &Task, now: DateTime<Utc>) -> f64 {
+        let weights = i128::from(self.age_weight) + i128::from(self.due_weight);
+        let score = weights * i128::from(minutes(now)) - self.weighted(task);
+        score as f64 / MINUTES_PER_DAY as f64
+    }
+
+    /// The part of the score, in weighted minutes, that does not depend on
+    /// the time. The score at `now` is a constant, the same for all tasks,
+    /// minus this, so sorting by it sorts by score.
+    fn weighted(&self, task: &Task) -> i128 {
+        let created = minutes(task.created);
+        let due = match task.due {
+            Some(due) => minutes(start_of(due)),
+            None => created + i64::from(self.undated_after) * MINUTES_PER_DAY,
+        };
+        i128::from(self.age_weight) * i128::from(created)
+            + i128::from(self.due_weight) * i128::from(due)
+            + i128::from(self.priority_weight)
+                * i128::from(task.priority)
+                * i128::from(MINUTES_PER_DAY)
+    }
+}
+
+impl TaskOrdering for ByPriority {
+    fn name(&self) -> String {
+        "priority".to_string()
+    }
+
+    fn key(&self, task: &Task) -> Vec<KeyPart> {
+        vec![KeyPart::Number(task.priority.into())]
+    }
+}
+
+impl TaskOrdering for ByDueDate {
+    fn name(&self) -> String {
+        "due".to_string()
+    }
+
+    fn key(&self, task: &Task) -> Vec<KeyPart> {
+        let day = task.due.map_or(0, |due| due.num_days_from_ce().into());
+        vec![
+            KeyPart::Number(task.due.is_none().into()),
+            KeyPart::Number(day),
+            KeyPart::Number(task.priority.into()),
+        ]
+    }
+}
+
+impl TaskOrdering for ByCreation {
+    fn name(&self) -> String {
+        "created".to_string()
+    }
+
+    fn key(&self, task: &Task) -> Vec<KeyPart> {
+        vec![KeyPart::Number(task.created.timestamp_millis())]
+    }
+}
+
+impl TaskOrdering for Alphabetical {
+    fn name(&self) -> String {
+        "alphabetical".to_string()
+    }
+
+    fn key(&self, task: &Task) -> Vec<KeyPart> {
+        vec![
+            KeyPart::Text(task.description.to_lowercase()),
+            KeyPart::Text(task.description.clone()),
+        ]
+    }
+}
+
+impl TaskOrdering for Urgency {
+    fn name(&self) -> String {
+        format!(
+            "urgency({},{},{},{})",
+            self.priority_weight, self.due_weight, self.age_weight, self.undated_after
+        )
+    }
+
+    fn key(&self, task: &Task) -> Vec<KeyPart> {
+        let weighted = self.weighted(task).clamp(i64::MIN.into(), i64::MAX.into());
+        vec![
+            KeyPart::Number(task.done.into()),
+            KeyPart::Number(weighted as i64),
+        ]
+    }
+}
+
+/// The indexes of the active orderings of a list.
+#[derive(Debug, Default)]
+pub(super) struct OrderIndexes {
+    indexes: Vec<OrderIndex>,
+}
+
+#[derive(Debug)]
+struct OrderIndex {
+    ordering: Box<dyn TaskOrdering>,
+    name: String,
+    entries: BTreeSet<(Vec<KeyPart>, TaskId)>,
+}
+
+impl OrderIndexes {
+    pub(super) fn insert(&mut self, task: &Task) {
+        for index in &mut self.indexes {
+            index.entries.insert((index.ordering.key(task), task.id));
+        }
+    }
+
+    pub(super) fn remove(&mut self, task: &Task) {
+        for index in &mut self.indexes {
+            index.entries.remove(&(index.ordering.key(task), task.id));
+        }
+    }
+
+    /// Indexes `after` in place of `before`, the same task before a change.
+    pub(super) fn replace(&mut self, before: &Task, after: &Task) {
+        for index in &mut self.indexes {
+            let (old, new) = (index.ordering.key(before), index.ordering.key(after));
+            if old != new {
+                index.entries.remove(&(old, before.id));
+                index.entries.insert((new, after.id));
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&OrderIndex> {
+        self.indexes.iter().find(|index| index.name == name)
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Makes `ordering` active on the list; see `keep_ordered`.
+    pub fn with_ordering(mut self, ordering: impl TaskOrdering + 'static) -> Self {
+        self.keep_ordered(ordering);
+        self
+    }
+
+    /// Keeps an index of the tasks sorted by `ordering`, so that `sorted_by`
+    /// and `print_by` do not need to sort. Does nothing if an ordering with
+    /// the same name is already active.
+    pub fn keep_ordered(&mut self, ordering: impl TaskOrdering + 'static) {
+        let name = ordering.name();
+        if self.orderings.get(&name).is_some() {
+            return;
+        }
+        let entries = self
+            .tasks
+            .values()
+            .map(|task| (ordering.key(task), task.id))
+            .collect();
+        self.orderings.indexes.push(OrderIndex {
+            ordering: Box::new(ordering),
+            name,
+            entries,
+        });
+    }
+
+    /// Drops the index of the ordering called `name`, returning whether it
+    /// was active.
+    pub fn forget_ordering(&mut self, name: &str) -> bool {
+        let before = self.orderings.indexes.len();
+        self.orderings.indexes.retain(|index| index.name != name);
+        self.orderings.indexes.len() != before
+    }
+
+    /// Names of the active orderings, in the order they were made active.
+    pub fn active_orderings(&self) -> impl Iterator<Item = &str> {
+        self.orderings
+            .indexes
+            .iter()
+            .map(|index| index.name.as_str())
+    }
+
+    /// Returns all tasks sorted by `ordering`.
+    pub fn sorted_by(&self, ordering: &dyn TaskOrdering) -> Vec<&Task> {
+        match self.orderings.get(&ordering.name()) {
+            Some(index) => index
+                .entries
+                .iter()
+                .map(|(_, id)| self.get(*id).expect("indexed tasks are in the list"))
+                .collect(),
+            None => {
+                let mut tasks: Vec<(Vec<KeyPart>, &Task)> =
+                    self.tasks.values().map(|t| (ordering.key(t), t)).collect();
+                tasks.sort_by(|(a, x), (b, y)| a.cmp(b).then(x.id.cmp(&y.id)));
+                tasks.into_iter().map(|(_, task)| task).collect()
+            }
+        }
+    }
+
+    /// Prints all tasks as a tree, like `print`, with the subtasks of every
+    /// task, and the top-level tasks, sorted by `ordering`.
+    pub fn print_by(&self, ordering: &dyn TaskOrdering) {
+        for (depth, task) in self.tree_of(self.sorted_by(ordering)) {
+            print_task(depth, task);
+        }
+    }
+
+    /// Prints the tasks selected by `filter`, like `print_where`, sorted by
+    /// `ordering`.
+    pub fn print_where_by(&self, filter: &Filter, ordering: &dyn TaskOrdering) {
+        let tree = self.tree_of(self.sorted_by(ordering));
+        for (depth, task) in self.select(tree, filter) {
+            print_task(depth, task);
+        }
+    }
+}
+
+/// Finds a built-in ordering by the name the command line uses for it:
+/// `priority`, `due`, `created`, `alphabetical` or `urgency`.
+pub fn ordering_named(name: &str) -> Option<Box<dyn TaskOrdering>> {
+    Some(match name {
+        "priority" => Box::new(ByPriority),
+        "due" => Box::new(ByDueDate),
+        "created" => Box::new(ByCreation),
+        "alphabetical" => Box::new(Alphabetical),
+        "urgency" => Box::new(Urgency::default()),
+        _ => return None,
+    })
+}
+
+fn minutes(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(60)
+}
+
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("midnight exists")
+        .and_utc()
+}
@@ -0,0 +1,158 @@
+//! The rules a task must follow to get into a list.
+//!
+//! A policy is written as a JSON file; fields left out keep their default:
+//!
+//! ```json
+//! { "max_description_len": 60, "max_priority": 9, "allow_blank": false }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::ToDoError;
+
+/// Rules checked by `add_task`, `merge`, the importers and every other way a
+/// task gets into a ToDoList.
+///
+/// The default policy allows descriptions of up to 40 characters, priorities
+/// 1 to 5, and blank or repeated descriptions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationPolicy {
+    /// Longest description allowed, in grapheme clusters: what a reader
+    /// counts as characters, so that `è` or `🇮🇹` count as one.
+    pub max_description_len: usize,
+    /// Most urgent priority allowed.
+    pub min_priority: u8,
+    /// Least urgent priority allowed.
+    pub max_priority: u8,
+    /// Whether an open task may have the same description as another open
+    /// task. Done tasks never count, so recurring tasks can respawn.
+    pub allow_duplicates: bool,
+    /// Whether a description may be empty or only white space.
+    pub allow_blank: bool,
+}
+
+/// Errors that can occur while loading a policy.
+#[derive(Debug)]
+pub enum PolicyError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not a valid policy.
+    Parse(serde_json::Error),
+    /// `min_priority` is greater than `max_priority`, so no priority is allowed.
+    EmptyPriorityRange { min: u8, max: u8 },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Io(err) => write!(f, "I/O error: {}", err),
+            PolicyError::Parse(err) => write!(f, "invalid policy: {}", err),
+            PolicyError::EmptyPriorityRange { min, max } => write!(
+                f,
+                "min_priority {} is greater than max_priority {}",
+                min, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PolicyError::Io(err) => Some(err),
+            PolicyError::Parse(err) => Some(err),
+            PolicyError::EmptyPriorityRange { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for PolicyError {
+    fn from(err: io::Error) -> Self {
+        PolicyError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PolicyError {
+    fn from(err: serde_json::Error) -> Self {
+        PolicyError::Parse(err)
+    }
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            max_description_len: 40,
+            min_priority: 1,
+            max_priority: 5,
+            allow_duplicates: true,
+            allow_blank: true,
+        }
+    }
+}
+
+impl ValidationPolicy {
+    /// Loads a policy from a JSON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PolicyError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Reads a policy in the format `load` reads.
+    pub fn from_json(text: &str) -> Result<Self, PolicyError> {
+        let policy: ValidationPolicy = serde_json::from_str(text)?;
+        if policy.min_priority > policy.max_priority {
+            return Err(PolicyError::EmptyPriorityRange {
+                min: policy.min_priority,
+                max: policy.max_priority,
+            });
+        }
+        Ok(policy)
+    }
+
+    /// A policy that allows everything, for lists whose tasks were already
+    /// checked elsewhere.
+    pub(super) fn unrestricted() -> Self {
+        Self {
+            max_description_len: usize::MAX,
+            min_priority: u8::MIN,
+            max_priority: u8::MAX,
+            allow_duplicates: true,
+            allow_blank: true,
+        }
+    }
+
+    /// The priorities allowed, most urgent first.
+    pub fn priorities(&self) -> RangeInclusive<u8> {
+        self.min_priority..=self.max_priority
+    }
+
+    /// Checks the rules that depend on the task alone, that is all of them
+    /// but `allow_duplicates`.
+    pub fn check(&self, description: &str, priority: u8) -> Result<(), ToDoError> {
+        let len = description.graphemes(true).count();
+        if len > self.max_description_len {
+            return Err(ToDoError::DescriptionTooLong {
+                len,
+                max: self.max_description_len,
+            });
+        }
+        if !self.allow_blank && description.trim().is_empty() {
+            return Err(ToDoError::BlankDescription);
+        }
+        if !self.priorities().contains(&priority) {
+            return Err(ToDoError::PriorityOutOfRange {
+                priority,
+                min: self.min_priority,
+                max: self.max_priority,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,399 @@
+//! Recurring tasks.
+//!
+//! Rules are written in a subset of the iCalendar RRULE syntax (RFC 5545),
+//! so that they can be exchanged with calendars:
+//!
+//! | Part                     | Meaning                                        |
+//! |--------------------------|------------------------------------------------|
+//! | `FREQ=DAILY`, `WEEKLY`, `MONTHLY` | how often the task comes back (required) |
+//! | `INTERVAL=N`             | every N days, weeks or months (default 1)      |
+//! | `BYDAY=MO,WE,...`        | weekdays, with `FREQ=WEEKLY` only              |
+//! | `BYMONTHDAY=N`           | day of the month, negative from the end, with `FREQ=MONTHLY` only |
+//! | `UNTIL=YYYYMMDD`         | last date an occurrence may fall on            |
+//! | `X-FROM=COMPLETION`      | count from the day the task is done, not from its due date |
+//!
+//! Weeks start on Monday. As in RFC 5545, months without the requested day
+//! are skipped rather than clamped.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use super::{TaskId, TaskStore, ToDoError, ToDoList};
+
+/// Periods searched for an occurrence before a rule is considered exhausted,
+/// which only happens for rules like the 30th of every 12th February.
+const MAX_PERIODS: u32 = 1000;
+
+/// How often a recurring task comes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A recurrence rule, such as `FREQ=WEEKLY;BYDAY=MO,TH`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    /// Weekdays of a weekly rule, Monday first; empty means the start's weekday.
+    by_day: Vec<Weekday>,
+    /// Day of a monthly rule; `None` means the start's day.
+    by_month_day: Option<i8>,
+    until: Option<NaiveDate>,
+    from_completion: bool,
+}
+
+/// Why a recurrence rule could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecurrenceError {
+    /// The rule has no `FREQ` part.
+    MissingFrequency,
+    /// The part is not part of the supported subset.
+    UnsupportedPart(String),
+    /// The part appears more than once.
+    DuplicatePart(String),
+    /// The value of a part is malformed or not supported.
+    InvalidValue { part: String, value: String },
+    /// The part does not apply to the frequency of the rule.
+    Misplaced(String),
+}
+
+impl fmt::Display for RecurrenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecurrenceError::MissingFrequency => write!(f, "rule has no FREQ"),
+            RecurrenceError::UnsupportedPart(part) => write!(f, "{} is not supported", part),
+            RecurrenceError::DuplicatePart(part) => write!(f, "{} is given twice", part),
+            RecurrenceError::InvalidValue { part, value } => {
+                write!(f, "invalid value '{}' for {}", value, part)
+            }
+            RecurrenceError::Misplaced(part) => {
+                write!(f, "{} does not apply to this frequency", part)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecurrenceError {}
+
+impl Recurrence {
+    /// Parses a rule such as `FREQ=DAILY;INTERVAL=2`, with or without a
+    /// leading `RRULE:`. Part names and values are case-insensitive.
+    pub fn parse(input: &str) -> Result<Self, RecurrenceError> {
+        let input = input.trim();
+        let rule = match input.get(..6) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("RRULE:") => &input[6..],
+            _ => input,
+        };
+        let mut frequency = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+        let mut until = None;
+        let mut from_completion = None;
+
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (name, value) = part.split_once('=').unwrap_or((part, ""));
+            let name = name.to_ascii_uppercase();
+            let value = value.to_ascii_uppercase();
+            let invalid = || RecurrenceError::InvalidValue {
+                part: name.clone(),
+                value: value.clone(),
+            };
+            let duplicate = match name.as_str() {
+                "FREQ" => {
+                    let parsed = match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(invalid()),
+                    };
+                    frequency.replace(parsed).is_some()
+                }
+                "INTERVAL" => {
+                    let parsed = value.parse().ok().filter(|&n: &u32| n > 0);
+                    interval.replace(parsed.ok_or_else(invalid)?).is_some()
+                }
+                "BYDAY" => {
+                    let mut days = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(invalid)?;
+                    days.sort_by_key(Weekday::num_days_from_monday);
+                    days.dedup();
+                    by_day.replace(days).is_some()
+                }
+                "BYMONTHDAY" => {
+                    let parsed = value
+                        .parse()
+                        .ok()
+                        .filter(|&d: &i8| d != 0 && (-31..=31).contains(&d));
+                    by_month_day.replace(parsed.ok_or_else(invalid)?).is_some()
+                }
+                "UNTIL" => {
+                    let parsed = NaiveDate::parse_from_str(&value, "%Y%m%d");
+                    until.replace(parsed.map_err(|_| invalid())?).is_some()
+                }
+                "X-FROM" => {
+                    let parsed = match value.as_str() {
+                        "COMPLETION" => true,
+                        "DUE" => false,
+                        _ => return Err(invalid()),
+                    };
+                    from_completion.replace(parsed).is_some()
+                }
+                _ => return Err(RecurrenceError::UnsupportedPart(name)),
+            };
+            if duplicate {
+                return Err(RecurrenceError::DuplicatePart(name));
+            }
+        }
+
+        let frequency = frequency.ok_or(RecurrenceError::MissingFrequency)?;
+        if by_day.is_some() && frequency != Frequency::Weekly {
+            return Err(RecurrenceError::Misplaced("BYDAY".to_string()));
+        }
+        if by_month_day.is_some() && frequency != Frequency::Monthly {
+            return Err(RecurrenceError::Misplaced("BYMONTHDAY".to_string()));
+        }
+        Ok(Recurrence {
+            frequency,
+            interval: interval.unwrap_or(1),
+            by_day: by_day.unwrap_or_default(),
+            by_month_day,
+            until,
+            from_completion: from_completion.unwrap_or(false),
+        })
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Whether occurrences are counted from the completion day rather than
+    /// from the due date.
+    pub fn from_completion(&self) -> bool {
+        self.from_completion
+    }
+
+    /// Returns the first occurrence of the series starting on `start` that
+    /// falls strictly after `after`, or `None` if the rule has ended.
+    pub fn next(&self, start: NaiveDate, after: NaiveDate) -> Option<NaiveDate> {
+        // Skip the periods that end before `after` without walking them.
+        let first = if after > start {
+            self.periods_between(start, after) / self.interval
+        } else {
+            0
+        };
+        (first..first.saturating_add(MAX_PERIODS))
+            .flat_map(|period| self.occurrences(start, period))
+            .find(|&date| date >= start && date > after)
+            .filter(|&date| self.until.is_none_or(|until| date <= until))
+    }
+
+    /// Number of whole days, weeks or months from `start` to `date`.
+    fn periods_between(&self, start: NaiveDate, date: NaiveDate) -> u32 {
+        let count = match self.frequency {
+            Frequency::Daily => (date - start).num_days(),
+            Frequency::Weekly => (week_start(date) - week_start(start)).num_days() / 7,
+            Frequency::Monthly => i64::from(month_index(date) - month_index(start)),
+        };
+        u32::try_from(count).unwrap_or(u32::MAX)
+    }
+
+    /// Occurrences in the `period`-th period of the series starting on
+    /// `start`, in date order.
+    fn occurrences(&self, start: NaiveDate, period: u32) -> Vec<NaiveDate> {
+        let step = u64::from(period) * u64::from(self.interval);
+        match self.frequency {
+            Frequency::Daily => start
+                .checked_add_days(Days::new(step))
+                .into_iter()
+                .collect(),
+            Frequency::Weekly => {
+                let Some(week) = week_start(start).checked_add_days(Days::new(step * 7)) else {
+                    return Vec::new();
+                };
+                let weekday = start.weekday();
+                let days = if self.by_day.is_empty() {
+                    std::slice::from_ref(&weekday)
+                } else {
+                    &self.by_day
+                };
+                days.iter()
+                    .filter_map(|d| {
+                        week.checked_add_days(Days::new(d.num_days_from_monday().into()))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let Some(month) = u32::try_from(step)
+                    .ok()
+                    .and_then(|step| start.with_day(1)?.checked_add_months(Months::new(step)))
+                else {
+                    return Vec::new();
+                };
+                let day = self.by_month_day.unwrap_or(start.day() as i8);
+                day_of_month(month, day).into_iter().collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|d| weekday_code(*d)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(day) = self.by_month_day {
+            write!(f, ";BYMONTHDAY={}", day)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+        if self.from_completion {
+            write!(f, ";X-FROM=COMPLETION")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Recurrence::parse(s)
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = RecurrenceError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Recurrence::parse(&s)
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(rule: Recurrence) -> Self {
+        rule.to_string()
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Makes the task with the given ID recur by `rule`, or stop recurring
+    /// when `rule` is `None`.
+    ///
+    /// When a recurring task is marked as done, a new open task with the same
+    /// description, priority and tags is added, due on the next occurrence,
+    /// and the rule moves over to it. The new task goes under the nearest
+    /// ancestor that is still open, which is the parent unless it was
+    /// completed along with the task. Occurrences are counted from
+    /// the due date, or from today for a task without one, and missed ones
+    /// are skipped: the new task is never due before today.
+    pub fn set_recurrence(
+        &mut self,
+        id: TaskId,
+        rule: Option<Recurrence>,
+    ) -> Result<(), ToDoError> {
+        self.transaction("set recurrence", |list| {
+            list.modify(id, |task| task.recurrence = rule)
+        })
+    }
+
+    /// Adds the next occurrence of the recurring task with the given ID,
+    /// which was just marked as done, and returns its ID.
+    pub(super) fn spawn_next(&mut self, id: TaskId) -> Result<Option<TaskId>, ToDoError> {
+        let task = self.tasks[&self.position(id)?].clone();
+        let Some(rule) = task.recurrence else {
+            return Ok(None);
+        };
+        self.modify(id, |task| task.recurrence = None)?;
+
+        let today = self.clock.today();
+        let (start, after) = match task.due {
+            _ if rule.from_completion => (today, today),
+            Some(due) => (due, due.max(today.pred_opt().unwrap_or(today))),
+            None => (today, today),
+        };
+        let Some(due) = rule.next(start, after) else {
+            return Ok(None);
+        };
+        // A done task cannot have open subtasks.
+        let parent = self
+            .ancestors(id)
+            .find(|&a| self.get(a).is_some_and(|t| !t.done));
+        let next = self.add(&task.description, task.priority, Some(due))?;
+        self.modify(next, |new| {
+            new.tags = task.tags;
+            new.parent = parent;
+            new.recurrence = Some(rule);
+        })?;
+        Ok(Some(next))
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+/// Returns the `day`-th day of the month of `month`, counting from the end
+/// if `day` is negative, or `None` if the month is too short.
+fn day_of_month(month: NaiveDate, day: i8) -> Option<NaiveDate> {
+    if day > 0 {
+        return month.with_day(day as u32);
+    }
+    let last = month.checked_add_months(Months::new(1))?.pred_opt()?;
+    let day = u32::try_from(last.day() as i32 + 1 + i32::from(day)).ok()?;
+    month.with_day(day)
+}
+
+fn month_index(date: NaiveDate) -> i32 {
+    date.year() * 12 + date.month0() as i32
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    Some(match code {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
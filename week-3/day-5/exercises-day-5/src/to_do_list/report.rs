@@ -0,0 +1,269 @@
+//! Numbers for standups: open and done tasks per priority, how old the open
+//! tasks are and how the list burned down over a range of days.
+//!
+//! Reports are computed from the tasks still in the list, at the time of
+//! the list's clock, so a fixed clock gives the same report every time.
+//! Tasks removed with `remove_done` no longer count.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use super::{Task, TaskStore, ToDoList};
+
+/// Aggregates of a list at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// When the report was made, from the list's clock.
+    pub as_of: DateTime<Utc>,
+    /// One row per priority allowed by the list's policy, plus any other
+    /// priority in use, most urgent first.
+    pub priorities: Vec<PriorityRow>,
+    /// Mean time since the open tasks were created, if there are any.
+    pub average_open_age: Option<Duration>,
+    /// One row per day of the range asked for.
+    pub days: Vec<DayRow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityRow {
+    pub priority: u8,
+    pub open: usize,
+    pub done: usize,
+}
+
+/// The state of the list at the end of one day (UTC).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayRow {
+    pub date: NaiveDate,
+    /// Tasks created during the day.
+    pub created: usize,
+    /// Tasks completed during the day.
+    pub completed: usize,
+    /// Tasks that existed and were open at the end of the day.
+    pub open: usize,
+    /// Share of the tasks existing at the end of the day that were done,
+    /// if there were any.
+    pub completion_rate: Option<f64>,
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Computes a report as of now, with one row per day from `from` to
+    /// `to`, both included. The day rows are empty if `from` is after `to`.
+    pub fn report(&self, from: NaiveDate, to: NaiveDate) -> Report {
+        let as_of = self.clock.now();
+
+        let mut priorities: Vec<PriorityRow> = self
+            .policy
+            .priorities()
+            .map(|priority| PriorityRow {
+                priority,
+                open: 0,
+                done: 0,
+            })
+            .collect();
+        for task in self.tasks.values() {
+            let row = match priorities.iter().position(|r| r.priority == task.priority) {
+                Some(index) => &mut priorities[index],
+                None => {
+                    priorities.push(PriorityRow {
+                        priority: task.priority,
+                        open: 0,
+                        done: 0,
+                    });
+                    priorities.last_mut().expect("row was just pushed")
+                }
+            };
+            if task.done {
+                row.done += 1;
+            } else {
+                row.open += 1;
+            }
+        }
+        priorities.sort_by_key(|row| row.priority);
+
+        let open: Vec<&Task> = self.tasks.values().filter(|t| !t.done).collect();
+        let average_open_age = (!open.is_empty()).then(|| {
+            let total: Duration = open.iter().map(|t| as_of - t.created).sum();
+            total / open.len() as i32
+        });
+
+        let days = from
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .map(|date| self.day(date))
+            .collect();
+
+        Report {
+            as_of,
+            priorities,
+            average_open_age,
+            days,
+        }
+    }
+
+    fn day(&self, date: NaiveDate) -> DayRow {
+        let end = start_of(date + Duration::days(1));
+        let existing: Vec<&Task> = self.tasks.values().filter(|t| t.created < end).collect();
+        let done = existing
+            .iter()
+            .filter(|t| t.completed.is_some_and(|c| c < end))
+            .count();
+        DayRow {
+            date,
+            created: self
+                .tasks
+                .values()
+                .filter(|t| t.created.date_naive() == date)
+                .count(),
+            completed: self
+                .tasks
+                .values()
+                .filter(|t| t.completed.is_some_and(|c| c.date_naive() == date))
+                .count(),
+            open: existing.len() - done,
+            completion_rate: (!existing.is_empty()).then(|| done as f64 / existing.len() as f64),
+        }
+    }
+}
+
+impl Report {
+    /// Renders the whole report as plain-text tables.
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Report as of {}",
+            self.as_of.format("%Y-%m-%d %H:%M UTC")
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(out, "Priority  Open  Done  Total");
+        for row in &self.priorities {
+            let total = row.open + row.done;
+            let _ = writeln!(
+                out,
+                "{:>8}  {:>4}  {:>4}  {:>5}",
+                row.priority, row.open, row.done, total
+            );
+        }
+        let open: usize = self.priorities.iter().map(|r| r.open).sum();
+        let done: usize = self.priorities.iter().map(|r| r.done).sum();
+        let _ = writeln!(
+            out,
+            "{:>8}  {:>4}  {:>4}  {:>5}",
+            "Total",
+            open,
+            done,
+            open + done
+        );
+        let _ = writeln!(out);
+        match self.average_open_age {
+            Some(age) => {
+                let days = age.num_minutes() as f64 / (24.0 * 60.0);
+                let _ = writeln!(out, "Average age of open tasks: {:.1} days", days);
+            }
+            None => {
+                let _ = writeln!(out, "Average age of open tasks: no open tasks");
+            }
+        }
+        if !self.days.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "Date        Created  Completed  Open  Done");
+            for day in &self.days {
+                let _ = writeln!(
+                    out,
+                    "{}  {:>7}  {:>9}  {:>4}  {:>4}",
+                    day.date,
+                    day.created,
+                    day.completed,
+                    day.open,
+                    percent(day.completion_rate)
+                );
+            }
+        }
+        out
+    }
+
+    /// Renders the priority rows as CSV, with a header line.
+    pub fn priorities_csv(&self) -> String {
+        let mut out = String::from("priority,open,done\n");
+        for row in &self.priorities {
+            let _ = writeln!(out, "{},{},{}", row.priority, row.open, row.done);
+        }
+        out
+    }
+
+    /// Renders the day rows as CSV, with a header line. The completion rate
+    /// is a fraction between 0 and 1, empty on days without tasks.
+    pub fn days_csv(&self) -> String {
+        let mut out = String::from("date,created,completed,open,completion_rate\n");
+        for day in &self.days {
+            let rate = day
+                .completion_rate
+                .map(|rate| format!("{:.3}", rate))
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{}",
+                day.date, day.created, day.completed, day.open, rate
+            );
+        }
+        out
+    }
+
+    /// Draws the open tasks at the end of each day as a bar chart `height`
+    /// lines tall, one column per day.
+    pub fn burndown(&self, height: usize) -> String {
+        let (Some(first), Some(last)) = (self.days.first(), self.days.last()) else {
+            return "(no days)\n".to_string();
+        };
+        let height = height.max(1);
+        let max = self.days.iter().map(|d| d.open).max().unwrap_or(0).max(1);
+        let gutter = max.to_string().len();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "Open tasks, {} to {}", first.date, last.date);
+        for level in (1..=height).rev() {
+            let label = if level == height {
+                max.to_string()
+            } else {
+                String::new()
+            };
+            let bars: String = self
+                .days
+                .iter()
+                // A bar reaches this line if it is at least half way into it.
+                .map(|d| {
+                    if 2 * d.open * height >= (2 * level - 1) * max {
+                        '#'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect();
+            let _ = writeln!(out, "{:>gutter$} |{}", label, bars.trim_end());
+        }
+        let _ = writeln!(out, "{:>gutter$} +{}", 0, "-".repeat(self.days.len()));
+        let first_label = first.date.format("%m-%d").to_string();
+        let last_label = last.date.format("%m-%d").to_string();
+        let axis = if self.days.len() > first_label.len() + last_label.len() {
+            let gap = self.days.len() - first_label.len() - last_label.len();
+            format!("{}{}{}", first_label, " ".repeat(gap), last_label)
+        } else {
+            first_label
+        };
+        let _ = writeln!(out, "{:>gutter$}  {}", "", axis);
+        out
+    }
+}
+
+fn percent(rate: Option<f64>) -> String {
+    rate.map(|rate| format!("{:.0}%", rate * 100.0))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("midnight exists")
+        .and_utc()
+}
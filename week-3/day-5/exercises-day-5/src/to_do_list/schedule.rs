@@ -0,0 +1,51 @@
+use chrono::{Days, NaiveDate};
+
+use super::{Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Sets or clears the due date of the task with the given ID.
+    pub fn set_due(&mut self, id: TaskId, due: Option<NaiveDate>) -> Result<(), ToDoError> {
+        self.transaction("set due date", |list| {
+            list.modify(id, |task| task.due = due)
+        })
+    }
+
+    /// Returns the open tasks whose due date is before today.
+    pub fn overdue(&self) -> impl Iterator<Item = &Task> {
+        let today = self.clock.today();
+        self.open_due(move |due| due < today)
+    }
+
+    /// Returns the open tasks due today.
+    pub fn due_today(&self) -> impl Iterator<Item = &Task> {
+        let today = self.clock.today();
+        self.open_due(move |due| due == today)
+    }
+
+    /// Returns the open tasks due between today and `days` days from now,
+    /// both included. Overdue tasks are not part of the result.
+    pub fn due_within(&self, days: u32) -> impl Iterator<Item = &Task> {
+        let today = self.clock.today();
+        let last = today
+            .checked_add_days(Days::new(days.into()))
+            .unwrap_or(NaiveDate::MAX);
+        self.open_due(move |due| today <= due && due <= last)
+    }
+
+    /// Returns all tasks sorted by due date, earliest first, with undated
+    /// tasks last. Tasks with the same due date keep the list order, that is
+    /// ascending priority and then insertion order.
+    pub fn by_due_date(&self) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.values().collect();
+        // `sort_by_key` is stable, so ties keep the priority order of `tasks`.
+        tasks.sort_by_key(|task| (task.due.is_none(), task.due));
+        tasks
+    }
+
+    /// Open tasks in list order whose due date satisfies `pred`.
+    fn open_due(&self, pred: impl Fn(NaiveDate) -> bool) -> impl Iterator<Item = &Task> {
+        self.tasks
+            .values()
+            .filter(move |task| !task.done && task.due.is_some_and(&pred))
+    }
+}
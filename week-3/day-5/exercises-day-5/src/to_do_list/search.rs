@@ -0,0 +1,232 @@
+//! Full-text search over the descriptions and tags of tasks.
+//!
+//! The list keeps an inverted index from every word to the tasks that use
+//! it. It is updated by `insert_at`, `remove_at` and `replace_at`, like the
+//! undo history, so it follows every change, undo and redo included.
+//!
+//! Words are compared in lower case. A query word matches a word of a task
+//! exactly, as its prefix, or with a few typos: one for words of four to six
+//! letters, two for longer ones. Every word of the query has to match.
+//!
+//! Typos change the length of a word by at most their number, so the index
+//! also groups its words by length, and a query word is only compared with
+//! the words whose length is close enough to its own.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+/// Score of a query word that matches a word of the task exactly.
+const EXACT: u32 = 3;
+/// Score of a query word that is the start of a word of the task.
+const PREFIX: u32 = 2;
+/// Score of a query word that matches a word of the task with typos.
+const FUZZY: u32 = 1;
+
+/// Inverted index of the words of a list's tasks.
+#[derive(Debug, Default)]
+pub(super) struct SearchIndex {
+    /// Every word, with the tasks that use it.
+    words: BTreeMap<String, BTreeSet<TaskId>>,
+    /// The words of every task, to take them out when it changes.
+    by_task: HashMap<TaskId, BTreeSet<String>>,
+    /// The words of `words`, by their length in characters.
+    by_length: BTreeMap<usize, BTreeSet<String>>,
+}
+
+/// A task found by `ToDoList::search`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchHit<'a> {
+    pub task: &'a Task,
+    /// Higher is better: 3 per query word matched exactly, 2 per prefix and
+    /// 1 per word with typos.
+    pub score: u32,
+}
+
+impl SearchIndex {
+    pub(super) fn insert(&mut self, task: &Task) {
+        let words = words_of(task);
+        for word in &words {
+            let ids = self.words.entry(word.clone()).or_default();
+            if ids.is_empty() {
+                let length = word.chars().count();
+                self.by_length
+                    .entry(length)
+                    .or_default()
+                    .insert(word.clone());
+            }
+            ids.insert(task.id);
+        }
+        self.by_task.insert(task.id, words);
+    }
+
+    pub(super) fn remove(&mut self, task: &Task) {
+        for word in self.by_task.remove(&task.id).unwrap_or_default() {
+            if let Some(ids) = self.words.get_mut(&word) {
+                ids.remove(&task.id);
+                if ids.is_empty() {
+                    self.words.remove(&word);
+                    let length = word.chars().count();
+                    if let Some(bucket) = self.by_length.get_mut(&length) {
+                        bucket.remove(&word);
+                        if bucket.is_empty() {
+                            self.by_length.remove(&length);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Indexes `after` in place of `before`, the same task before a change.
+    pub(super) fn replace(&mut self, before: &Task, after: &Task) {
+        if before.description != after.description || before.tags != after.tags {
+            self.remove(before);
+            self.insert(after);
+        }
+    }
+
+    /// Returns the tasks that match every word of `query`, with their score.
+    fn search(&self, query: &str) -> HashMap<TaskId, u32> {
+        let mut scores: Option<HashMap<TaskId, u32>> = None;
+        for term in tokenize(query) {
+            let mut best: HashMap<TaskId, u32> = HashMap::new();
+            let mut credit = |ids: &BTreeSet<TaskId>, score: u32| {
+                for &id in ids {
+                    let entry = best.entry(id).or_default();
+                    *entry = (*entry).max(score);
+                }
+            };
+            // Words starting with the term sort right after it.
+            let prefixed = self
+                .words
+                .range(term.clone()..)
+                .take_while(|(word, _)| word.starts_with(&term));
+            for (word, ids) in prefixed {
+                credit(ids, if *word == term { EXACT } else { PREFIX });
+            }
+            let typos = typos_allowed(&term);
+            if typos > 0 {
+                let length = term.chars().count();
+                let candidates = self
+                    .by_length
+                    .range(length - typos..=length + typos)
+                    .flat_map(|(_, words)| words);
+                for word in candidates {
+                    if !word.starts_with(&term) && distance_within(&term, word, typos) {
+                        credit(&self.words[word], FUZZY);
+                    }
+                }
+            }
+            scores = Some(match scores {
+                None => best,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| best.get(&id).map(|b| (id, score + b)))
+                    .collect(),
+            });
+        }
+        scores.unwrap_or_default()
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Returns the tasks matching `query`, best first; tasks with the same
+    /// score keep their list order. A query without words matches nothing.
+    pub fn search(&self, query: &str) -> Vec<SearchHit<'_>> {
+        let scores = self.index.search(query);
+        let mut hits: Vec<SearchHit> = self
+            .tasks
+            .values()
+            .filter_map(|task| {
+                let score = *scores.get(&task.id)?;
+                Some(SearchHit { task, score })
+            })
+            .collect();
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+        hits
+    }
+
+    /// Marks as done the open task that best matches `query` and returns
+    /// its ID.
+    ///
+    /// Refuses to choose when several open tasks match equally well, and
+    /// lists them in the error instead.
+    pub fn mark_done_matching(&mut self, query: &str) -> Result<TaskId, ToDoError> {
+        let open: Vec<SearchHit> = self
+            .search(query)
+            .into_iter()
+            .filter(|hit| !hit.task.done)
+            .collect();
+        let Some(best) = open.first() else {
+            return Err(ToDoError::NoMatch(query.to_string()));
+        };
+        let tied: Vec<TaskId> = open
+            .iter()
+            .take_while(|hit| hit.score == best.score)
+            .map(|hit| hit.task.id)
+            .collect();
+        if let [id] = tied[..] {
+            self.mark_done_by_id(id)?;
+            Ok(id)
+        } else {
+            Err(ToDoError::AmbiguousMatch {
+                query: query.to_string(),
+                candidates: tied,
+            })
+        }
+    }
+}
+
+/// The words of the description and tags of a task. The `+` and `@` of
+/// todo.txt tags are not part of their words.
+fn words_of(task: &Task) -> BTreeSet<String> {
+    let tags = task.tags.iter().map(String::as_str);
+    std::iter::once(task.description.as_str())
+        .chain(tags)
+        .flat_map(tokenize)
+        .collect()
+}
+
+/// Splits `text` into lower-case words of letters and digits.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn typos_allowed(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    }
+}
+
+/// Whether `a` can be turned into `b` with at most `max` insertions,
+/// deletions, substitutions or swaps of adjacent letters.
+fn distance_within(a: &str, b: &str, max: usize) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return false;
+    }
+    // Optimal string alignment distance, keeping the last two rows.
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (previous[j] + 1)
+                .min(row[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()] <= max
+}
@@ -0,0 +1,93 @@
+//! A ToDoList that several threads can use at once.
+
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{Filter, MergeReport, Task, TaskId, ToDoError, ToDoList};
+
+/// Handle to a ToDoList shared between threads.
+///
+/// Cloning the handle shares the same list. Any number of threads may read
+/// at the same time, while each change takes the lock for the whole
+/// operation, so it happens at one point in time between those of the other
+/// threads and the list is always sorted by priority.
+///
+/// A thread that panics while changing the list poisons it, and every later
+/// use of the handle panics too rather than work on a list left half-changed.
+#[derive(Debug, Clone, Default)]
+pub struct SharedToDoList {
+    inner: Arc<RwLock<ToDoList>>,
+}
+
+impl SharedToDoList {
+    /// Shares `list` between threads.
+    pub fn new(list: ToDoList) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(list)),
+        }
+    }
+
+    /// Locks the list for reading. Other readers are let in, writers wait
+    /// until the guard is dropped.
+    pub fn read(&self) -> RwLockReadGuard<'_, ToDoList> {
+        self.inner.read().expect("ToDoList lock poisoned")
+    }
+
+    /// Locks the list for writing, so that several changes can be made as
+    /// one step. Everyone else waits until the guard is dropped.
+    pub fn write(&self) -> RwLockWriteGuard<'_, ToDoList> {
+        self.inner.write().expect("ToDoList lock poisoned")
+    }
+
+    /// Adds a new task, as `ToDoList::add_task` does.
+    pub fn add_task(&self, description: &str, priority: u8) -> Result<TaskId, ToDoError> {
+        self.write().add_task(description, priority)
+    }
+
+    /// Marks the first open task with this description as done, as
+    /// `ToDoList::mark_done` does.
+    pub fn mark_done(&self, description: &str) -> Result<TaskId, ToDoError> {
+        self.write().mark_done(description)
+    }
+
+    /// Marks the task with the given ID as done, returning the ID of its
+    /// next occurrence if it recurs.
+    pub fn mark_done_by_id(&self, id: TaskId) -> Result<Option<TaskId>, ToDoError> {
+        self.write().mark_done_by_id(id)
+    }
+
+    /// Removes all done tasks and returns how many were removed.
+    pub fn remove_done(&self) -> Result<usize, ToDoError> {
+        self.write().remove_done()
+    }
+
+    /// Merges another list into this one, as `ToDoList::merge` does.
+    pub fn merge(&self, other: &ToDoList) -> MergeReport {
+        self.write().merge(other)
+    }
+
+    /// Returns a copy of the task with the given ID, if any.
+    pub fn get(&self, id: TaskId) -> Option<Task> {
+        self.read().get(id).cloned()
+    }
+
+    /// Returns a copy of all tasks, in list order, taken at one point in time.
+    pub fn snapshot(&self) -> Vec<Task> {
+        self.read().iter().cloned().collect()
+    }
+
+    /// Returns a copy of the tasks selected by `filter`, in list order.
+    pub fn snapshot_where(&self, filter: &Filter) -> Vec<Task> {
+        self.read().iter_where(filter).cloned().collect()
+    }
+
+    /// Prints the list, as `ToDoList::print` does.
+    pub fn print(&self) {
+        self.read().print();
+    }
+}
+
+impl From<ToDoList> for SharedToDoList {
+    fn from(list: ToDoList) -> Self {
+        Self::new(list)
+    }
+}
@@ -0,0 +1,268 @@
+//! A task store in an embedded SQLite database.
+//!
+//! The schema is created and upgraded by the migrations below, run in order
+//! when a database is opened; SQLite's `user_version` records how many have
+//! run. A migration, once released, is never changed: a new field of `Task`
+//! needs a new migration.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use super::store::{StoreChange, StoreError, TaskStore};
+use super::{Filter, Recurrence, Task, TaskId};
+
+/// The migrations, in order; the schema version is the number that ran.
+const MIGRATIONS: &[&str] = &[
+    // 1: the fields of tasks before subtasks and recurrence.
+    "CREATE TABLE tasks (
+         id INTEGER PRIMARY KEY,
+         description TEXT NOT NULL,
+         priority INTEGER NOT NULL,
+         done INTEGER NOT NULL,
+         created TEXT NOT NULL,
+         completed TEXT
+     );
+     CREATE TABLE meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+     INSERT INTO meta (key, value) VALUES ('next_id', 1);",
+    // 2: due dates, subtasks and recurrence rules.
+    "ALTER TABLE tasks ADD COLUMN due TEXT;
+     ALTER TABLE tasks ADD COLUMN parent INTEGER;
+     ALTER TABLE tasks ADD COLUMN recurrence TEXT;",
+    // 3: tags.
+    "CREATE TABLE tags (
+         task INTEGER NOT NULL,
+         tag TEXT NOT NULL,
+         PRIMARY KEY (task, tag)
+     );",
+];
+
+/// Keeps the tasks in an SQLite database, one row per task.
+///
+/// Each `apply` runs in one database transaction, so the database never
+/// holds half an operation.
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// The schema version this version of the store writes.
+    pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+    /// Opens the database in `path`, creating it if needed, and upgrades
+    /// its schema.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens a database that only lives as long as the store.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, StoreError> {
+        let version = schema_version(&conn)?;
+        if version > Self::SCHEMA_VERSION {
+            return Err(StoreError::UnsupportedSchema(version));
+        }
+        for (done, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", done + 1)?;
+            tx.commit()?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// Returns the schema version of the database.
+    pub fn schema_version(&self) -> Result<u32, StoreError> {
+        schema_version(&self.conn)
+    }
+}
+
+impl TaskStore for SqliteStore {
+    fn query(&self, filter: Option<&Filter>) -> Result<Vec<Task>, StoreError> {
+        let mut tags = self
+            .conn
+            .prepare("SELECT task, tag FROM tags ORDER BY task, tag")?;
+        let mut tags: Vec<(u64, String)> = tags
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        tags.reverse();
+
+        let mut rows = self.conn.prepare(
+            "SELECT id, description, priority, done, due, created, completed, parent, recurrence
+             FROM tasks ORDER BY id",
+        )?;
+        let mut tasks = Vec::new();
+        for task in rows.query_map([], task_from_row)? {
+            let mut task = task?;
+            // Both lists are sorted by task ID.
+            while tags.last().is_some_and(|(id, _)| *id <= task.id.0) {
+                let (id, tag) = tags.pop().expect("checked above");
+                if id == task.id.0 {
+                    task.tags.insert(tag);
+                }
+            }
+            if filter.is_none_or(|f| f.matches(&task)) {
+                tasks.push(task);
+            }
+        }
+        Ok(tasks)
+    }
+
+    fn next_id(&self) -> Result<u64, StoreError> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = 'next_id'", [], |row| {
+                row.get(0)
+            })?)
+    }
+
+    fn apply(&mut self, changes: &[StoreChange]) -> Result<(), StoreError> {
+        // Dropping the transaction on error rolls it back.
+        let tx = self.conn.transaction()?;
+        for change in changes {
+            match change {
+                StoreChange::Insert(task) => {
+                    if exists(&tx, task.id)? {
+                        return Err(StoreError::Duplicate(task.id));
+                    }
+                    write_task(
+                        &tx,
+                        "INSERT INTO tasks (id, description, priority, done, due, created,
+                             completed, parent, recurrence)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        task,
+                    )?;
+                    insert_tags(&tx, task)?;
+                    raise_next_id(&tx, task.id.0 + 1)?;
+                }
+                StoreChange::Update(task) => {
+                    let updated = write_task(
+                        &tx,
+                        "UPDATE tasks SET description = ?2, priority = ?3, done = ?4, due = ?5,
+                             created = ?6, completed = ?7, parent = ?8, recurrence = ?9
+                         WHERE id = ?1",
+                        task,
+                    )?;
+                    if updated == 0 {
+                        return Err(StoreError::NotFound(task.id));
+                    }
+                    tx.execute("DELETE FROM tags WHERE task = ?1", [task.id.0])?;
+                    insert_tags(&tx, task)?;
+                }
+                StoreChange::Delete(id) => {
+                    if tx.execute("DELETE FROM tasks WHERE id = ?1", [id.0])? == 0 {
+                        return Err(StoreError::NotFound(*id));
+                    }
+                    tx.execute("DELETE FROM tags WHERE task = ?1", [id.0])?;
+                }
+                StoreChange::NextId(next_id) => {
+                    tx.execute(
+                        "UPDATE meta SET value = ?1 WHERE key = 'next_id'",
+                        [next_id],
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn schema_version(conn: &Connection) -> Result<u32, StoreError> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+fn exists(tx: &Transaction, id: TaskId) -> rusqlite::Result<bool> {
+    tx.query_row("SELECT 1 FROM tasks WHERE id = ?1", [id.0], |_| Ok(()))
+        .optional()
+        .map(|row| row.is_some())
+}
+
+/// Runs `sql` with the fields of `task` but its tags as parameters 1 to 9,
+/// and returns the number of rows changed.
+fn write_task(tx: &Transaction, sql: &str, task: &Task) -> rusqlite::Result<usize> {
+    tx.execute(
+        sql,
+        params![
+            task.id.0,
+            task.description,
+            task.priority,
+            task.done,
+            task.due.map(|due| due.to_string()),
+            timestamp(task.created),
+            task.completed.map(timestamp),
+            task.parent.map(|parent| parent.0),
+            task.recurrence.as_ref().map(Recurrence::to_string),
+        ],
+    )
+}
+
+fn insert_tags(tx: &Transaction, task: &Task) -> rusqlite::Result<()> {
+    let mut insert = tx.prepare_cached("INSERT INTO tags (task, tag) VALUES (?1, ?2)")?;
+    for tag in &task.tags {
+        insert.execute(params![task.id.0, tag])?;
+    }
+    Ok(())
+}
+
+/// Makes sure the next ID handed out is at least `next_id`.
+fn raise_next_id(tx: &Transaction, next_id: u64) -> rusqlite::Result<()> {
+    tx.execute(
+        "UPDATE meta SET value = MAX(value, ?1) WHERE key = 'next_id'",
+        [next_id],
+    )?;
+    Ok(())
+}
+
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    Ok(Task {
+        id: TaskId(row.get(0)?),
+        description: row.get(1)?,
+        priority: row.get(2)?,
+        done: row.get(3)?,
+        due: row
+            .get::<_, Option<String>>(4)?
+            .map(|due| parse(4, &due, |s| s.parse::<NaiveDate>()))
+            .transpose()?,
+        created: parse(5, &row.get::<_, String>(5)?, parse_timestamp)?,
+        completed: row
+            .get::<_, Option<String>>(6)?
+            .map(|at| parse(6, &at, parse_timestamp))
+            .transpose()?,
+        tags: BTreeSet::new(),
+        parent: row.get::<_, Option<u64>>(7)?.map(TaskId),
+        recurrence: row
+            .get::<_, Option<String>>(8)?
+            .map(|rule| parse(8, &rule, Recurrence::parse))
+            .transpose()?,
+    })
+}
+
+/// Parses the text in column `index`, reporting a failure as SQLite does
+/// for values of the wrong type.
+fn parse<T, E>(
+    index: usize,
+    text: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> rusqlite::Result<T>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    parse(text)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err)))
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn parse_timestamp(text: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(text).map(|at| at.with_timezone(&Utc))
+}
@@ -0,0 +1,334 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{validate_tag, Task, TaskId, TaskStore, ToDoError, ToDoList, ValidationPolicy};
+
+/// Version of the on-disk document written by `save`.
+///
+/// Older versions are still accepted: version 1 had no task IDs, so its tasks
+/// are numbered in file order, and neither version 1 nor 2 had timestamps, so
+/// their tasks are treated as created at load time.
+/// Fields added since version 3, such as tags, parents and recurrence rules, are optional and
+/// left out when empty, so they did not need a new version.
+const FORMAT_VERSION: u32 = 3;
+
+/// Environment variable naming the list file when no path is given.
+const FILE_ENV: &str = "TODO_FILE";
+/// List file used when no path is given and `TODO_FILE` is not set.
+const DEFAULT_FILE: &str = "todo.json";
+
+/// The list file the front ends use when none is given: `$TODO_FILE`, or
+/// `todo.json` in the working directory.
+pub fn default_file() -> PathBuf {
+    std::env::var_os(FILE_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_FILE))
+}
+
+/// On-disk representation of a ToDoList.
+#[derive(Serialize)]
+pub(super) struct Document<'a> {
+    version: u32,
+    next_id: u64,
+    tasks: Vec<&'a Task>,
+}
+
+impl<'a> Document<'a> {
+    pub(super) fn new(next_id: u64, tasks: impl IntoIterator<Item = &'a Task>) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            next_id,
+            tasks: tasks.into_iter().collect(),
+        }
+    }
+}
+
+/// Only the header is read first, so that a document written by a newer
+/// version is reported as such instead of as a generic parse error.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct DocumentV1 {
+    tasks: Vec<TaskV1>,
+}
+
+#[derive(Deserialize)]
+struct TaskV1 {
+    description: String,
+    priority: u8,
+    done: bool,
+}
+
+#[derive(Deserialize)]
+struct DocumentV2 {
+    next_id: u64,
+    tasks: Vec<TaskV2>,
+}
+
+#[derive(Deserialize)]
+struct TaskV2 {
+    id: TaskId,
+    description: String,
+    priority: u8,
+    done: bool,
+}
+
+#[derive(Deserialize)]
+struct DocumentV3 {
+    next_id: u64,
+    tasks: Vec<Task>,
+}
+
+/// Errors that can occur while loading or saving a ToDoList.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The file is not a valid ToDoList document.
+    Parse(serde_json::Error),
+    /// The document was written with a format version we do not understand.
+    UnsupportedVersion(u32),
+    /// The task at `index` does not satisfy the rules enforced by `add_task`.
+    InvalidTask { index: usize, error: ToDoError },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(err) => write!(f, "I/O error: {}", err),
+            StorageError::Parse(err) => write!(f, "invalid document: {}", err),
+            StorageError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {}", v)
+            }
+            StorageError::InvalidTask { index, error } => {
+                write!(f, "task at position {} is invalid: {}", index, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Io(err) => Some(err),
+            StorageError::Parse(err) => Some(err),
+            StorageError::InvalidTask { error, .. } => Some(error),
+            StorageError::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Parse(err)
+    }
+}
+
+impl ToDoList {
+    /// Loads a list previously written by `save`.
+    ///
+    /// Every task is checked against the same rules as `add_task`, under the
+    /// default `ValidationPolicy`; the first one that breaks them is reported
+    /// by its position in the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        Self::load_with_policy(path, ValidationPolicy::default())
+    }
+
+    /// Loads a list as `load` does, but starts an empty one if the file does
+    /// not exist yet.
+    pub fn load_or_new<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        match Self::load(path) {
+            Err(StorageError::Io(err)) if err.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            result => result,
+        }
+    }
+
+    /// Loads a list as `load` does, but checks it against `policy`, which
+    /// the list then keeps for the tasks added to it.
+    pub fn load_with_policy<P: AsRef<Path>>(
+        path: P,
+        policy: ValidationPolicy,
+    ) -> Result<Self, StorageError> {
+        Self::from_value(serde_json::from_str(&fs::read_to_string(path)?)?, policy)
+    }
+
+    /// Reads a list from a document in the format written by `save`, with
+    /// the same checks as `load`.
+    pub fn from_json(text: &str) -> Result<Self, StorageError> {
+        Self::from_value(serde_json::from_str(text)?, ValidationPolicy::default())
+    }
+
+    /// Reads a list from a document already parsed as JSON, with the same
+    /// checks as `load_with_policy`.
+    pub(super) fn from_value(
+        value: serde_json::Value,
+        policy: ValidationPolicy,
+    ) -> Result<Self, StorageError> {
+        let header = Header::deserialize(&value)?;
+        let mut list = ToDoList::new().with_policy(policy);
+        let now = list.clock.now();
+        let (next_id, tasks) = match header.version {
+            1 => {
+                let tasks: Vec<Task> = DocumentV1::deserialize(value)?
+                    .tasks
+                    .into_iter()
+                    .zip(1..)
+                    .map(|(t, id)| upgrade(TaskId(id), t.description, t.priority, t.done, now))
+                    .collect();
+                (tasks.len() as u64 + 1, tasks)
+            }
+            2 => {
+                let document = DocumentV2::deserialize(value)?;
+                let tasks = document
+                    .tasks
+                    .into_iter()
+                    .map(|t| upgrade(t.id, t.description, t.priority, t.done, now))
+                    .collect();
+                (document.next_id, tasks)
+            }
+            3 => {
+                let document = DocumentV3::deserialize(value)?;
+                (document.next_id, document.tasks)
+            }
+            v => return Err(StorageError::UnsupportedVersion(v)),
+        };
+        list.fill(next_id, tasks)?;
+        // Memory stores cannot fail.
+        list.sync().expect("memory store failed");
+        Ok(list)
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Adds `tasks`, read from storage, to an empty list, checking them as
+    /// `load_with_policy` does.
+    pub(super) fn fill(&mut self, next_id: u64, tasks: Vec<Task>) -> Result<(), StorageError> {
+        self.next_id = next_id;
+        let links: Vec<(TaskId, Option<TaskId>)> = tasks.iter().map(|t| (t.id, t.parent)).collect();
+        for (index, task) in tasks.into_iter().enumerate() {
+            // A done task is no duplicate of anything, as in `add_task`.
+            let checked = if task.done {
+                self.policy.check(&task.description, task.priority)
+            } else {
+                self.validate(&task.description, task.priority, None)
+            };
+            checked
+                .and_then(|_| task.tags.iter().try_for_each(|tag| validate_tag(tag)))
+                .map_err(|error| StorageError::InvalidTask { index, error })?;
+            if self.get(task.id).is_some() {
+                let error = ToDoError::Duplicate(task.id);
+                return Err(StorageError::InvalidTask { index, error });
+            }
+            // Never hand out an ID that is already taken, even if the stored
+            // counter was edited by hand.
+            self.next_id = self.next_id.max(task.id.0 + 1);
+            self.insert_sorted(task);
+        }
+        // Parents can only be checked once every task is in.
+        for (index, (id, parent)) in links.into_iter().enumerate() {
+            check_links(self, id, parent)
+                .map_err(|error| StorageError::InvalidTask { index, error })?;
+        }
+        Ok(())
+    }
+
+    /// Saves the list to `path`.
+    ///
+    /// The document is first written to a temporary file next to `path` and
+    /// then renamed over it, so a crash mid-save leaves the old file intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StorageError> {
+        Ok(replace_file(path.as_ref(), self.to_json()?.as_bytes())?)
+    }
+
+    /// Writes the list as the document `save` stores.
+    pub fn to_json(&self) -> Result<String, StorageError> {
+        Ok(serde_json::to_string_pretty(&self.document())?)
+    }
+
+    /// Returns the document `save` stores, as JSON to embed in another one.
+    pub(super) fn to_value(&self) -> Result<serde_json::Value, StorageError> {
+        Ok(serde_json::to_value(self.document())?)
+    }
+
+    fn document(&self) -> Document<'_> {
+        Document::new(self.next_id, self.tasks.values())
+    }
+}
+
+/// Replaces the content of `path` with `bytes`.
+///
+/// The bytes are first written to a temporary file next to `path` and then
+/// renamed over it, so a crash midway leaves the old file intact.
+pub(super) fn replace_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = temp_path(path);
+    let result = write_synced(&tmp, bytes).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Checks that the parent of a loaded task exists, that the task is not its
+/// own ancestor, and that it is not done while it has open subtasks.
+fn check_links<S: TaskStore>(
+    list: &ToDoList<S>,
+    id: TaskId,
+    parent: Option<TaskId>,
+) -> Result<(), ToDoError> {
+    if let Some(parent) = parent {
+        list.get(parent).ok_or(ToDoError::NotFound(parent))?;
+        // A chain longer than the list has to go round in a circle.
+        if list.ancestors(id).take(list.tasks.len()).any(|a| a == id) {
+            return Err(ToDoError::Cycle { task: id, parent });
+        }
+    }
+    let done = list.get(id).is_some_and(|task| task.done);
+    if done && list.has_open_children(id) {
+        return Err(ToDoError::OpenSubtasks(id));
+    }
+    Ok(())
+}
+
+/// Builds a task read from a document that predates timestamps.
+fn upgrade(id: TaskId, description: String, priority: u8, done: bool, now: DateTime<Utc>) -> Task {
+    Task {
+        id,
+        description,
+        priority,
+        done,
+        due: None,
+        created: now,
+        completed: done.then_some(now),
+        tags: Default::default(),
+        parent: None,
+        recurrence: None,
+    }
+}
+
+/// Returns `<path>.tmp`, in the same directory so that the rename is atomic.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Writes `bytes` to `path` and flushes them to disk before returning.
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
@@ -0,0 +1,358 @@
+//! Where the tasks of a list are kept.
+//!
+//! A ToDoList works on its tasks in memory, sorted by priority, and writes
+//! what an operation changed through to its `TaskStore` when the operation
+//! ends, undo and redo included. Several programs can share a store by
+//! opening it in turn; a list does not see the changes others make to the
+//! store after it was opened.
+//!
+//! The list remembers, for every task changed since the last write, what
+//! the store holds of it, so a write only looks at those tasks and sends
+//! the ones whose state differs. If the store fails, an operation that
+//! returns a `Result` is undone and fails with `ToDoError::Store`. The
+//! others, imports, merges, undo and redo, keep their changes in the list;
+//! `store_error` tells why, and the next operation or `sync` sends them
+//! again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::storage::{self, Document};
+use super::StorageError;
+use super::{Filter, Task, TaskId, ToDoList, ValidationPolicy};
+
+/// A change to a store, as passed to `TaskStore::apply`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreChange {
+    /// Adds a task whose ID is not in the store yet.
+    Insert(Task),
+    /// Replaces the stored task with the same ID.
+    Update(Task),
+    /// Deletes the stored task with this ID.
+    Delete(TaskId),
+    /// Sets the ID the list gives to its next task.
+    NextId(u64),
+}
+
+/// Storage for the tasks of a ToDoList.
+///
+/// A store only keeps tasks; the rules of the list, such as its
+/// `ValidationPolicy`, are checked by the list before it writes.
+pub trait TaskStore: fmt::Debug {
+    /// Returns the stored tasks selected by `filter`, or all of them, by ID.
+    fn query(&self, filter: Option<&Filter>) -> Result<Vec<Task>, StoreError>;
+
+    /// Returns the ID the list gives to its next task. It is always greater
+    /// than the IDs of the stored tasks.
+    fn next_id(&self) -> Result<u64, StoreError>;
+
+    /// Applies `changes` in order, all of them or, if one fails, none.
+    ///
+    /// Inserting a task with an ID in use fails with `Duplicate`, updating
+    /// or deleting one that is not stored with `NotFound`.
+    fn apply(&mut self, changes: &[StoreChange]) -> Result<(), StoreError>;
+
+    fn insert(&mut self, task: &Task) -> Result<(), StoreError> {
+        self.apply(&[StoreChange::Insert(task.clone())])
+    }
+
+    fn update(&mut self, task: &Task) -> Result<(), StoreError> {
+        self.apply(&[StoreChange::Update(task.clone())])
+    }
+
+    fn delete(&mut self, id: TaskId) -> Result<(), StoreError> {
+        self.apply(&[StoreChange::Delete(id)])
+    }
+}
+
+/// Errors that can occur while reading or writing a store.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The file of a `FileStore` could not be read, written or understood.
+    Storage(StorageError),
+    /// The database of a `SqliteStore` failed.
+    Sqlite(rusqlite::Error),
+    /// The database was created by a newer version, with this schema version.
+    UnsupportedSchema(u32),
+    /// A task with this ID is already stored.
+    Duplicate(TaskId),
+    /// No task with this ID is stored.
+    NotFound(TaskId),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Storage(err) => write!(f, "{}", err),
+            StoreError::Sqlite(err) => write!(f, "database error: {}", err),
+            StoreError::UnsupportedSchema(v) => {
+                write!(f, "unsupported database schema version {}", v)
+            }
+            StoreError::Duplicate(id) => write!(f, "task {} is already stored", id),
+            StoreError::NotFound(id) => write!(f, "task {} is not stored", id),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Storage(err) => Some(err),
+            StoreError::Sqlite(err) => Some(err),
+            StoreError::UnsupportedSchema(_)
+            | StoreError::Duplicate(_)
+            | StoreError::NotFound(_) => None,
+        }
+    }
+}
+
+impl From<StorageError> for StoreError {
+    fn from(err: StorageError) -> Self {
+        StoreError::Storage(err)
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(err: io::Error) -> Self {
+        StoreError::Storage(StorageError::Io(err))
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Sqlite(err)
+    }
+}
+
+/// Keeps the tasks in memory only; the store of `ToDoList::new`.
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    tasks: BTreeMap<TaskId, Task>,
+    next_id: u64,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TaskStore for MemoryStore {
+    fn query(&self, filter: Option<&Filter>) -> Result<Vec<Task>, StoreError> {
+        Ok(select(self.tasks.values(), filter))
+    }
+
+    fn next_id(&self) -> Result<u64, StoreError> {
+        Ok(self.next_id)
+    }
+
+    fn apply(&mut self, changes: &[StoreChange]) -> Result<(), StoreError> {
+        check(changes, |id| self.tasks.contains_key(&id))?;
+        for change in changes {
+            apply_checked(&mut self.tasks, &mut self.next_id, change);
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the tasks in a JSON file, in the format of `ToDoList::save`.
+///
+/// Every write replaces the whole file, the same way `save` does, so the
+/// file is never left half written. The file is created by the first write.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    tasks: BTreeMap<TaskId, Task>,
+    next_id: u64,
+}
+
+impl FileStore {
+    /// Opens the store kept in `path`, which may not exist yet. Files
+    /// written by older versions of `save` are read as `load` reads them.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let (tasks, next_id) = match fs::read_to_string(&path) {
+            Ok(text) => {
+                let value = serde_json::from_str(&text).map_err(StorageError::Parse)?;
+                // The list that opens the store checks its own policy.
+                let list = ToDoList::from_value(value, ValidationPolicy::unrestricted())?;
+                let tasks = list.tasks.into_values().map(|t| (t.id, t)).collect();
+                (tasks, list.next_id)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (BTreeMap::new(), 1),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            tasks,
+            next_id,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TaskStore for FileStore {
+    fn query(&self, filter: Option<&Filter>) -> Result<Vec<Task>, StoreError> {
+        Ok(select(self.tasks.values(), filter))
+    }
+
+    fn next_id(&self) -> Result<u64, StoreError> {
+        Ok(self.next_id)
+    }
+
+    fn apply(&mut self, changes: &[StoreChange]) -> Result<(), StoreError> {
+        check(changes, |id| self.tasks.contains_key(&id))?;
+        let (mut tasks, mut next_id) = (self.tasks.clone(), self.next_id);
+        for change in changes {
+            apply_checked(&mut tasks, &mut next_id, change);
+        }
+        let json = serde_json::to_string_pretty(&Document::new(next_id, tasks.values()))
+            .map_err(StorageError::Parse)?;
+        storage::replace_file(&self.path, json.as_bytes())?;
+        (self.tasks, self.next_id) = (tasks, next_id);
+        Ok(())
+    }
+}
+
+fn select<'a>(tasks: impl Iterator<Item = &'a Task>, filter: Option<&Filter>) -> Vec<Task> {
+    tasks
+        .filter(|task| filter.is_none_or(|f| f.matches(task)))
+        .cloned()
+        .collect()
+}
+
+/// Checks that `changes` can all be applied to a store that holds the IDs
+/// for which `stored` is true.
+fn check(changes: &[StoreChange], stored: impl Fn(TaskId) -> bool) -> Result<(), StoreError> {
+    // Whether each ID touched so far is stored after the changes before.
+    let mut now: HashMap<TaskId, bool> = HashMap::new();
+    for change in changes {
+        let (id, present) = match change {
+            StoreChange::Insert(task) => (task.id, false),
+            StoreChange::Update(task) => (task.id, true),
+            StoreChange::Delete(id) => (*id, true),
+            StoreChange::NextId(_) => continue,
+        };
+        let is_stored = *now.entry(id).or_insert_with(|| stored(id));
+        match (present, is_stored) {
+            (false, true) => return Err(StoreError::Duplicate(id)),
+            (true, false) => return Err(StoreError::NotFound(id)),
+            _ => {}
+        }
+        now.insert(id, !matches!(change, StoreChange::Delete(_)));
+    }
+    Ok(())
+}
+
+/// Applies a change that `check` accepted.
+fn apply_checked(tasks: &mut BTreeMap<TaskId, Task>, next_id: &mut u64, change: &StoreChange) {
+    match change {
+        StoreChange::Insert(task) | StoreChange::Update(task) => {
+            *next_id = (*next_id).max(task.id.0 + 1);
+            tasks.insert(task.id, task.clone());
+        }
+        StoreChange::Delete(id) => {
+            tasks.remove(id);
+        }
+        StoreChange::NextId(id) => *next_id = *id,
+    }
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Opens the list kept in `store`, checked against the default
+    /// `ValidationPolicy` as `load` does. Tasks of the same priority come in
+    /// the order of their IDs.
+    pub fn open(store: S) -> Result<Self, StoreError> {
+        Self::open_with_policy(store, ValidationPolicy::default())
+    }
+
+    /// Opens the list kept in `store` as `open` does, but checks it against
+    /// `policy`, which the list then keeps for the tasks added to it.
+    pub fn open_with_policy(store: S, policy: ValidationPolicy) -> Result<Self, StoreError> {
+        let tasks = store.query(None)?;
+        let next_id = store.next_id()?;
+        let mut list = ToDoList::empty(store).with_policy(policy);
+        list.fill(next_id, tasks)?;
+        // What was just read is what the store holds.
+        list.unsaved.clear();
+        list.saved_next_id = next_id;
+        Ok(list)
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the store, after writing the changes not written yet.
+    pub fn into_store(mut self) -> Result<S, StoreError> {
+        self.sync()?;
+        Ok(self.store)
+    }
+
+    /// Whether the store holds every change made to the list.
+    pub fn is_synced(&self) -> bool {
+        self.unsaved.is_empty() && self.saved_next_id == self.next_id
+    }
+
+    /// Why the last write to the store failed, while the changes it was to
+    /// write are still waiting; see the module documentation.
+    pub fn store_error(&self) -> Option<&StoreError> {
+        self.store_error.as_ref()
+    }
+
+    /// Writes the changes the store does not hold yet, which only happens if
+    /// it failed before.
+    pub fn sync(&mut self) -> Result<(), StoreError> {
+        let changes = self.pending_changes();
+        if !changes.is_empty() {
+            self.store.apply(&changes)?;
+        }
+        self.unsaved.clear();
+        self.saved_next_id = self.next_id;
+        self.store_error = None;
+        Ok(())
+    }
+
+    /// Forgets the changes of an operation that was just undone, if nothing
+    /// else is waiting to be written.
+    pub(super) fn forget_reverted(&mut self) {
+        if self.pending_changes().is_empty() {
+            self.unsaved.clear();
+            self.store_error = None;
+        }
+    }
+
+    /// The changes that bring the store up to date with the list.
+    fn pending_changes(&self) -> Vec<StoreChange> {
+        let mut changes = Vec::new();
+        for (&id, saved) in &self.unsaved {
+            match (saved, self.get(id)) {
+                (None, Some(task)) => changes.push(StoreChange::Insert(task.clone())),
+                (Some(saved), Some(task)) if saved != task => {
+                    changes.push(StoreChange::Update(task.clone()))
+                }
+                (Some(_), None) => changes.push(StoreChange::Delete(id)),
+                _ => {}
+            }
+        }
+        if self.saved_next_id != self.next_id {
+            changes.push(StoreChange::NextId(self.next_id));
+        }
+        changes
+    }
+}
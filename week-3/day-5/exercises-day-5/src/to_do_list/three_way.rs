@@ -0,0 +1,450 @@
+//! Three-way merge of two copies of a list edited separately since a common
+//! ancestor, such as the same file changed on two machines.
+//!
+//! Tasks are matched by ID, so both copies must come from the ancestor. Each
+//! field of a task is merged on its own: a change made on one side only is
+//! taken, the same change made on both sides is taken once, and different
+//! changes to the same field are a conflict. Tags are merged as sets, so
+//! they never conflict. A task changed on one side and removed on the other
+//! is a conflict too. Conflicts are settled by a `MergeStrategy` and listed
+//! in the report.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::{Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+/// How `merge3` settles a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep our version of the task.
+    Ours,
+    /// Take their version of the task.
+    Theirs,
+    /// Keep our version, and add theirs as a new task next to it. A task
+    /// changed on one side and removed on the other is kept.
+    Both,
+}
+
+/// A field of a task that can change between versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Description,
+    Priority,
+    /// Whether the task is done, with its completion time.
+    Done,
+    Due,
+    Tags,
+    Parent,
+    Recurrence,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Description,
+        Field::Priority,
+        Field::Done,
+        Field::Due,
+        Field::Tags,
+        Field::Parent,
+        Field::Recurrence,
+    ];
+
+    fn same(self, a: &Task, b: &Task) -> bool {
+        match self {
+            Field::Description => a.description == b.description,
+            Field::Priority => a.priority == b.priority,
+            Field::Done => a.done == b.done,
+            Field::Due => a.due == b.due,
+            Field::Tags => a.tags == b.tags,
+            Field::Parent => a.parent == b.parent,
+            Field::Recurrence => a.recurrence == b.recurrence,
+        }
+    }
+
+    fn copy(self, to: &mut Task, from: &Task) {
+        match self {
+            Field::Description => to.description = from.description.clone(),
+            Field::Priority => to.priority = from.priority,
+            Field::Done => {
+                to.done = from.done;
+                to.completed = from.completed;
+            }
+            Field::Due => to.due = from.due,
+            Field::Tags => to.tags = from.tags.clone(),
+            Field::Parent => to.parent = from.parent,
+            Field::Recurrence => to.recurrence = from.recurrence.clone(),
+        }
+    }
+}
+
+/// A change one side made to the common ancestor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// ID of the task on that side.
+    pub id: TaskId,
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Completed,
+    Reopened,
+    Reprioritised {
+        from: u8,
+        to: u8,
+    },
+    /// Any other field changed.
+    Edited(Field),
+}
+
+/// Changes of both sides to the same task that could not be combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub id: TaskId,
+    pub kind: ConflictKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides changed these fields to different values.
+    Edited {
+        fields: Vec<Field>,
+        ours: Task,
+        theirs: Task,
+    },
+    /// We changed the task and they removed it.
+    EditedRemoved { ours: Task },
+    /// We removed the task and they changed it.
+    RemovedEdited { theirs: Task },
+}
+
+/// Outcome of `ToDoList::merge3`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ThreeWayReport {
+    /// What we changed since the ancestor.
+    pub ours: Vec<Change>,
+    /// What they changed since the ancestor.
+    pub theirs: Vec<Change>,
+    /// Conflicts found, each settled by the strategy.
+    pub conflicts: Vec<Conflict>,
+    /// IDs given here to tasks that came from their side as new tasks.
+    pub added: Vec<TaskId>,
+    /// Versions of their side that the policy of this list refused, by their
+    /// ID there, with the reason. Where the task is also here, our version
+    /// is kept.
+    pub rejected: Vec<(TaskId, ToDoError)>,
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Merges `theirs` into this list, given `base`, the version both were
+    /// edited from, as one undoable operation.
+    ///
+    /// Changes made only on their side are applied; changes made only here
+    /// are kept. Tasks they added get fresh IDs here, except that a task
+    /// added on both sides with the same description and priority is kept
+    /// once. Conflicts are settled by `strategy` and listed in the report.
+    /// A move under another task that would make a task its own ancestor is
+    /// not taken and is reported as a conflict on `Field::Parent`. Tasks the
+    /// merge would bring in or change are checked against the policy of this
+    /// list, and those refused are listed in the report. Afterwards the
+    /// ancestors of open tasks are reopened, so that done tasks still have no
+    /// open subtasks.
+    pub fn merge3(
+        &mut self,
+        base: &ToDoList<impl TaskStore>,
+        theirs: &ToDoList<impl TaskStore>,
+        strategy: MergeStrategy,
+    ) -> ThreeWayReport {
+        let ours: Vec<Task> = self.tasks.values().cloned().collect();
+        let base: Vec<Task> = base.tasks.values().cloned().collect();
+        let theirs: Vec<Task> = theirs.tasks.values().cloned().collect();
+        self.record("merge", |list| {
+            list.merge3_tasks(&base, &ours, &theirs, strategy)
+        })
+    }
+
+    fn merge3_tasks(
+        &mut self,
+        base: &[Task],
+        ours: &[Task],
+        theirs: &[Task],
+        strategy: MergeStrategy,
+    ) -> ThreeWayReport {
+        let by_id = |tasks: &'_ [Task]| -> HashMap<TaskId, Task> {
+            tasks.iter().map(|t| (t.id, t.clone())).collect()
+        };
+        let (base_map, ours_map, theirs_map) = (by_id(base), by_id(ours), by_id(theirs));
+        let mut report = ThreeWayReport {
+            ours: changes(base, ours),
+            theirs: changes(base, theirs),
+            ..Default::default()
+        };
+        // Tasks whose new parent is only set at the end, once every task is in.
+        let mut parents: Vec<(TaskId, Option<TaskId>)> = Vec::new();
+        let mut removals = Vec::new();
+
+        for old in base {
+            let edited = |side: &Task| Field::ALL.iter().any(|f| !f.same(old, side));
+            match (ours_map.get(&old.id), theirs_map.get(&old.id)) {
+                (Some(ours), Some(theirs)) => {
+                    let (mut merged, fields) = merge_fields(old, ours, theirs);
+                    if !fields.is_empty() {
+                        match strategy {
+                            MergeStrategy::Ours => {}
+                            MergeStrategy::Theirs => {
+                                fields.iter().for_each(|f| f.copy(&mut merged, theirs))
+                            }
+                            MergeStrategy::Both => match self.copy_of(theirs) {
+                                Ok(copy) => {
+                                    report.added.push(copy);
+                                    if theirs.parent.is_some() {
+                                        parents.push((copy, theirs.parent));
+                                    }
+                                }
+                                Err(err) => report.rejected.push((old.id, err)),
+                            },
+                        }
+                        report.conflicts.push(Conflict {
+                            id: old.id,
+                            kind: ConflictKind::Edited {
+                                fields,
+                                ours: ours.clone(),
+                                theirs: theirs.clone(),
+                            },
+                        });
+                    }
+                    let parent = merged.parent;
+                    merged.parent = ours.parent;
+                    if let Err(err) = self.put(merged) {
+                        report.rejected.push((old.id, err));
+                    } else if parent != ours.parent {
+                        parents.push((old.id, parent));
+                    }
+                }
+                (Some(ours), None) if edited(ours) => {
+                    report.conflicts.push(Conflict {
+                        id: old.id,
+                        kind: ConflictKind::EditedRemoved { ours: ours.clone() },
+                    });
+                    if strategy == MergeStrategy::Theirs {
+                        removals.push(old.id);
+                    }
+                }
+                (Some(_), None) => removals.push(old.id),
+                (None, Some(theirs)) if edited(theirs) => {
+                    report.conflicts.push(Conflict {
+                        id: old.id,
+                        kind: ConflictKind::RemovedEdited {
+                            theirs: theirs.clone(),
+                        },
+                    });
+                    if strategy != MergeStrategy::Ours {
+                        match self.validate(&theirs.description, theirs.priority, None) {
+                            Ok(()) => {
+                                // The ID is still free here, so the task keeps it.
+                                let mut task = theirs.clone();
+                                parents.push((task.id, task.parent.take()));
+                                self.insert_sorted(task);
+                            }
+                            Err(err) => report.rejected.push((old.id, err)),
+                        }
+                    }
+                }
+                (None, _) => {}
+            }
+        }
+
+        // Tasks they added, unless we added the same one.
+        let ours_added: Vec<&Task> = ours
+            .iter()
+            .filter(|t| !base_map.contains_key(&t.id))
+            .collect();
+        let mut new_ids = HashMap::new();
+        for task in theirs.iter().filter(|t| !base_map.contains_key(&t.id)) {
+            let twin = ours_added
+                .iter()
+                .find(|o| o.description == task.description && o.priority == task.priority);
+            let id = match twin {
+                Some(twin) => twin.id,
+                None => match self.copy_of(task) {
+                    Ok(id) => {
+                        report.added.push(id);
+                        if task.parent.is_some() {
+                            parents.push((id, task.parent));
+                        }
+                        id
+                    }
+                    Err(err) => {
+                        report.rejected.push((task.id, err));
+                        continue;
+                    }
+                },
+            };
+            new_ids.insert(task.id, id);
+        }
+
+        for id in removals {
+            if let Ok(pos) = self.position(id) {
+                self.lift_children(pos).expect("task is in the list");
+                self.remove_at(pos);
+            }
+        }
+
+        // Tasks they added but that were refused here hand their subtasks to
+        // their nearest ancestor that was taken.
+        let refused: HashSet<TaskId> = report
+            .rejected
+            .iter()
+            .map(|&(id, _)| id)
+            .filter(|id| !base_map.contains_key(id))
+            .collect();
+        let taken = |mut parent: Option<TaskId>| {
+            while let Some(p) = parent.filter(|p| refused.contains(p)) {
+                parent = theirs_map[&p].parent;
+            }
+            parent
+        };
+        for (id, parent) in parents {
+            // Their IDs for tasks they added stand for the new IDs here.
+            let parent = taken(parent).map(|p| new_ids.get(&p).copied().unwrap_or(p));
+            let parent = parent.filter(|&p| self.get(p).is_some());
+            let cycle = parent.is_some_and(|p| p == id || self.ancestors(p).any(|a| a == id));
+            if cycle {
+                if let (Some(ours), Some(theirs)) = (ours_map.get(&id), theirs_map.get(&id)) {
+                    report.conflicts.push(Conflict {
+                        id,
+                        kind: ConflictKind::Edited {
+                            fields: vec![Field::Parent],
+                            ours: ours.clone(),
+                            theirs: theirs.clone(),
+                        },
+                    });
+                }
+            } else {
+                self.modify(id, |task| task.parent = parent)
+                    .expect("task is in the list");
+            }
+        }
+
+        let open: Vec<TaskId> = self
+            .tasks
+            .values()
+            .filter(|t| !t.done)
+            .map(|t| t.id)
+            .collect();
+        for id in open {
+            self.reopen_ancestors(id).expect("task is in the list");
+        }
+        report
+    }
+
+    /// Adds a copy of `task` under a fresh ID, at the top level, and returns
+    /// the ID, if the policy allows it.
+    fn copy_of(&mut self, task: &Task) -> Result<TaskId, ToDoError> {
+        self.validate(&task.description, task.priority, None)?;
+        let id = self.allocate_id();
+        self.insert_sorted(Task {
+            id,
+            parent: None,
+            ..task.clone()
+        });
+        Ok(id)
+    }
+
+    /// Replaces the task with the same ID as `task`, moving it if its
+    /// priority changed. A changed task must follow the policy.
+    fn put(&mut self, task: Task) -> Result<(), ToDoError> {
+        let Ok(pos) = self.position(task.id) else {
+            return Ok(());
+        };
+        if self.tasks[&pos] == task {
+            return Ok(());
+        }
+        self.validate(&task.description, task.priority, Some(task.id))?;
+        if self.tasks[&pos].priority == task.priority {
+            self.replace_at(pos, task);
+        } else {
+            self.remove_at(pos);
+            self.insert_sorted(task);
+        }
+        Ok(())
+    }
+}
+
+/// Merges the fields of one task changed on both sides, starting from ours,
+/// and returns the fields that conflict; those keep our value.
+fn merge_fields(base: &Task, ours: &Task, theirs: &Task) -> (Task, Vec<Field>) {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+    for field in Field::ALL {
+        let ours_changed = !field.same(base, ours);
+        let theirs_changed = !field.same(base, theirs);
+        match (ours_changed, theirs_changed) {
+            (_, false) => {}
+            (false, true) => field.copy(&mut merged, theirs),
+            (true, true) if field.same(ours, theirs) => {}
+            (true, true) if field == Field::Tags => {
+                merged.tags = merge_tags(&base.tags, &ours.tags, &theirs.tags)
+            }
+            (true, true) => conflicts.push(field),
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Keeps the tags added on either side and drops those removed on either side.
+fn merge_tags(
+    base: &BTreeSet<String>,
+    ours: &BTreeSet<String>,
+    theirs: &BTreeSet<String>,
+) -> BTreeSet<String> {
+    let removed: HashSet<&String> = base
+        .iter()
+        .filter(|t| !ours.contains(*t) || !theirs.contains(*t))
+        .collect();
+    ours.union(theirs)
+        .filter(|t| !removed.contains(t))
+        .cloned()
+        .collect()
+}
+
+/// Lists what `side` changed since `base`, in the order of `side` and then,
+/// for removed tasks, of `base`.
+fn changes(base: &[Task], side: &[Task]) -> Vec<Change> {
+    let base_map: HashMap<TaskId, &Task> = base.iter().map(|t| (t.id, t)).collect();
+    let side_ids: HashSet<TaskId> = side.iter().map(|t| t.id).collect();
+    let mut changes = Vec::new();
+    for task in side {
+        let id = task.id;
+        let Some(old) = base_map.get(&id) else {
+            changes.push(Change {
+                id,
+                kind: ChangeKind::Added,
+            });
+            continue;
+        };
+        for field in Field::ALL.into_iter().filter(|f| !f.same(old, task)) {
+            let kind = match field {
+                Field::Done if task.done => ChangeKind::Completed,
+                Field::Done => ChangeKind::Reopened,
+                Field::Priority => ChangeKind::Reprioritised {
+                    from: old.priority,
+                    to: task.priority,
+                },
+                field => ChangeKind::Edited(field),
+            };
+            changes.push(Change { id, kind });
+        }
+    }
+    changes.extend(
+        base.iter()
+            .filter(|t| !side_ids.contains(&t.id))
+            .map(|t| Change {
+                id: t.id,
+                kind: ChangeKind::Removed,
+            }),
+    );
+    changes
+}
@@ -0,0 +1,286 @@
+//! Import and export in the todo.txt format (<https://github.com/todotxt/todo.txt>).
+//!
+//! | todo.txt                     | ToDoList                          |
+//! |------------------------------|-----------------------------------|
+//! | `x ` prefix                  | `done`                            |
+//! | `(A)`..`(Z)`, or `pri:A`     | priority 1..26                    |
+//! | no priority                  | priority 5                        |
+//! | completion / creation date   | `completed` / `created`, to the day |
+//! | `due:YYYY-MM-DD`             | due date                          |
+//! | `+project`, `@context`       | tags `+project`, `@context`       |
+//! | `tag:name`                   | tag `name`                        |
+//! | `id:key`, `parent:key`       | subtask of the task with that `id:` |
+//! | `rec:RULE`                   | recurrence, as in `Recurrence`    |
+//!
+//! The export writes the ID of a task with subtasks as its `id:`, and the
+//! ID of the parent of a subtask as its `parent:`. On import, `id:` keys are
+//! only used to link `parent:` keys, and tasks get fresh IDs as always.
+//!
+//! Everything else on the line is the description. Completed tasks keep
+//! their priority as `pri:A`, as most todo.txt tools do, so exporting and
+//! importing a list gives back the same tasks apart from their IDs and the
+//! time of day of their timestamps. The format has no escaping, so words of
+//! a description that todo.txt reads as fields (`+x`, `@x`, `due:...`) and
+//! runs of spaces cannot survive the round trip, and neither can priorities
+//! outside 1..26, which have no letter.
+//!
+//! Imported tasks are checked against the list's `ValidationPolicy`, so by
+//! default `(F)` and beyond are refused.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::{validate_tag, ImportReport, Recurrence, Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+/// Priority given to lines without one: the least urgent.
+const DEFAULT_PRIORITY: u8 = 5;
+
+/// Why a todo.txt line could not be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TodoTxtError {
+    /// A date is not a valid `YYYY-MM-DD` date.
+    InvalidDate(String),
+    /// A `rec:` rule is not a valid `Recurrence`.
+    InvalidRecurrence(String),
+    /// The task breaks one of the rules of the list.
+    Task(ToDoError),
+}
+
+impl fmt::Display for TodoTxtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TodoTxtError::InvalidDate(date) => write!(f, "invalid date '{}'", date),
+            TodoTxtError::InvalidRecurrence(rule) => write!(f, "invalid recurrence '{}'", rule),
+            TodoTxtError::Task(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TodoTxtError {}
+
+impl From<ToDoError> for TodoTxtError {
+    fn from(err: ToDoError) -> Self {
+        TodoTxtError::Task(err)
+    }
+}
+
+/// A todo.txt line broken into the fields the list understands.
+struct Line {
+    done: bool,
+    priority: u8,
+    completed: Option<NaiveDate>,
+    created: Option<NaiveDate>,
+    due: Option<NaiveDate>,
+    tags: Vec<String>,
+    /// The `id:` of the line, for subtasks to refer to.
+    key: Option<String>,
+    /// The `id:` of the parent.
+    parent: Option<String>,
+    recurrence: Option<Recurrence>,
+    description: String,
+}
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Adds every task of a todo.txt document to the list, as one undoable
+    /// operation. Blank lines are ignored; lines that cannot be imported are
+    /// skipped and reported with their line number.
+    pub fn import_todo_txt(&mut self, text: &str) -> ImportReport<TodoTxtError> {
+        self.record("import", |list| {
+            let mut report = ImportReport::default();
+            let mut keys = HashMap::new();
+            let mut links = Vec::new();
+            for (number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match list.import_line(line, &mut keys, &mut links) {
+                    Ok(id) => report.added.push(id),
+                    Err(err) => report.skipped.push((number, err)),
+                }
+            }
+            list.link_imported(links, &keys)
+                .expect("imported tasks are in the list");
+            report
+        })
+    }
+
+    /// Writes the list as a todo.txt document, one task per line in list order.
+    pub fn to_todo_txt(&self) -> String {
+        let parents: HashSet<TaskId> = self.tasks.values().filter_map(|t| t.parent).collect();
+        self.tasks
+            .values()
+            .map(|task| format_line(task, parents.contains(&task.id)) + "\n")
+            .collect()
+    }
+
+    /// Imports one line, noting its `id:` in `keys` and its `parent:` in
+    /// `links`.
+    fn import_line(
+        &mut self,
+        text: &str,
+        keys: &mut HashMap<String, TaskId>,
+        links: &mut Vec<(TaskId, String)>,
+    ) -> Result<TaskId, TodoTxtError> {
+        let line = parse_line(text)?;
+        for tag in &line.tags {
+            validate_tag(tag)?;
+        }
+        let id = if line.done {
+            self.add_done(&line.description, line.priority, line.due)?
+        } else {
+            self.add(&line.description, line.priority, line.due)?
+        };
+        self.modify(id, |task| {
+            task.tags = line.tags.into_iter().collect();
+            if let Some(created) = line.created {
+                task.created = start_of(created);
+            }
+            if line.done {
+                task.completed = line.completed.map(start_of).or(Some(task.created));
+            }
+            task.recurrence = line.recurrence;
+        })?;
+        keys.extend(line.key.map(|key| (key, id)));
+        links.extend(line.parent.map(|parent| (id, parent)));
+        Ok(id)
+    }
+}
+
+fn parse_line(text: &str) -> Result<Line, TodoTxtError> {
+    let mut words = text.split_whitespace().peekable();
+    let mut line = Line {
+        done: false,
+        priority: DEFAULT_PRIORITY,
+        completed: None,
+        created: None,
+        due: None,
+        tags: Vec::new(),
+        key: None,
+        parent: None,
+        recurrence: None,
+        description: String::new(),
+    };
+
+    if words.next_if_eq(&"x").is_some() {
+        line.done = true;
+        line.completed = words.next_if(is_date).map(parse_date).transpose()?;
+    } else if let Some(letter) = words.peek().and_then(|w| priority_letter(w)) {
+        words.next();
+        line.priority = priority_from_letter(letter);
+    }
+    line.created = words.next_if(is_date).map(parse_date).transpose()?;
+
+    let mut description = Vec::new();
+    for word in words {
+        if word.len() > 1 && (word.starts_with('+') || word.starts_with('@')) {
+            line.tags.push(word.to_string());
+        } else if let Some(tag) = word.strip_prefix("tag:").filter(|t| !t.is_empty()) {
+            line.tags.push(tag.to_string());
+        } else if let Some(date) = word.strip_prefix("due:") {
+            line.due = Some(parse_date(date)?);
+        } else if let Some(letter) = word.strip_prefix("pri:").and_then(single_uppercase) {
+            line.priority = priority_from_letter(letter);
+        } else if let Some(key) = word.strip_prefix("id:").filter(|k| !k.is_empty()) {
+            line.key = Some(key.to_string());
+        } else if let Some(key) = word.strip_prefix("parent:").filter(|k| !k.is_empty()) {
+            line.parent = Some(key.to_string());
+        } else if let Some(rule) = word.strip_prefix("rec:").filter(|r| !r.is_empty()) {
+            let rule = Recurrence::parse(rule)
+                .map_err(|_| TodoTxtError::InvalidRecurrence(rule.to_string()))?;
+            line.recurrence = Some(rule);
+        } else {
+            description.push(word);
+        }
+    }
+    line.description = description.join(" ");
+    Ok(line)
+}
+
+/// Writes one task; `has_children` asks for its `id:`.
+fn format_line(task: &Task, has_children: bool) -> String {
+    let mut words = Vec::new();
+    let letter = priority_to_letter(task.priority);
+    if task.done {
+        words.push("x".to_string());
+        let completed = task.completed.unwrap_or(task.created);
+        words.push(completed.date_naive().to_string());
+    } else if let Some(letter) = letter {
+        words.push(format!("({})", letter));
+    }
+    words.push(task.created.date_naive().to_string());
+    if !task.description.is_empty() {
+        words.push(task.description.clone());
+    }
+    for tag in &task.tags {
+        if tag.starts_with('+') || tag.starts_with('@') {
+            words.push(tag.clone());
+        } else {
+            words.push(format!("tag:{}", tag));
+        }
+    }
+    if let Some(due) = task.due {
+        words.push(format!("due:{}", due));
+    }
+    if let (true, Some(letter)) = (task.done, letter) {
+        words.push(format!("pri:{}", letter));
+    }
+    if has_children {
+        words.push(format!("id:{}", task.id.0));
+    }
+    if let Some(parent) = task.parent {
+        words.push(format!("parent:{}", parent.0));
+    }
+    if let Some(rule) = &task.recurrence {
+        words.push(format!("rec:{}", rule));
+    }
+    words.join(" ")
+}
+
+/// Returns the letter of a `(X)` priority marker.
+fn priority_letter(word: &str) -> Option<char> {
+    word.strip_prefix('(')
+        .and_then(|w| w.strip_suffix(')'))
+        .and_then(single_uppercase)
+}
+
+fn single_uppercase(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+        _ => None,
+    }
+}
+
+/// Maps `A`..`Z` onto priorities 1..26.
+fn priority_from_letter(letter: char) -> u8 {
+    letter as u8 - b'A' + 1
+}
+
+fn priority_to_letter(priority: u8) -> Option<char> {
+    (1..=26)
+        .contains(&priority)
+        .then(|| char::from(b'A' + priority - 1))
+}
+
+/// Whether `word` looks like a date; it may still be an invalid one.
+fn is_date(word: &&str) -> bool {
+    word.len() == 10
+        && word.bytes().enumerate().all(|(i, b)| {
+            if i == 4 || i == 7 {
+                b == b'-'
+            } else {
+                b.is_ascii_digit()
+            }
+        })
+}
+
+fn parse_date(word: &str) -> Result<NaiveDate, TodoTxtError> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d")
+        .map_err(|_| TodoTxtError::InvalidDate(word.to_string()))
+}
+
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
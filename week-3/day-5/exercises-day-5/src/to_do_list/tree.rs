@@ -0,0 +1,219 @@
+//! Subtasks.
+//!
+//! A task may have a parent, so the list forms a forest of any depth. The
+//! tasks themselves stay sorted by priority; the tree is only followed when
+//! it matters, for example by `print`.
+//!
+//! The list keeps one rule: a done task never has open descendants. So a
+//! parent counts as done only when all its children are, `mark_done_by_id`
+//! refuses a task with open subtasks, `complete_tree` completes a task
+//! together with its subtasks, and adding an open subtask under a done task
+//! reopens it.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{Filter, Slot, Task, TaskId, TaskStore, ToDoError, ToDoList};
+
+impl<S: TaskStore> ToDoList<S> {
+    /// Adds a new task as the last child of `parent`, with the same rules as
+    /// `add_task`. Done ancestors of the new task are reopened.
+    pub fn add_subtask(
+        &mut self,
+        parent: TaskId,
+        description: &str,
+        priority: u8,
+    ) -> Result<TaskId, ToDoError> {
+        self.position(parent)?;
+        self.transaction("add subtask", |list| {
+            let id = list.add(description, priority, None)?;
+            list.modify(id, |task| task.parent = Some(parent))?;
+            list.reopen_ancestors(id)?;
+            Ok(id)
+        })
+    }
+
+    /// Moves the task with the given ID, with its subtasks, under
+    /// `new_parent`, or makes it a top-level task when `new_parent` is `None`.
+    ///
+    /// A task cannot be moved under itself or one of its own subtasks.
+    pub fn reparent(&mut self, id: TaskId, new_parent: Option<TaskId>) -> Result<(), ToDoError> {
+        self.position(id)?;
+        if let Some(parent) = new_parent {
+            self.position(parent)?;
+            if parent == id || self.ancestors(parent).any(|a| a == id) {
+                return Err(ToDoError::Cycle { task: id, parent });
+            }
+        }
+        self.transaction("reparent", |list| {
+            list.modify(id, |task| task.parent = new_parent)?;
+            if list.tasks[&list.position(id)?].done {
+                Ok(())
+            } else {
+                list.reopen_ancestors(id)
+            }
+        })
+    }
+
+    /// Marks the task with the given ID and all its subtasks as done.
+    pub fn complete_tree(&mut self, id: TaskId) -> Result<(), ToDoError> {
+        let mut ids = self.descendants(id)?;
+        // Children first, so that no parent is completed before its children.
+        ids.reverse();
+        ids.push(id);
+        self.transaction("complete tree", |list| list.complete(&ids).map(|_| ()))
+    }
+
+    /// Removes the task with the given ID and all its subtasks, and returns
+    /// them, the task first.
+    pub fn remove_tree(&mut self, id: TaskId) -> Result<Vec<Task>, ToDoError> {
+        let mut ids = self.descendants(id)?;
+        ids.insert(0, id);
+        self.transaction("remove tree", |list| {
+            ids.iter()
+                .map(|&id| {
+                    let pos = list.position(id)?;
+                    Ok(list.remove_at(pos))
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the parent of the task with the given ID, if it has one.
+    pub fn parent(&self, id: TaskId) -> Option<TaskId> {
+        self.get(id).and_then(|task| task.parent)
+    }
+
+    /// Returns the direct subtasks of the task with the given ID, in list order.
+    pub fn children(&self, id: TaskId) -> impl Iterator<Item = &Task> {
+        let slots = self.children.get(&id).into_iter().flatten();
+        slots.map(|slot| &self.tasks[slot])
+    }
+
+    /// Returns the IDs of all subtasks of `id`, at any depth, parents before
+    /// their children.
+    pub fn descendants(&self, id: TaskId) -> Result<Vec<TaskId>, ToDoError> {
+        self.position(id)?;
+        let mut found = Vec::new();
+        let mut next = 0;
+        let mut current = id;
+        loop {
+            found.extend(self.children(current).map(|t| t.id));
+            match found.get(next) {
+                Some(&child) => current = child,
+                None => return Ok(found),
+            }
+            next += 1;
+        }
+    }
+
+    /// Returns the tasks in tree order with their depth: each top-level task
+    /// in list order, followed by its subtasks, recursively.
+    pub fn tree(&self) -> Vec<(usize, &Task)> {
+        self.tree_of(self.tasks.values().collect())
+    }
+
+    /// Returns `tasks`, all the tasks of the list, in tree order, with the
+    /// top-level tasks and the children of every task in the order of `tasks`.
+    pub(super) fn tree_of<'a>(&'a self, tasks: Vec<&'a Task>) -> Vec<(usize, &'a Task)> {
+        let mut children: HashMap<Option<TaskId>, Vec<&Task>> = HashMap::new();
+        for task in tasks {
+            children.entry(task.parent).or_default().push(task);
+        }
+        let mut out = Vec::with_capacity(self.tasks.len());
+        let top = children.remove(&None).unwrap_or_default();
+        let mut stack: Vec<(usize, &Task)> = top.into_iter().rev().map(|t| (0, t)).collect();
+        while let Some((depth, task)) = stack.pop() {
+            out.push((depth, task));
+            let below = children.remove(&Some(task.id)).unwrap_or_default();
+            stack.extend(below.into_iter().rev().map(|t| (depth + 1, t)));
+        }
+        out
+    }
+
+    /// Returns the tasks selected by `filter` in tree order, each with its
+    /// depth among the selected tasks: a task sits under its nearest
+    /// ancestor that is selected too.
+    pub fn tree_where(&self, filter: &Filter) -> Vec<(usize, &Task)> {
+        self.select(self.tree(), filter)
+    }
+
+    /// Keeps the tasks of `tree` selected by `filter`, with their depth among
+    /// the selected tasks.
+    pub(super) fn select<'a>(
+        &'a self,
+        tree: Vec<(usize, &'a Task)>,
+        filter: &Filter,
+    ) -> Vec<(usize, &'a Task)> {
+        tree.into_iter()
+            .filter(|(_, task)| filter.matches(task))
+            .map(|(_, task)| {
+                let depth = self
+                    .ancestors(task.id)
+                    .filter_map(|id| self.get(id))
+                    .filter(|ancestor| filter.matches(ancestor))
+                    .count();
+                (depth, task)
+            })
+            .collect()
+    }
+
+    /// Returns the ancestors of `id`, nearest first.
+    pub(super) fn ancestors(&self, id: TaskId) -> impl Iterator<Item = TaskId> + '_ {
+        std::iter::successors(self.parent(id), move |&p| self.parent(p))
+    }
+
+    pub(super) fn has_open_children(&self, id: TaskId) -> bool {
+        self.children(id).any(|t| !t.done)
+    }
+
+    /// Reopens every done ancestor of `id`, to keep the rule that a done task
+    /// has no open descendants.
+    pub(super) fn reopen_ancestors(&mut self, id: TaskId) -> Result<(), ToDoError> {
+        let ancestors: Vec<TaskId> = self.ancestors(id).collect();
+        for ancestor in ancestors {
+            self.modify(ancestor, |task| {
+                task.done = false;
+                task.completed = None;
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Puts imported tasks under their parents, once every task of the
+    /// document is in. `links` pairs a task with the key of its parent in the
+    /// document, and `keys` maps keys to the tasks imported under them. Links
+    /// to unknown keys, or that would make a task its own ancestor, are left
+    /// out, and done parents of open tasks are reopened.
+    pub(super) fn link_imported<K: Eq + Hash>(
+        &mut self,
+        links: Vec<(TaskId, K)>,
+        keys: &HashMap<K, TaskId>,
+    ) -> Result<(), ToDoError> {
+        for (id, key) in &links {
+            let Some(&parent) = keys.get(key) else {
+                continue;
+            };
+            if parent != *id && self.ancestors(parent).all(|a| a != *id) {
+                self.modify(*id, |task| task.parent = Some(parent))?;
+            }
+        }
+        for (id, _) in links {
+            if self.get(id).is_some_and(|task| !task.done) {
+                self.reopen_ancestors(id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the children of the task in `slot` to its parent, before it is
+    /// removed.
+    pub(super) fn lift_children(&mut self, slot: Slot) -> Result<(), ToDoError> {
+        let (id, parent) = (self.tasks[&slot].id, self.tasks[&slot].parent);
+        let children: Vec<TaskId> = self.children(id).map(|t| t.id).collect();
+        for child in children {
+            self.modify(child, |task| task.parent = parent)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+//! State and drawing of the full-screen terminal UI.
+//!
+//! Nothing here touches the terminal: `App` takes keys and `render` returns
+//! the text of every screen line, so the whole UI can be driven and checked
+//! without a terminal. The `todo-tui` binary does the terminal I/O.
+//!
+//! | Key                 | Action                                  |
+//! |---------------------|-----------------------------------------|
+//! | `↑`/`k`, `↓`/`j`    | move the selection                      |
+//! | `PgUp`, `PgDn`      | move one screen                         |
+//! | `Home`/`g`, `End`/`G` | first or last task                    |
+//! | `Space`/`x`         | toggle done                             |
+//! | `e`                 | edit the description in place           |
+//! | `p`                 | edit the priority in place              |
+//! | `/`                 | edit the filter; an empty one shows all |
+//! | `u`, `r`            | undo, redo                              |
+//! | `q`/`Esc`/`Ctrl+C`  | quit                                    |
+//!
+//! While editing, `Enter` applies the change and `Esc` or `Ctrl+C` cancels
+//! it.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::to_do_list::{Filter, Task, TaskId, ToDoList};
+
+/// Keys the UI reacts to, independent of the terminal library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Char(char),
+}
+
+/// What the keyboard is currently editing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Description(String),
+    Priority(String),
+    Filter(String),
+}
+
+/// The text of a full screen, ready to be drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    /// One entry per screen line, each exactly as wide as the screen.
+    pub lines: Vec<String>,
+    /// Line of the selected task, to be drawn highlighted.
+    pub highlight: Option<usize>,
+    /// Where the text cursor goes while editing, as (column, line).
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// The terminal UI over one ToDoList.
+#[derive(Debug)]
+pub struct App {
+    list: ToDoList,
+    filter: Option<Filter>,
+    filter_text: String,
+    /// Index of the selected task among the shown ones.
+    selected: usize,
+    /// Index of the first shown task on screen.
+    scroll: usize,
+    mode: Mode,
+    message: Option<String>,
+    width: usize,
+    height: usize,
+    changed: bool,
+    quit: bool,
+}
+
+/// Lines above and below the task rows: title, then input bar and status line.
+const CHROME_LINES: usize = 3;
+
+impl App {
+    pub fn new(list: ToDoList) -> Self {
+        Self {
+            list,
+            filter: None,
+            filter_text: String::new(),
+            selected: 0,
+            scroll: 0,
+            mode: Mode::Browse,
+            message: None,
+            width: 80,
+            height: 24,
+            changed: false,
+            quit: false,
+        }
+    }
+
+    /// Sets the size of the screen, in columns and lines.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.keep_selection_visible();
+    }
+
+    pub fn list(&self) -> &ToDoList {
+        &self.list
+    }
+
+    pub fn into_list(self) -> ToDoList {
+        self.list
+    }
+
+    /// Whether the list was changed since the UI started.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Applies one key press.
+    pub fn handle_key(&mut self, key: Key) {
+        self.message = None;
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+        self.mode = match mode {
+            Mode::Browse => {
+                self.browse(key);
+                return;
+            }
+            Mode::Description(text) => self.edit(key, text, Mode::Description),
+            Mode::Priority(text) => self.edit(key, text, Mode::Priority),
+            Mode::Filter(text) => self.edit(key, text, Mode::Filter),
+        };
+    }
+
+    fn browse(&mut self, key: Key) {
+        let rows = self.rows().len();
+        let page = self.task_lines().max(1);
+        match key {
+            Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.selected += 1,
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected += page,
+            Key::Home | Key::Char('g') => self.selected = 0,
+            Key::End | Key::Char('G') => self.selected = rows.saturating_sub(1),
+            Key::Char(' ') | Key::Char('x') => self.toggle_done(),
+            Key::Char('e') => {
+                if let Some(task) = self.selected_task() {
+                    self.mode = Mode::Description(task.description().to_string());
+                }
+            }
+            Key::Char('p') if self.selected_task().is_some() => {
+                self.mode = Mode::Priority(String::new());
+            }
+            Key::Char('/') => self.mode = Mode::Filter(self.filter_text.clone()),
+            Key::Char('u') => self.history(ToDoList::undo, "undo"),
+            Key::Char('r') => self.history(ToDoList::redo, "redo"),
+            Key::Char('q') | Key::Esc => self.quit = true,
+            _ => {}
+        }
+        self.keep_selection_visible();
+    }
+
+    /// Handles a key in one of the editing modes and returns the next mode.
+    fn edit(&mut self, key: Key, mut text: String, mode: fn(String) -> Mode) -> Mode {
+        match key {
+            Key::Char(c) => text.push(c),
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Esc => return Mode::Browse,
+            Key::Enter => {
+                let editing = mode(text);
+                return match self.apply(&editing) {
+                    Ok(()) => Mode::Browse,
+                    Err(message) => {
+                        self.message = Some(message);
+                        editing
+                    }
+                };
+            }
+            _ => {}
+        }
+        mode(text)
+    }
+
+    /// Applies the edit in progress.
+    fn apply(&mut self, editing: &Mode) -> Result<(), String> {
+        let id = self.selected_task().map(Task::id);
+        match (editing, id) {
+            (Mode::Description(text), Some(id)) => self
+                .list
+                .edit_description(id, text)
+                .map_err(|err| err.to_string())?,
+            (Mode::Priority(text), Some(id)) => {
+                let priority = text
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a priority", text))?;
+                self.list
+                    .set_priority(id, priority)
+                    .map_err(|err| err.to_string())?;
+            }
+            (Mode::Filter(text), _) => {
+                self.filter = if text.trim().is_empty() {
+                    None
+                } else {
+                    Some(Filter::parse(text).map_err(|err| format!("filter {}", err))?)
+                };
+                self.filter_text = text.trim().to_string();
+                self.selected = 0;
+                self.keep_selection_visible();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        self.changed = true;
+        if let Some(id) = id {
+            self.select(id);
+        }
+        Ok(())
+    }
+
+    fn toggle_done(&mut self) {
+        let Some(task) = self.selected_task() else {
+            return;
+        };
+        let (id, done) = (task.id(), task.is_done());
+        let result = if done {
+            self.list.reopen(id)
+        } else {
+            self.list.mark_done_by_id(id).map(|_| ())
+        };
+        match result {
+            Ok(()) => {
+                self.changed = true;
+                self.select(id);
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    fn history(&mut self, op: fn(&mut ToDoList) -> Option<&'static str>, verb: &str) {
+        self.message = Some(match op(&mut self.list) {
+            Some(name) => {
+                self.changed = true;
+                format!("{}: {}", verb, name)
+            }
+            None => format!("nothing to {}", verb),
+        });
+    }
+
+    /// Tasks shown on screen, in tree order with their depth.
+    fn rows(&self) -> Vec<(usize, &Task)> {
+        match &self.filter {
+            Some(filter) => self.list.tree_where(filter),
+            None => self.list.tree(),
+        }
+    }
+
+    fn selected_task(&self) -> Option<&Task> {
+        self.rows().get(self.selected).map(|&(_, task)| task)
+    }
+
+    /// Moves the selection to the task with the given ID, if it is shown.
+    fn select(&mut self, id: TaskId) {
+        if let Some(index) = self.rows().iter().position(|(_, t)| t.id() == id) {
+            self.selected = index;
+        }
+        self.keep_selection_visible();
+    }
+
+    fn task_lines(&self) -> usize {
+        self.height.saturating_sub(CHROME_LINES)
+    }
+
+    fn keep_selection_visible(&mut self) {
+        let rows = self.rows().len();
+        self.selected = self.selected.min(rows.saturating_sub(1));
+        let lines = self.task_lines().max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + lines {
+            self.scroll = self.selected + 1 - lines;
+        }
+        self.scroll = self.scroll.min(rows.saturating_sub(lines));
+    }
+
+    /// Draws the whole screen.
+    pub fn render(&self) -> Screen {
+        let rows = self.rows();
+        let total = self.list.iter().count();
+        let mut lines = Vec::with_capacity(self.height);
+        let mut highlight = None;
+        let mut cursor = None;
+
+        let title = match &self.filter {
+            Some(_) => format!(
+                "ToDoList - {} of {} tasks - filter: {}",
+                rows.len(),
+                total,
+                self.filter_text
+            ),
+            None => format!("ToDoList - {} tasks", total),
+        };
+        lines.push(title);
+
+        for (index, &(depth, task)) in rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(self.task_lines())
+        {
+            let selected = index == self.selected;
+            let marker = if task.is_done() { "[X]" } else { "[ ]" };
+            let prefix = format!(
+                "{}{}{} ",
+                if selected { "> " } else { "  " },
+                "  ".repeat(depth),
+                marker
+            );
+            let (priority, description) = match &self.mode {
+                Mode::Priority(text) if selected => (text.clone(), task.description().to_string()),
+                Mode::Description(text) if selected => (task.priority().to_string(), text.clone()),
+                _ => (task.priority().to_string(), task.description().to_string()),
+            };
+            let mut line = format!("{}{} - {}", prefix, priority, description);
+            if selected {
+                highlight = Some(lines.len());
+                cursor = match self.mode {
+                    Mode::Priority(_) => Some(prefix.width() + priority.width()),
+                    Mode::Description(_) => Some(line.width()),
+                    _ => None,
+                }
+                .map(|column| (column, lines.len()));
+            }
+            if let Some(due) = task.due() {
+                line.push_str(&format!("  due {}", due));
+            }
+            for tag in task.tags() {
+                line.push_str(&format!("  #{}", tag));
+            }
+            lines.push(line);
+        }
+        if rows.is_empty() {
+            lines.push("  (no tasks)".to_string());
+        }
+        while lines.len() < self.height.saturating_sub(2) {
+            lines.push(String::new());
+        }
+
+        let bar = match (&self.mode, &self.message) {
+            (Mode::Filter(text), message) => {
+                let bar = format!("Filter: {}", text);
+                cursor = Some((bar.width(), lines.len()));
+                match message {
+                    Some(message) => format!("{}  ({})", bar, message),
+                    None => bar,
+                }
+            }
+            (_, Some(message)) => message.clone(),
+            (Mode::Description(_) | Mode::Priority(_), None) => {
+                "Enter: save  Esc: cancel".to_string()
+            }
+            (Mode::Browse, None) => {
+                "j/k: move  space: done  e: edit  p: priority  /: filter  u/r: undo/redo  q: quit"
+                    .to_string()
+            }
+        };
+        lines.push(bar);
+        lines.push(self.status());
+
+        lines.truncate(self.height);
+        let lines = lines.into_iter().map(|l| fit(&l, self.width)).collect();
+        Screen {
+            lines,
+            highlight,
+            cursor: cursor.filter(|&(column, line)| column < self.width && line < self.height),
+        }
+    }
+
+    /// Number of tasks per priority allowed by the list's policy, and how
+    /// many are done.
+    fn status(&self) -> String {
+        let priorities = self.list.policy().priorities();
+        let first = usize::from(*priorities.start());
+        let mut counts = vec![0; priorities.len()];
+        let mut done = 0;
+        for task in self.list.iter() {
+            let index = usize::from(task.priority()).checked_sub(first);
+            if let Some(count) = index.and_then(|i| counts.get_mut(i)) {
+                *count += 1;
+            }
+            done += usize::from(task.is_done());
+        }
+        let per_priority: Vec<String> = counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| format!("P{}: {}", first + i, count))
+            .collect();
+        format!(
+            "{} | done {}/{}",
+            per_priority.join("  "),
+            done,
+            self.list.iter().count()
+        )
+    }
+}
+
+/// Cuts or pads `line` to exactly `width` terminal columns.
+///
+/// Lines are cut between grapheme clusters, so an accent stays on its letter;
+/// a wide character that would straddle the edge is left out and its column
+/// padded instead.
+fn fit(line: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for grapheme in line.graphemes(true) {
+        let columns = grapheme.width();
+        if used + columns > width {
+            break;
+        }
+        out.push_str(grapheme);
+        used += columns;
+    }
+    out.extend(std::iter::repeat_n(' ', width - used));
+    out
+}
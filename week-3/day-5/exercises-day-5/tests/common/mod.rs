@@ -0,0 +1,11 @@
+//! Helpers shared by the integration tests.
+
+/// Steps the xorshift generator in `seed`, which must not be 0, and returns
+/// a number below `n`, for the property tests.
+#[allow(dead_code)]
+pub fn xorshift(seed: &mut u64, n: usize) -> usize {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    (*seed % n as u64) as usize
+}
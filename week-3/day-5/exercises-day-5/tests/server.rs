@@ -0,0 +1,229 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use exercises_day_5::server::Server;
+use exercises_day_5::to_do_list::ToDoList;
+use serde_json::Value;
+
+/// Starts a server on a free port for the list in `file`.
+fn start(file: &Path) -> SocketAddr {
+    let server = Server::bind(0, file).unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    addr
+}
+
+/// A list file in the temporary directory, removed if it is left over.
+fn temp_file(name: &str) -> PathBuf {
+    let file = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_file(&file);
+    file
+}
+
+/// Sends one request as JSON and returns the status code and the JSON body.
+fn http(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json",
+        method, path, addr
+    );
+    raw_http(addr, &head, body)
+}
+
+/// Sends the request line and headers in `head`, then `body`.
+fn raw_http(addr: SocketAddr, head: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{}\r\nContent-Length: {}\r\n\r\n{}",
+        head,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn binds_to_loopback_only() {
+    let file = temp_file("todo_api_test_bind.json");
+    let server = Server::bind(0, &file).unwrap();
+    let addr = server.local_addr().unwrap();
+    assert!(addr.ip().is_loopback() && addr.port() != 0);
+}
+
+#[test]
+fn every_endpoint_round_trips() {
+    let file = temp_file("todo_api_test_endpoints.json");
+    let addr = start(&file);
+
+    let (status, task) = http(
+        addr,
+        "POST",
+        "/tasks",
+        r#"{"description":"Ship API","priority":2}"#,
+    );
+    assert!(status == 201 && task["description"] == "Ship API" && task["done"] == false);
+    let id = task["id"].as_u64().unwrap();
+    let (status, _) = http(
+        addr,
+        "POST",
+        "/tasks",
+        r#"{"description":"Urgent","priority":1}"#,
+    );
+    assert_eq!(status, 201);
+    let (status, err) = http(
+        addr,
+        "POST",
+        "/tasks",
+        r#"{"description":"Bad","priority":9}"#,
+    );
+    assert!(status == 422 && err["error"].as_str().unwrap().contains("priority 9"));
+    let (status, _) = http(addr, "POST", "/tasks", "{not json");
+    assert_eq!(status, 400);
+
+    let (status, tasks) = http(addr, "GET", "/tasks", "");
+    assert!(status == 200 && tasks.as_array().unwrap().len() == 2);
+    assert_eq!(tasks[0]["description"], "Urgent");
+
+    let (status, task) = http(addr, "PATCH", &format!("/tasks/{}", id), r#"{"done":true}"#);
+    assert!(status == 200 && task["done"] == true);
+    let (status, _) = http(addr, "PATCH", "/tasks/999", r#"{"done":true}"#);
+    assert_eq!(status, 404);
+    let (status, task) = http(
+        addr,
+        "PATCH",
+        &format!("/tasks/{}", id),
+        r#"{"done":false}"#,
+    );
+    assert!(status == 200 && task["done"] == false);
+    let (status, _) = http(addr, "PATCH", &format!("/tasks/{}", id), r#"{"done":true}"#);
+    assert_eq!(status, 200);
+    let (status, open) = http(
+        addr,
+        "GET",
+        "/tasks?filter=not+done%20and+priority%3C%3D2",
+        "",
+    );
+    assert!(status == 200 && open.as_array().unwrap().len() == 1);
+    let (status, _) = http(addr, "GET", "/tasks?filter=priority%3C", "");
+    assert_eq!(status, 400);
+
+    let (status, removed) = http(addr, "DELETE", "/tasks/done", "");
+    assert!(status == 200 && removed["removed"] == 1);
+
+    let mut incoming = ToDoList::new();
+    incoming.add_task("From dashboard", 3).unwrap();
+    let (status, report) = http(addr, "POST", "/merge", &incoming.to_json().unwrap());
+    assert!(status == 200 && report["added"].as_array().unwrap().len() == 1);
+    let (status, _) = http(addr, "POST", "/merge", r#"{"version":99}"#);
+    assert_eq!(status, 400);
+
+    let (status, _) = http(addr, "PUT", "/tasks", "");
+    assert_eq!(status, 405);
+    let (status, _) = http(addr, "GET", "/nowhere", "");
+    assert_eq!(status, 404);
+
+    let on_disk = ToDoList::load(&file).unwrap();
+    assert_eq!(on_disk.iter().count(), 2);
+    let _ = std::fs::remove_file(&file);
+}
+
+/// Requests a web page could send are refused: a form posted as text, or
+/// any request to a name that was rebound to the loopback address.
+#[test]
+fn cross_site_requests_are_refused() {
+    let file = temp_file("todo_api_test_origin.json");
+    let addr = start(&file);
+
+    let host = format!("Host: localhost:{}", addr.port());
+    let (status, _) = raw_http(
+        addr,
+        &format!(
+            "POST /tasks HTTP/1.1\r\n{}\r\nContent-Type: text/plain",
+            host
+        ),
+        r#"{"description":"Forged","priority":1}"#,
+    );
+    assert_eq!(status, 415);
+    let (status, _) = raw_http(
+        addr,
+        &format!("DELETE /tasks/done HTTP/1.1\r\n{}", host),
+        "",
+    );
+    assert_eq!(status, 200);
+    let (status, _) = raw_http(
+        addr,
+        &format!("GET /tasks HTTP/1.1\r\nHost: evil.example:{}", addr.port()),
+        "",
+    );
+    assert_eq!(status, 421);
+    let (status, _) = raw_http(addr, "GET /tasks HTTP/1.1", "");
+    assert_eq!(status, 421);
+    let (_, tasks) = http(addr, "GET", "/tasks", "");
+    assert!(tasks.as_array().unwrap().is_empty());
+}
+
+/// Many clients at once; every change reaches the file.
+#[test]
+fn concurrent_clients_all_reach_the_file() {
+    let file = temp_file("todo_api_test_clients.json");
+    let addr = start(&file);
+
+    let clients: Vec<_> = (0..8)
+        .map(|n| {
+            thread::spawn(move || {
+                let body = format!(
+                    r#"{{"description":"client {}","priority":{}}}"#,
+                    n,
+                    n % 5 + 1
+                );
+                http(addr, "POST", "/tasks", &body).0
+            })
+        })
+        .collect();
+    assert!(clients.into_iter().all(|c| c.join().unwrap() == 201));
+    let on_disk = ToDoList::load(&file).unwrap();
+    let (_, tasks) = http(addr, "GET", "/tasks", "");
+    assert!(on_disk.iter().count() == 8 && tasks.as_array().unwrap().len() == 8);
+    let _ = std::fs::remove_file(&file);
+}
+
+/// A change that cannot be saved is not kept, even when there is no file
+/// to read the list back from.
+#[test]
+fn changes_that_cannot_be_saved_are_dropped() {
+    let addr = start(&std::env::temp_dir().join("todo_api_missing_dir/list.json"));
+    let (status, _) = http(
+        addr,
+        "POST",
+        "/tasks",
+        r#"{"description":"Lost","priority":1}"#,
+    );
+    assert_eq!(status, 500);
+    let (_, tasks) = http(addr, "GET", "/tasks", "");
+    assert!(tasks.as_array().unwrap().is_empty());
+}
+
+/// Idle clients cannot take every thread: past the limit, connections are
+/// turned away until some close.
+#[test]
+fn connections_past_the_limit_are_turned_away() {
+    let file = temp_file("todo_api_test_limit.json");
+    let addr = start(&file);
+
+    let idle: Vec<_> = (0..32).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    assert_eq!(http(addr, "GET", "/tasks", "").0, 503);
+    drop(idle);
+    let served = (0..100).any(|_| {
+        thread::sleep(Duration::from_millis(10));
+        http(addr, "GET", "/tasks", "").0 == 200
+    });
+    assert!(served);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use exercises_day_5::to_do_list::{SharedToDoList, Task, ToDoList};
+
+use common::xorshift;
+
+#[test]
+fn shared_list_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedToDoList>();
+}
+
+/// Eight writers add and complete tasks while readers check that every
+/// snapshot is sorted; no task is lost and every ID is unique.
+#[test]
+fn concurrent_writers_keep_the_list_consistent() {
+    const WRITERS: u64 = 8;
+    const TASKS_PER_WRITER: u64 = 200;
+    let shared = SharedToDoList::new(ToDoList::new());
+    let sorted = |tasks: &[Task]| {
+        tasks
+            .windows(2)
+            .all(|pair| pair[0].priority() <= pair[1].priority())
+    };
+    let writing = AtomicBool::new(true);
+    let added: Vec<_> = std::thread::scope(|scope| {
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let shared = shared.clone();
+                scope.spawn(move || {
+                    let mut seed = writer * 7919 + 1;
+                    let mut ids = Vec::new();
+                    for n in 0..TASKS_PER_WRITER {
+                        let priority = xorshift(&mut seed, 5) as u8 + 1;
+                        let id = shared
+                            .add_task(&format!("w{} t{}", writer, n), priority)
+                            .unwrap();
+                        // Complete every other task, some through the write
+                        // guard to mix both ways of changing the list.
+                        if n % 4 == 1 {
+                            shared.mark_done_by_id(id).unwrap();
+                        } else if n % 4 == 3 {
+                            shared.write().mark_done_by_id(id).unwrap();
+                        }
+                        ids.push(id);
+                    }
+                    ids
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                let writing = &writing;
+                scope.spawn(move || {
+                    while writing.load(Ordering::Relaxed) {
+                        assert!(sorted(&shared.snapshot()));
+                    }
+                })
+            })
+            .collect();
+        let added: Vec<_> = writers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect();
+        writing.store(false, Ordering::Relaxed);
+        readers.into_iter().for_each(|r| r.join().unwrap());
+        added
+    });
+
+    let tasks = shared.snapshot();
+    assert!(tasks.len() as u64 == WRITERS * TASKS_PER_WRITER);
+    assert!(sorted(&tasks));
+    let ids: BTreeSet<_> = tasks.iter().map(|t| t.id()).collect();
+    assert!(ids.len() == tasks.len() && added.iter().all(|id| ids.contains(id)));
+    let done = tasks.iter().filter(|t| t.is_done()).count() as u64;
+    assert!(done == WRITERS * TASKS_PER_WRITER / 2);
+    assert!(shared.remove_done() == Ok(done as usize));
+    assert!(shared.snapshot().iter().all(|t| !t.is_done()));
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use exercises_day_5::to_do_list::{
+    FileStore, Filter, FixedClock, MemoryStore, Recurrence, SqliteStore, StoreChange, StoreError,
+    Task, TaskId, TaskStore, ToDoList,
+};
+
+/// Checks that `store`, which must be empty, behaves as `TaskStore` says,
+/// on its own and under a ToDoList. `reopen` closes the store and opens it
+/// again from wherever it keeps the tasks.
+fn check_store<S: TaskStore>(mut store: S, reopen: impl Fn(S) -> S) {
+    assert!(store.query(None).unwrap().is_empty());
+    assert!(store.next_id().unwrap() == 1);
+
+    // Tasks with every field set, from a list in memory.
+    let clock = Arc::new(FixedClock::new(
+        Utc.with_ymd_and_hms(2025, 5, 1, 8, 30, 15).unwrap(),
+    ));
+    let mut source = ToDoList::new().with_clock(clock.clone());
+    let plants = source.add_task("Water plants", 3).unwrap();
+    source.add_tag(plants, "home").unwrap();
+    source
+        .set_due(plants, NaiveDate::from_ymd_opt(2025, 5, 3))
+        .unwrap();
+    source
+        .set_recurrence(plants, Some("FREQ=WEEKLY;BYDAY=SA".parse().unwrap()))
+        .unwrap();
+    clock.advance(chrono::Duration::milliseconds(1500));
+    let fertilize = source.add_subtask(plants, "Fertilize", 4).unwrap();
+    source.mark_done_by_id(fertilize).unwrap();
+    let tea = source.add_task("Buy tea", 1).unwrap();
+    let task = |list: &ToDoList, id| -> Task { list.get(id).unwrap().clone() };
+    let (plants_task, fertilize_task, tea_task) = (
+        task(&source, plants),
+        task(&source, fertilize),
+        task(&source, tea),
+    );
+
+    store.insert(&plants_task).unwrap();
+    store.insert(&fertilize_task).unwrap();
+    assert!(store.query(None).unwrap() == [plants_task.clone(), fertilize_task.clone()]);
+    assert!(store.next_id().unwrap() == 3);
+    assert!(matches!(store.insert(&plants_task), Err(StoreError::Duplicate(id)) if id == plants));
+    assert!(matches!(store.update(&tea_task), Err(StoreError::NotFound(id)) if id == tea));
+
+    source.edit_description(plants, "Water the plants").unwrap();
+    let renamed = task(&source, plants);
+    store.update(&renamed).unwrap();
+    let home = Filter::parse("tag:home").unwrap();
+    assert!(store.query(Some(&home)).unwrap() == [renamed.clone()]);
+
+    // A batch that fails halfway changes nothing.
+    let batch = [
+        StoreChange::Insert(tea_task.clone()),
+        StoreChange::Delete(tea),
+    ];
+    store.apply(&batch[..1]).unwrap();
+    store.delete(tea).unwrap();
+    let failing = [
+        StoreChange::Insert(tea_task.clone()),
+        StoreChange::Delete(fertilize),
+        StoreChange::Delete(fertilize),
+    ];
+    assert!(matches!(store.apply(&failing), Err(StoreError::NotFound(id)) if id == fertilize));
+    assert!(store.query(None).unwrap() == [renamed.clone(), fertilize_task.clone()]);
+    assert!(matches!(store.delete(tea), Err(StoreError::NotFound(_))));
+    store.apply(&[StoreChange::NextId(10)]).unwrap();
+
+    // Everything survives closing the store.
+    let mut store = reopen(store);
+    assert!(store.query(None).unwrap() == [renamed.clone(), fertilize_task.clone()]);
+    assert!(store.next_id().unwrap() == 10);
+    store.delete(plants).unwrap();
+    store.delete(fertilize).unwrap();
+
+    // A list writes every operation through, undo included.
+    let mut list = ToDoList::open(store).unwrap();
+    assert!(list.iter().count() == 0);
+    let milk = list.add_task("Buy milk", 2).unwrap();
+    assert!(milk == TaskId::from(10));
+    let bread = list.add_task("Buy bread", 2).unwrap();
+    list.add_tag(bread, "shop").unwrap();
+    list.mark_done_by_id(milk).unwrap();
+    list.remove_done().unwrap();
+    list.undo();
+    list.set_recurrence(bread, Some(Recurrence::parse("FREQ=DAILY").unwrap()))
+        .unwrap();
+    assert!(list.is_synced());
+    let mut expected: Vec<Task> = list.iter().cloned().collect();
+    expected.sort_by_key(|task| task.id());
+    let store = reopen(list.into_store().unwrap());
+    assert!(store.query(None).unwrap() == expected);
+    let mut list = ToDoList::open(store).unwrap();
+    list.remove_done().unwrap();
+    // IDs are never given twice, even after their task is gone.
+    assert!(list.add_task("Buy eggs", 2).unwrap() == TaskId::from(12));
+}
+
+#[test]
+fn memory_store() {
+    check_store(MemoryStore::new(), |store| store);
+}
+
+#[test]
+fn file_store() {
+    let file = std::env::temp_dir().join("exercises-day-5-store-test.json");
+    let _ = std::fs::remove_file(&file);
+    check_store(FileStore::open(&file).unwrap(), |store| {
+        let path = store.path().to_path_buf();
+        drop(store);
+        FileStore::open(path).unwrap()
+    });
+    // The file is a list like `save` writes.
+    let saved = ToDoList::load(&file).unwrap();
+    assert_eq!(saved.iter().count(), 2);
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn sqlite_store() {
+    let db = std::env::temp_dir().join("exercises-day-5-store-test.db");
+    let _ = std::fs::remove_file(&db);
+    check_store(SqliteStore::open(&db).unwrap(), |store| {
+        drop(store);
+        SqliteStore::open(&db).unwrap()
+    });
+    let _ = std::fs::remove_file(&db);
+}
+
+#[test]
+fn sqlite_store_in_memory() {
+    check_store(SqliteStore::open_in_memory().unwrap(), |store| store);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use chrono::NaiveDate;
+use exercises_day_5::to_do_list::{Task, TaskId, ToDoList};
+
+use common::xorshift;
+
+/// Any sequence of operations followed by the same number of undos gives
+/// back the original list, and redoing them all gives back the final one.
+#[test]
+fn undoing_every_step_restores_the_list() {
+    let mut another_list = ToDoList::new();
+    another_list.add_task("Task1", 1).ok();
+    another_list.add_task("Task2", 2).ok();
+
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move |n: usize| xorshift(&mut seed, n);
+    for _ in 0..200 {
+        let mut list = ToDoList::new();
+        for i in 0..random(6) {
+            list.add_task(&format!("Start{}", i), (random(5) + 1) as u8)
+                .ok();
+        }
+        list.clear_history();
+        let original: Vec<Task> = list.iter().cloned().collect();
+
+        let steps = random(12) + 1;
+        for _ in 0..steps {
+            let ids: Vec<_> = list.iter().map(|t| t.id()).collect();
+            let id = if ids.is_empty() {
+                TaskId::from(0)
+            } else {
+                ids[random(ids.len())]
+            };
+            let priority = random(7) as u8;
+            let _ = match random(9) {
+                0 => list.add_task("New", priority).map(|_| ()),
+                1 => list.mark_done_by_id(id).map(|_| ()),
+                2 => list.remove_done().map(|_| ()),
+                3 => {
+                    list.merge(&another_list);
+                    Ok(())
+                }
+                4 => list.set_priority(id, priority),
+                5 => list.edit_description(id, "Edited"),
+                6 => list.remove(id).map(|_| ()),
+                7 => list.add_tag(id, "tag"),
+                _ => list.set_due(id, Some(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap())),
+            };
+        }
+        let last: Vec<Task> = list.iter().cloned().collect();
+
+        for _ in 0..steps {
+            list.undo();
+        }
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            original.iter().collect::<Vec<_>>()
+        );
+        while list.redo().is_some() {}
+        assert_eq!(
+            list.iter().collect::<Vec<_>>(),
+            last.iter().collect::<Vec<_>>()
+        );
+    }
+}